@@ -0,0 +1,414 @@
+//! System-wide key remapping daemon.
+//!
+//! Grabs the keyboards exclusively, runs every event through a tap-hold stage and a
+//! `Remapper`, and re-emits the result through a uinput virtual keyboard, so the remap applies
+//! to every program: X11, Wayland and the console alike.
+//!
+//! ```text
+//! keypress-remapd [--config FILE] [--device /dev/input/eventN]...
+//! ```
+//!
+//! The configuration (default `/etc/keypress-remapd.toml`) takes the `Remapper` sections
+//! (`[keys]`, `[chords]`, `[layers.*]`) plus:
+//!
+//! ```toml
+//! [daemon]
+//! devices = ["/dev/input/by-id/usb-Example_Keyboard-event-kbd"]  # default: every keyboard
+//! abort = ["Escape", "Backspace", "Enter"]                       # held together: exit
+//! hold_timeout_ms = 200
+//!
+//! [tap_hold]
+//! CapsLock = { tap = "Escape", hold = "ControlLeft" }
+//! ```
+//!
+//! Keys the remapping does not know, such as media keys, are passed through unchanged.
+//!
+//! SIGHUP reloads the configuration and reopens the keyboards, picking up changes to
+//! `daemon.devices` and keyboards plugged in since; a broken file or a device that fails to
+//! open is reported and the old setup kept. Keys held during a reload are released on the
+//! virtual keyboard and ignored until they come up. The abort chord is checked on the physical keys
+//! before any remapping, so a bad configuration can always be escaped.
+
+#[cfg(target_os = "linux")]
+fn main() {
+    if let Err(err) = daemon::run() {
+        eprintln!("keypress-remapd: {}", err);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn main() {
+    eprintln!("keypress-remapd: only supported on Linux");
+    std::process::exit(1);
+}
+
+#[cfg(target_os = "linux")]
+mod daemon {
+    use keypress::grab::{Grab, GrabEvent};
+    use keypress::injector::Injector;
+    use keypress::{Key, KeyEvent, KeyEventKind, Modifiers, Remapper};
+    use std::collections::{HashMap, HashSet};
+    use std::io;
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::{Duration, Instant};
+
+    const DEFAULT_CONFIG: &str = "/etc/keypress-remapd.toml";
+    const DEFAULT_ABORT: [Key; 3] = [Key::Escape, Key::Backspace, Key::Enter];
+    const DEFAULT_HOLD_TIMEOUT: Duration = Duration::from_millis(200);
+    /// How often the loop wakes up to notice a reload request.
+    const POLL: Duration = Duration::from_millis(250);
+    /// How long to wait for keys held at startup (the Enter that launched us) to be released.
+    const SETTLE: Duration = Duration::from_secs(2);
+
+    static RELOAD: AtomicBool = AtomicBool::new(false);
+
+    extern "C" fn request_reload(_: libc::c_int) {
+        RELOAD.store(true, Ordering::SeqCst);
+    }
+
+    struct Args {
+        config: PathBuf,
+        devices: Vec<PathBuf>,
+    }
+
+    impl Args {
+        fn parse() -> io::Result<Args> {
+            let mut args = Args { config: PathBuf::from(DEFAULT_CONFIG), devices: Vec::new() };
+            let mut argv = std::env::args().skip(1);
+            while let Some(arg) = argv.next() {
+                let mut value = |name: &str| {
+                    argv.next().ok_or_else(|| invalid(format!("{} needs a value", name)))
+                };
+                match arg.as_str() {
+                    "-c" | "--config" => args.config = value("--config")?.into(),
+                    "-d" | "--device" => args.devices.push(value("--device")?.into()),
+                    "-h" | "--help" => {
+                        println!("usage: keypress-remapd [--config FILE] [--device /dev/input/eventN]...");
+                        std::process::exit(0);
+                    }
+                    _ => return Err(invalid(format!("unexpected argument {:?}", arg))),
+                }
+            }
+            Ok(args)
+        }
+
+        /// The devices to read: those on the command line, else those in `config`; none
+        /// means every keyboard.
+        fn devices_or<'a>(&'a self, config: &'a Config) -> &'a [PathBuf] {
+            if self.devices.is_empty() {
+                &config.devices
+            } else {
+                &self.devices
+            }
+        }
+    }
+
+    struct Config {
+        devices: Vec<PathBuf>,
+        abort: Vec<Key>,
+        tap_hold: TapHold,
+        remapper: Remapper,
+    }
+
+    impl Config {
+        fn load(path: &PathBuf) -> io::Result<Config> {
+            let text = std::fs::read_to_string(path)
+                .map_err(|err| io::Error::new(err.kind(), format!("{}: {}", path.display(), err)))?;
+            let mut table: toml::Table = text.parse().map_err(|err| invalid(format!("{}: {}", path.display(), err)))?;
+
+            let daemon = table.remove("daemon").unwrap_or_else(|| toml::Value::Table(toml::Table::new()));
+            let daemon = daemon.as_table().ok_or_else(|| invalid("[daemon] must be a table".into()))?;
+            let devices = match daemon.get("devices") {
+                Some(list) => strings(list, "daemon.devices")?.into_iter().map(PathBuf::from).collect(),
+                None => Vec::new(),
+            };
+            let abort = match daemon.get("abort") {
+                Some(list) => strings(list, "daemon.abort")?.into_iter().map(|name| key(&name)).collect::<io::Result<_>>()?,
+                None => DEFAULT_ABORT.to_vec(),
+            };
+            if abort.is_empty() {
+                return Err(invalid("daemon.abort must name at least one key".into()));
+            }
+            let timeout = match daemon.get("hold_timeout_ms") {
+                Some(ms) => Duration::from_millis(
+                    ms.as_integer().filter(|&ms| ms >= 0).ok_or_else(|| invalid("daemon.hold_timeout_ms must be a number".into()))? as u64,
+                ),
+                None => DEFAULT_HOLD_TIMEOUT,
+            };
+
+            let mut tap_hold = TapHold::new(timeout);
+            if let Some(bindings) = table.remove("tap_hold") {
+                let bindings = bindings.as_table().ok_or_else(|| invalid("[tap_hold] must be a table".into()))?;
+                for (name, binding) in bindings {
+                    let field = |field: &str| -> io::Result<Key> {
+                        let value = binding.get(field).and_then(|v| v.as_str());
+                        key(value.ok_or_else(|| invalid(format!("tap_hold.{} needs a {:?} key", name, field)))?)
+                    };
+                    tap_hold.bindings.insert(key(name)?, (field("tap")?, field("hold")?));
+                }
+            }
+
+            let remapper = Remapper::from_toml(&toml::to_string(&table).map_err(|err| invalid(err.to_string()))?)?;
+            Ok(Config { devices, abort, tap_hold, remapper })
+        }
+    }
+
+    /// Keys that act as one key when tapped and another when held, like QMK's mod-tap.
+    ///
+    /// The press is held back until the key is released (a tap), another key is pressed or
+    /// the timeout passes (both a hold).
+    struct TapHold {
+        bindings: HashMap<Key, (Key, Key)>,
+        timeout: Duration,
+        pending: Option<(KeyEvent, Instant)>,
+        holding: HashMap<Key, Key>,
+    }
+
+    impl TapHold {
+        fn new(timeout: Duration) -> TapHold {
+            TapHold { bindings: HashMap::new(), timeout, pending: None, holding: HashMap::new() }
+        }
+
+        fn deadline(&self) -> Option<Instant> {
+            self.pending.map(|(_, since)| since + self.timeout)
+        }
+
+        /// Turns a pending press into a hold once its timeout has passed.
+        fn expire(&mut self, now: Instant) -> Vec<KeyEvent> {
+            match self.deadline() {
+                Some(deadline) if deadline <= now => self.resolve_hold().into_iter().collect(),
+                _ => Vec::new(),
+            }
+        }
+
+        fn resolve_hold(&mut self) -> Option<KeyEvent> {
+            let (press, _) = self.pending.take()?;
+            let (_, hold) = self.bindings[&press.key];
+            self.holding.insert(press.key, hold);
+            Some(KeyEvent { key: hold, ..press })
+        }
+
+        fn process(&mut self, event: KeyEvent) -> Vec<KeyEvent> {
+            let mut out = Vec::new();
+            match event.kind {
+                KeyEventKind::Press => {
+                    // Another press decides a pending key is held, even another tap-hold key.
+                    out.extend(self.resolve_hold());
+                    if self.bindings.contains_key(&event.key) {
+                        self.pending = Some((event, Instant::now()));
+                    } else {
+                        out.push(event);
+                    }
+                }
+                KeyEventKind::Repeat => {
+                    let pending = self.pending.is_some_and(|(press, _)| press.key == event.key);
+                    if !pending && !self.holding.contains_key(&event.key) {
+                        out.push(event);
+                    }
+                }
+                KeyEventKind::Release => match self.pending {
+                    Some((press, _)) if press.key == event.key => {
+                        self.pending = None;
+                        let (tap, _) = self.bindings[&press.key];
+                        out.push(KeyEvent { key: tap, ..press });
+                        out.push(KeyEvent { key: tap, ..event });
+                    }
+                    _ => match self.holding.remove(&event.key) {
+                        Some(hold) => out.push(KeyEvent { key: hold, ..event }),
+                        None => out.push(event),
+                    },
+                },
+            }
+            out
+        }
+    }
+
+    pub fn run() -> io::Result<()> {
+        let args = Args::parse()?;
+        let mut config = Config::load(&args.config)?;
+
+        // Open the keyboards before creating the virtual one, so it is never read back.
+        let mut input = Grab::open(args.devices_or(&config), &[])?;
+        let settle = Instant::now() + SETTLE;
+        while !input.pressed_keys()?.is_empty() && Instant::now() < settle {
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        input.grab()?;
+        let mut output = Injector::with_name("keypress-remapd virtual keyboard")?;
+        let own = output.device_nodes()?;
+
+        unsafe {
+            libc::signal(libc::SIGHUP, request_reload as extern "C" fn(libc::c_int) as *const () as libc::sighandler_t);
+        }
+
+        let mut physical = HashSet::new();
+        // Keys held through a reload: their presses went out under the old configuration and
+        // `release_all` has let go of them, so the rest of their events are dropped.
+        let mut stale = HashSet::new();
+        // Keys without a `Key` variant held down through the virtual keyboard.
+        let mut passed = HashSet::new();
+        loop {
+            if RELOAD.swap(false, Ordering::SeqCst) {
+                match Config::load(&args.config).and_then(|new| Ok((Grab::open(args.devices_or(&new), &own)?, new))) {
+                    Ok((new_input, new)) => {
+                        output.release_all()?;
+                        for code in passed.drain() {
+                            output.send_code(code, KeyEventKind::Release)?;
+                        }
+                        // A device can only be grabbed once, so the old grab goes first.
+                        drop(std::mem::replace(&mut input, new_input));
+                        input.grab()?;
+                        physical = input.pressed_keys()?.into_iter().collect();
+                        stale = physical.clone();
+                        // A pending or held tap-hold key is one of them, so its state goes too.
+                        config = new;
+                        eprintln!("keypress-remapd: reloaded {}", args.config.display());
+                    }
+                    Err(err) => eprintln!("keypress-remapd: keeping previous configuration: {}", err),
+                }
+            }
+
+            let timeout = match config.tap_hold.deadline() {
+                Some(deadline) => deadline.saturating_duration_since(Instant::now()).min(POLL),
+                None => POLL,
+            };
+            let event = input.next_event(Some(timeout))?;
+            let mut staged = config.tap_hold.expire(Instant::now());
+            match event {
+                Some(GrabEvent::Key(event)) => {
+                    match event.kind {
+                        KeyEventKind::Press => {
+                            physical.insert(event.key);
+                        }
+                        KeyEventKind::Release => {
+                            physical.remove(&event.key);
+                        }
+                        KeyEventKind::Repeat => {}
+                    }
+                    if config.abort.iter().all(|key| physical.contains(key)) {
+                        eprintln!("keypress-remapd: abort chord pressed, exiting");
+                        return output.release_all();
+                    }
+                    let dropped = match event.kind {
+                        KeyEventKind::Press => {
+                            stale.remove(&event.key);
+                            false
+                        }
+                        KeyEventKind::Repeat => stale.contains(&event.key),
+                        KeyEventKind::Release => stale.remove(&event.key),
+                    };
+                    if !dropped {
+                        staged.extend(config.tap_hold.process(event));
+                    }
+                }
+                Some(GrabEvent::Unmapped { code, kind, .. }) => {
+                    // Like any other key, a press decides a pending tap-hold key is held.
+                    if kind == KeyEventKind::Press {
+                        staged.extend(config.tap_hold.resolve_hold());
+                    }
+                    for event in staged.drain(..) {
+                        if let Some(event) = config.remapper.process(event) {
+                            emit(&mut output, event)?;
+                        }
+                    }
+                    match kind {
+                        KeyEventKind::Press => {
+                            passed.insert(code);
+                        }
+                        KeyEventKind::Release => {
+                            passed.remove(&code);
+                        }
+                        KeyEventKind::Repeat => {}
+                    }
+                    output.send_code(code, kind)?;
+                }
+                None => {}
+            }
+            for event in staged {
+                if let Some(event) = config.remapper.process(event) {
+                    emit(&mut output, event)?;
+                }
+            }
+        }
+    }
+
+    /// Sends `event`, adjusting the virtual keyboard's modifiers for the press or repeat when a
+    /// remap changed them.
+    fn emit(output: &mut Injector, event: KeyEvent) -> io::Result<()> {
+        if event.kind == KeyEventKind::Release {
+            return output.release(event.key);
+        }
+        if is_modifier(event.key) {
+            return match event.kind {
+                KeyEventKind::Press => output.press(event.key),
+                _ => output.repeat(event.key),
+            };
+        }
+
+        let want = event.modifiers();
+        let mut lifted = Vec::new();
+        let mut added = Vec::new();
+        for (flag, keys) in [
+            (Modifiers::SHIFT, [Key::ShiftLeft, Key::ShiftRight]),
+            (Modifiers::CTRL, [Key::ControlLeft, Key::ControlRight]),
+            (Modifiers::ALT, [Key::AltLeft, Key::AltRight]),
+            (Modifiers::SUPER, [Key::MetaLeft, Key::MetaRight]),
+        ] {
+            let held: Vec<Key> = output.held().iter().copied().filter(|k| keys.contains(k)).collect();
+            if want.contains(flag) && held.is_empty() {
+                added.push(keys[0]);
+            } else if !want.contains(flag) {
+                lifted.extend(held);
+            }
+        }
+
+        for &key in &lifted {
+            output.release(key)?;
+        }
+        for &key in &added {
+            output.press(key)?;
+        }
+        match event.kind {
+            KeyEventKind::Press => output.press(event.key)?,
+            _ => output.repeat(event.key)?,
+        }
+        for &key in added.iter().rev() {
+            output.release(key)?;
+        }
+        for &key in &lifted {
+            output.press(key)?;
+        }
+        Ok(())
+    }
+
+    fn is_modifier(key: Key) -> bool {
+        matches!(
+            key,
+            Key::ShiftLeft
+                | Key::ShiftRight
+                | Key::ControlLeft
+                | Key::ControlRight
+                | Key::AltLeft
+                | Key::AltRight
+                | Key::MetaLeft
+                | Key::MetaRight
+        )
+    }
+
+    fn strings(value: &toml::Value, what: &str) -> io::Result<Vec<String>> {
+        value
+            .as_array()
+            .and_then(|items| items.iter().map(|item| item.as_str().map(String::from)).collect())
+            .ok_or_else(|| invalid(format!("{} must be a list of strings", what)))
+    }
+
+    fn key(name: &str) -> io::Result<Key> {
+        name.parse().map_err(|err: keypress::ParseKeyError| invalid(err.to_string()))
+    }
+
+    fn invalid(msg: String) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, msg)
+    }
+}
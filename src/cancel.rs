@@ -0,0 +1,55 @@
+use crate::platform::waker::Waker;
+use crate::Error;
+use std::fmt;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Aborts a blocking wait from another thread.
+///
+/// Once cancelled, every wait using this handle (current or future) fails with
+/// `Error::Cancelled` after restoring terminal state.
+#[derive(Clone)]
+pub struct CancelHandle {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    cancelled: AtomicBool,
+    waker: Waker,
+}
+
+impl CancelHandle {
+    pub fn new() -> io::Result<Self> {
+        Ok(CancelHandle {
+            inner: Arc::new(Inner { cancelled: AtomicBool::new(false), waker: Waker::new()? }),
+        })
+    }
+
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+        self.inner.waker.wake();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn waker(&self) -> &Waker {
+        &self.inner.waker
+    }
+
+    pub(crate) fn check(&self) -> io::Result<()> {
+        if self.is_cancelled() {
+            Err(Error::Cancelled.into())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl fmt::Debug for CancelHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CancelHandle").field("cancelled", &self.is_cancelled()).finish()
+    }
+}
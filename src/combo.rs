@@ -0,0 +1,328 @@
+use crate::{Key, KeyEvent, KeyEventKind, Timestamp};
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Output of a `ComboDetector`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ComboEvent<A> {
+    /// Every key of a combo went down within the window, in any order. Their presses,
+    /// repeats and releases are suppressed.
+    Combo { action: A, timestamp: Timestamp },
+    /// The last key of a fired combo was released.
+    Released { action: A, timestamp: Timestamp },
+    /// A key event that is not part of a combo, passed on unchanged. Presses held back while
+    /// a combo was possible come out late, but in their original order and with their
+    /// original timestamps.
+    Key(KeyEvent),
+}
+
+/// Detects keys pressed together, such as J+K for Escape or Ctrl+Alt+Delete.
+///
+/// A press of a key that belongs to a combo is held back until the combo completes, the
+/// window (50ms by default) passes since the first held-back press, another key is pressed
+/// or a held-back key is released. When a combo has a larger combo containing it, it fires
+/// only once the window rules the larger one out.
+///
+/// Like `GestureDetector`, it takes time from the event timestamps: call `feed` for every
+/// event and `advance` once `deadline` passes, or use `Session::next_combo`.
+///
+/// ```no_run
+/// use keypress::{Backend, ComboDetector, ComboEvent, Key, Session};
+///
+/// let mut session = Session::open(Backend::Auto)?;
+/// let mut combos = ComboDetector::new().combo([Key::KeyJ, Key::KeyK], "escape");
+/// loop {
+///     match session.next_combo(&mut combos)? {
+///         ComboEvent::Combo { action: "escape", .. } => break,
+///         ComboEvent::Key(event) => println!("{:?}", event),
+///         _ => {}
+///     }
+/// }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct ComboDetector<A> {
+    combos: Vec<(Vec<Key>, A)>,
+    window: Duration,
+    /// Held-back presses, oldest first.
+    pending: Vec<KeyEvent>,
+    /// Fired combos and which of their keys are still down.
+    fired: Vec<(usize, Vec<Key>)>,
+    ready: VecDeque<ComboEvent<A>>,
+}
+
+impl<A> Default for ComboDetector<A> {
+    fn default() -> Self {
+        ComboDetector {
+            combos: Vec::new(),
+            window: Duration::from_millis(50),
+            pending: Vec::new(),
+            fired: Vec::new(),
+            ready: VecDeque::new(),
+        }
+    }
+}
+
+impl<A: Clone> ComboDetector<A> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reports `action` when all of `keys` are pressed together.
+    pub fn combo(mut self, keys: impl IntoIterator<Item = Key>, action: A) -> Self {
+        let mut keys: Vec<Key> = keys.into_iter().collect();
+        keys.sort_by_key(|key| key_index(*key));
+        keys.dedup();
+        self.combos.push((keys, action));
+        self
+    }
+
+    /// How close together the presses of a combo must be.
+    pub fn window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Processes one event, returning what it completes in order.
+    pub fn feed(&mut self, event: &KeyEvent) -> Vec<ComboEvent<A>> {
+        self.push(event);
+        self.ready.drain(..).collect()
+    }
+
+    /// Resolves held-back presses whose window has passed by the monotonic time `now`.
+    pub fn advance(&mut self, now: Duration) -> Vec<ComboEvent<A>> {
+        self.expire(now);
+        self.ready.drain(..).collect()
+    }
+
+    /// The monotonic time at which `advance` will next have something to report.
+    pub fn deadline(&self) -> Option<Duration> {
+        self.pending.first().and_then(|first| first.timestamp.monotonic.checked_add(self.window))
+    }
+
+    /// Forgets held-back presses and fired combos, dropping the held-back presses.
+    pub fn reset(&mut self) {
+        self.pending.clear();
+        self.fired.clear();
+        self.ready.clear();
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<ComboEvent<A>> {
+        self.ready.pop_front()
+    }
+
+    pub(crate) fn push(&mut self, event: &KeyEvent) {
+        self.expire(event.timestamp.monotonic);
+        let held_back = self.pending.iter().any(|p| p.key == event.key);
+        match event.kind {
+            KeyEventKind::Press if !held_back && self.participates(event.key) && !self.suppressed(event.key) => {
+                self.pending.push(*event);
+                self.evaluate();
+                return;
+            }
+            KeyEventKind::Repeat if held_back => return,
+            _ => self.resolve(),
+        }
+
+        if let Some(i) = self.fired.iter().position(|(_, held)| held.contains(&event.key)) {
+            if event.kind == KeyEventKind::Repeat {
+                return;
+            }
+            self.fired[i].1.retain(|&key| key != event.key);
+            if self.fired[i].1.is_empty() {
+                let (combo, _) = self.fired.remove(i);
+                let action = self.combos[combo].1.clone();
+                self.ready.push_back(ComboEvent::Released { action, timestamp: event.timestamp });
+            }
+            if event.kind == KeyEventKind::Release {
+                return;
+            }
+        }
+        self.ready.push_back(ComboEvent::Key(*event));
+    }
+
+    pub(crate) fn expire(&mut self, now: Duration) {
+        if self.deadline().is_some_and(|deadline| deadline <= now) {
+            self.resolve();
+        }
+    }
+
+    fn participates(&self, key: Key) -> bool {
+        self.combos.iter().any(|(keys, _)| keys.contains(&key))
+    }
+
+    fn suppressed(&self, key: Key) -> bool {
+        self.fired.iter().any(|(_, held)| held.contains(&key))
+    }
+
+    /// Combos containing every held-back key.
+    fn candidates(&self) -> impl Iterator<Item = usize> + '_ {
+        self.combos
+            .iter()
+            .enumerate()
+            .filter(|(_, (keys, _))| self.pending.iter().all(|p| keys.contains(&p.key)))
+            .map(|(i, _)| i)
+    }
+
+    /// The combo made of exactly the held-back keys.
+    fn exact(&self) -> Option<usize> {
+        self.candidates().find(|&i| self.combos[i].0.len() == self.pending.len())
+    }
+
+    /// After a new press: fires a complete combo nothing larger could still extend, and lets
+    /// go of the oldest presses that no combo can use any more.
+    fn evaluate(&mut self) {
+        while !self.pending.is_empty() && self.candidates().next().is_none() {
+            let event = self.pending.remove(0);
+            self.ready.push_back(ComboEvent::Key(event));
+        }
+        if let Some(combo) = self.exact() {
+            if self.candidates().all(|i| i == combo) {
+                self.fire(combo);
+            }
+        }
+    }
+
+    /// Decides the held-back presses now: a complete combo fires, anything else goes out as
+    /// normal keys.
+    fn resolve(&mut self) {
+        while !self.pending.is_empty() {
+            match self.exact() {
+                Some(combo) => self.fire(combo),
+                None => {
+                    let event = self.pending.remove(0);
+                    self.ready.push_back(ComboEvent::Key(event));
+                }
+            }
+        }
+    }
+
+    fn fire(&mut self, combo: usize) {
+        let timestamp = self.pending.last().map(|p| p.timestamp).unwrap_or_default();
+        self.pending.clear();
+        self.fired.push((combo, self.combos[combo].0.clone()));
+        let action = self.combos[combo].1.clone();
+        self.ready.push_back(ComboEvent::Combo { action, timestamp });
+    }
+}
+
+fn key_index(key: Key) -> usize {
+    Key::ALL.iter().position(|&k| k == key).unwrap_or(usize::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use KeyEventKind::{Press, Release, Repeat};
+
+    const MS: Duration = Duration::from_millis(1);
+
+    fn event(key: Key, kind: KeyEventKind, ms: u32) -> KeyEvent {
+        KeyEvent {
+            key,
+            kind,
+            shift: false,
+            ctrl: false,
+            alt: false,
+            meta: false,
+            right: crate::Modifiers::NONE,
+            timestamp: Timestamp { monotonic: MS * ms, wall: None, latency: None },
+            device: 0,
+        }
+    }
+
+    /// Outputs as `(what, key or action, ms)`, `what` being `press`, `release`, `repeat`,
+    /// `combo` or `end`.
+    fn describe(events: Vec<ComboEvent<&'static str>>) -> Vec<(&'static str, String, u32)> {
+        let ms = |t: Timestamp| t.monotonic.as_millis() as u32;
+        events
+            .into_iter()
+            .map(|e| match e {
+                ComboEvent::Combo { action, timestamp } => ("combo", action.to_string(), ms(timestamp)),
+                ComboEvent::Released { action, timestamp } => ("end", action.to_string(), ms(timestamp)),
+                ComboEvent::Key(e) => {
+                    let kind = match e.kind {
+                        KeyEventKind::Press => "press",
+                        KeyEventKind::Repeat => "repeat",
+                        KeyEventKind::Release => "release",
+                    };
+                    (kind, e.key.to_string(), ms(e.timestamp))
+                }
+            })
+            .collect()
+    }
+
+    fn feed(d: &mut ComboDetector<&'static str>, steps: &[(Key, KeyEventKind, u32)]) -> Vec<(&'static str, String, u32)> {
+        let mut out = Vec::new();
+        for &(key, kind, ms) in steps {
+            out.extend(d.feed(&event(key, kind, ms)));
+        }
+        describe(out)
+    }
+
+    fn jk() -> ComboDetector<&'static str> {
+        ComboDetector::new().combo([Key::KeyJ, Key::KeyK], "escape")
+    }
+
+    #[test]
+    fn fires_in_any_order_and_suppresses_the_keys() {
+        let mut d = jk();
+        assert_eq!(feed(&mut d, &[(Key::KeyK, Press, 0), (Key::KeyJ, Press, 30)]), [("combo", "escape".into(), 30)]);
+        assert_eq!(feed(&mut d, &[(Key::KeyJ, Repeat, 300), (Key::KeyK, Repeat, 300), (Key::KeyJ, Release, 400)]), []);
+        assert_eq!(feed(&mut d, &[(Key::KeyK, Release, 450)]), [("end", "escape".into(), 450)]);
+        assert_eq!(d.deadline(), None);
+    }
+
+    #[test]
+    fn presses_outside_the_window_come_out_late() {
+        let mut d = jk();
+        assert_eq!(feed(&mut d, &[(Key::KeyJ, Press, 0)]), []);
+        assert_eq!(d.deadline(), Some(MS * 50));
+        assert_eq!(describe(d.advance(MS * 49)), []);
+        assert_eq!(describe(d.advance(MS * 50)), [("press", "KeyJ".into(), 0)]);
+        // The window passing is also noticed by the next event.
+        let out = feed(&mut d, &[(Key::KeyK, Press, 100), (Key::KeyJ, Press, 160)]);
+        assert_eq!(out, [("press", "KeyK".into(), 100)]);
+        assert_eq!(describe(d.advance(MS * 210)), [("press", "KeyJ".into(), 160)]);
+    }
+
+    #[test]
+    fn other_keys_and_releases_break_a_combo() {
+        let mut d = jk();
+        let out = feed(&mut d, &[(Key::KeyJ, Press, 0), (Key::KeyX, Press, 10), (Key::KeyK, Press, 20)]);
+        assert_eq!(out, [("press", "KeyJ".into(), 0), ("press", "KeyX".into(), 10)]);
+        let out = feed(&mut d, &[(Key::KeyK, Repeat, 30), (Key::KeyK, Release, 40)]);
+        assert_eq!(out, [("press", "KeyK".into(), 20), ("release", "KeyK".into(), 40)]);
+    }
+
+    #[test]
+    fn partial_release_ends_only_with_the_last_key() {
+        let mut d = jk();
+        assert_eq!(feed(&mut d, &[(Key::KeyJ, Press, 0), (Key::KeyK, Press, 10)]).len(), 1);
+        assert_eq!(feed(&mut d, &[(Key::KeyJ, Release, 100)]), []);
+        // J again while K is still down is an ordinary press, not a new combo.
+        assert_eq!(feed(&mut d, &[(Key::KeyJ, Press, 120)]), []);
+        assert_eq!(describe(d.advance(MS * 170)), [("press", "KeyJ".into(), 120)]);
+        let out = feed(&mut d, &[(Key::KeyK, Release, 200), (Key::KeyJ, Release, 210)]);
+        assert_eq!(out, [("end", "escape".into(), 200), ("release", "KeyJ".into(), 210)]);
+    }
+
+    #[test]
+    fn larger_combos_win_within_the_window() {
+        let mut d = jk().combo([Key::KeyL, Key::KeyK, Key::KeyJ], "all");
+        assert_eq!(feed(&mut d, &[(Key::KeyJ, Press, 0), (Key::KeyK, Press, 10)]), []);
+        assert_eq!(feed(&mut d, &[(Key::KeyL, Press, 40)]), [("combo", "all".into(), 40)]);
+        assert_eq!(feed(&mut d, &[(Key::KeyJ, Release, 90), (Key::KeyK, Release, 90), (Key::KeyL, Release, 95)]), [("end", "all".into(), 95)]);
+
+        assert_eq!(feed(&mut d, &[(Key::KeyJ, Press, 200), (Key::KeyK, Press, 210)]), []);
+        assert_eq!(describe(d.advance(MS * 250)), [("combo", "escape".into(), 210)]);
+    }
+
+    #[test]
+    fn huge_windows_never_expire() {
+        let mut d = jk().window(Duration::MAX);
+        assert_eq!(feed(&mut d, &[(Key::KeyJ, Press, 10)]), []);
+        assert_eq!(d.deadline(), None);
+        assert_eq!(feed(&mut d, &[(Key::KeyK, Press, 1_000_000)]), [("combo", "escape".into(), 1_000_000)]);
+    }
+}
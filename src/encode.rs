@@ -0,0 +1,392 @@
+use crate::{Key, KeyEvent, KeyEventKind};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Terminal {
+    Xterm,
+    Vt220,
+    LinuxConsole,
+    Kitty,
+}
+
+/// Encodes `event` as the bytes `terminal` would send to the program reading it.
+///
+/// Keys the terminal does not transmit on their own (modifiers, lock keys) and releases
+/// yield an empty vector.
+pub fn encode(event: &KeyEvent, terminal: Terminal) -> Vec<u8> {
+    if event.kind == KeyEventKind::Release {
+        return Vec::new();
+    }
+    match terminal {
+        Terminal::Kitty => encode_kitty(event),
+        _ => encode_legacy(event, terminal),
+    }
+}
+
+fn modifier_param(event: &KeyEvent) -> u8 {
+    1 + event.shift as u8 + ((event.alt as u8) << 1) + ((event.ctrl as u8) << 2) + ((event.meta as u8) << 3)
+}
+
+fn has_mods(event: &KeyEvent) -> bool {
+    event.shift || event.ctrl || event.alt || event.meta
+}
+
+fn csi(body: &str) -> Vec<u8> {
+    format!("\x1b[{}", body).into_bytes()
+}
+
+fn ss3(final_byte: char) -> Vec<u8> {
+    format!("\x1bO{}", final_byte).into_bytes()
+}
+
+fn with_meta(alt: bool, mut bytes: Vec<u8>) -> Vec<u8> {
+    if alt && !bytes.is_empty() {
+        bytes.insert(0, 0x1b);
+    }
+    bytes
+}
+
+fn encode_legacy(event: &KeyEvent, terminal: Terminal) -> Vec<u8> {
+    let modified = terminal == Terminal::Xterm && has_mods(event);
+    let m = modifier_param(event);
+
+    let cursor = |final_byte: char| -> Vec<u8> {
+        if modified {
+            csi(&format!("1;{}{}", m, final_byte))
+        } else {
+            csi(&final_byte.to_string())
+        }
+    };
+    let tilde = |n: u8| -> Vec<u8> {
+        if modified {
+            csi(&format!("{};{}~", n, m))
+        } else {
+            csi(&format!("{}~", n))
+        }
+    };
+    let pf = |final_byte: char| -> Vec<u8> {
+        if modified {
+            csi(&format!("1;{}{}", m, final_byte))
+        } else {
+            ss3(final_byte)
+        }
+    };
+
+    match event.key {
+        Key::ArrowUp => cursor('A'),
+        Key::ArrowDown => cursor('B'),
+        Key::ArrowRight => cursor('C'),
+        Key::ArrowLeft => cursor('D'),
+        Key::Home => match terminal {
+            Terminal::Xterm => cursor('H'),
+            _ => tilde(1),
+        },
+        Key::End => match terminal {
+            Terminal::Xterm => cursor('F'),
+            _ => tilde(4),
+        },
+        Key::Insert => tilde(2),
+        Key::Delete => tilde(3),
+        Key::PageUp => tilde(5),
+        Key::PageDown => tilde(6),
+        Key::F1 | Key::F2 | Key::F3 | Key::F4 | Key::F5 if terminal == Terminal::LinuxConsole => {
+            let final_byte = match event.key {
+                Key::F1 => 'A',
+                Key::F2 => 'B',
+                Key::F3 => 'C',
+                Key::F4 => 'D',
+                _ => 'E',
+            };
+            csi(&format!("[{}", final_byte))
+        }
+        Key::F1 => pf('P'),
+        Key::F2 => pf('Q'),
+        Key::F3 => pf('R'),
+        Key::F4 => pf('S'),
+        Key::F5 => tilde(15),
+        Key::F6 => tilde(17),
+        Key::F7 => tilde(18),
+        Key::F8 => tilde(19),
+        Key::F9 => tilde(20),
+        Key::F10 => tilde(21),
+        Key::F11 => tilde(23),
+        Key::F12 => tilde(24),
+        Key::Escape => with_meta(event.alt, vec![0x1b]),
+        Key::Enter | Key::NumpadEnter => with_meta(event.alt, vec![b'\r']),
+        Key::Tab if event.shift && terminal != Terminal::LinuxConsole => with_meta(event.alt, csi("Z")),
+        Key::Tab => with_meta(event.alt, vec![b'\t']),
+        Key::Backspace if event.ctrl => with_meta(event.alt, vec![0x08]),
+        Key::Backspace => with_meta(event.alt, vec![0x7f]),
+        key => match control_byte(event) {
+            Some(byte) => with_meta(event.alt, vec![byte]),
+            None => match us_char(key, event.shift) {
+                Some(c) => with_meta(event.alt, c.to_string().into_bytes()),
+                None => Vec::new(),
+            },
+        },
+    }
+}
+
+fn control_byte(event: &KeyEvent) -> Option<u8> {
+    if !event.ctrl {
+        return None;
+    }
+    match event.key {
+        Key::Space | Key::Digit2 => Some(0x00),
+        Key::LeftBracket | Key::Digit3 => Some(0x1b),
+        Key::Backslash | Key::Digit4 => Some(0x1c),
+        Key::RightBracket | Key::Digit5 => Some(0x1d),
+        Key::Digit6 => Some(0x1e),
+        Key::Minus | Key::Slash | Key::Digit7 => Some(0x1f),
+        Key::Digit8 => Some(0x7f),
+        key => match us_char(key, false) {
+            Some(c) if c.is_ascii_lowercase() => Some(c as u8 - b'a' + 1),
+            _ => None,
+        },
+    }
+}
+
+fn encode_kitty(event: &KeyEvent) -> Vec<u8> {
+    let m = modifier_param(event);
+    let csi_u = |code: u32| -> Vec<u8> {
+        if has_mods(event) {
+            csi(&format!("{};{}u", code, m))
+        } else {
+            csi(&format!("{}u", code))
+        }
+    };
+
+    let keypad = match event.key {
+        Key::Numpad0 => Some(57399),
+        Key::Numpad1 => Some(57400),
+        Key::Numpad2 => Some(57401),
+        Key::Numpad3 => Some(57402),
+        Key::Numpad4 => Some(57403),
+        Key::Numpad5 => Some(57404),
+        Key::Numpad6 => Some(57405),
+        Key::Numpad7 => Some(57406),
+        Key::Numpad8 => Some(57407),
+        Key::Numpad9 => Some(57408),
+        Key::NumpadDecimal => Some(57409),
+        Key::NumpadDivide => Some(57410),
+        Key::NumpadMultiply => Some(57411),
+        Key::NumpadSubtract => Some(57412),
+        Key::NumpadAdd => Some(57413),
+        Key::NumpadEnter => Some(57414),
+        Key::PrintScreen => Some(57361),
+        Key::Pause => Some(57362),
+        _ => None,
+    };
+    if let Some(code) = keypad {
+        return csi_u(code);
+    }
+
+    match event.key {
+        Key::Escape => csi_u(27),
+        Key::Enter if has_mods(event) => csi_u(13),
+        Key::Tab if has_mods(event) => csi_u(9),
+        Key::Backspace if has_mods(event) => csi_u(127),
+        Key::Enter => vec![b'\r'],
+        Key::Tab => vec![b'\t'],
+        Key::Backspace => vec![0x7f],
+        Key::F3 if has_mods(event) => csi(&format!("13;{}~", m)),
+        key => match us_char(key, false) {
+            Some(c) if event.ctrl || event.alt || event.meta => csi_u(c as u32),
+            Some(_) => us_char(key, event.shift)
+                .map(|c| c.to_string().into_bytes())
+                .unwrap_or_default(),
+            None => encode_legacy(event, Terminal::Xterm),
+        },
+    }
+}
+
+pub(crate) fn us_char(key: Key, shift: bool) -> Option<char> {
+    let (plain, shifted) = match key {
+        Key::Grave => ('`', '~'),
+        Key::Digit1 => ('1', '!'),
+        Key::Digit2 => ('2', '@'),
+        Key::Digit3 => ('3', '#'),
+        Key::Digit4 => ('4', '$'),
+        Key::Digit5 => ('5', '%'),
+        Key::Digit6 => ('6', '^'),
+        Key::Digit7 => ('7', '&'),
+        Key::Digit8 => ('8', '*'),
+        Key::Digit9 => ('9', '('),
+        Key::Digit0 => ('0', ')'),
+        Key::Minus => ('-', '_'),
+        Key::Equal => ('=', '+'),
+        Key::KeyQ => ('q', 'Q'),
+        Key::KeyW => ('w', 'W'),
+        Key::KeyE => ('e', 'E'),
+        Key::KeyR => ('r', 'R'),
+        Key::KeyT => ('t', 'T'),
+        Key::KeyY => ('y', 'Y'),
+        Key::KeyU => ('u', 'U'),
+        Key::KeyI => ('i', 'I'),
+        Key::KeyO => ('o', 'O'),
+        Key::KeyP => ('p', 'P'),
+        Key::LeftBracket => ('[', '{'),
+        Key::RightBracket => (']', '}'),
+        Key::Backslash => ('\\', '|'),
+        Key::KeyA => ('a', 'A'),
+        Key::KeyS => ('s', 'S'),
+        Key::KeyD => ('d', 'D'),
+        Key::KeyF => ('f', 'F'),
+        Key::KeyG => ('g', 'G'),
+        Key::KeyH => ('h', 'H'),
+        Key::KeyJ => ('j', 'J'),
+        Key::KeyK => ('k', 'K'),
+        Key::KeyL => ('l', 'L'),
+        Key::Semicolon => (';', ':'),
+        Key::Quote => ('\'', '"'),
+        Key::KeyZ => ('z', 'Z'),
+        Key::KeyX => ('x', 'X'),
+        Key::KeyC => ('c', 'C'),
+        Key::KeyV => ('v', 'V'),
+        Key::KeyB => ('b', 'B'),
+        Key::KeyN => ('n', 'N'),
+        Key::KeyM => ('m', 'M'),
+        Key::Comma => (',', '<'),
+        Key::Period => ('.', '>'),
+        Key::Slash => ('/', '?'),
+        Key::Space => (' ', ' '),
+        Key::Numpad0 => ('0', '0'),
+        Key::Numpad1 => ('1', '1'),
+        Key::Numpad2 => ('2', '2'),
+        Key::Numpad3 => ('3', '3'),
+        Key::Numpad4 => ('4', '4'),
+        Key::Numpad5 => ('5', '5'),
+        Key::Numpad6 => ('6', '6'),
+        Key::Numpad7 => ('7', '7'),
+        Key::Numpad8 => ('8', '8'),
+        Key::Numpad9 => ('9', '9'),
+        Key::NumpadDivide => ('/', '/'),
+        Key::NumpadMultiply => ('*', '*'),
+        Key::NumpadSubtract => ('-', '-'),
+        Key::NumpadAdd => ('+', '+'),
+        Key::NumpadDecimal => ('.', '.'),
+        _ => return None,
+    };
+    Some(if shift { shifted } else { plain })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Modifiers, Timestamp};
+
+    fn check(terminal: Terminal, cases: &[(Key, Modifiers, &str)]) {
+        for &(key, mods, expected) in cases {
+            let event = KeyEvent {
+                key,
+                kind: KeyEventKind::Press,
+                shift: mods.contains(Modifiers::SHIFT),
+                ctrl: mods.contains(Modifiers::CTRL),
+                alt: mods.contains(Modifiers::ALT),
+                meta: mods.contains(Modifiers::SUPER),
+                right: Modifiers::NONE,
+                timestamp: Timestamp::default(),
+                device: 0,
+            };
+            let bytes = encode(&event, terminal);
+            assert_eq!(String::from_utf8_lossy(&bytes), expected, "{:?} {:?} {:?}", terminal, mods, key);
+            assert!(encode(&KeyEvent { kind: KeyEventKind::Release, ..event }, terminal).is_empty());
+        }
+    }
+
+    const NONE: Modifiers = Modifiers::NONE;
+    const SHIFT: Modifiers = Modifiers::SHIFT;
+    const CTRL: Modifiers = Modifiers::CTRL;
+    const ALT: Modifiers = Modifiers::ALT;
+    const SUPER: Modifiers = Modifiers::SUPER;
+
+    #[test]
+    fn xterm() {
+        check(
+            Terminal::Xterm,
+            &[
+                (Key::ArrowUp, NONE, "\x1b[A"),
+                (Key::ArrowUp, SHIFT, "\x1b[1;2A"),
+                (Key::ArrowLeft, CTRL | ALT, "\x1b[1;7D"),
+                (Key::ArrowDown, SUPER, "\x1b[1;9B"),
+                (Key::Home, NONE, "\x1b[H"),
+                (Key::End, CTRL, "\x1b[1;5F"),
+                (Key::Delete, NONE, "\x1b[3~"),
+                (Key::F1, NONE, "\x1bOP"),
+                (Key::F1, CTRL, "\x1b[1;5P"),
+                (Key::F5, NONE, "\x1b[15~"),
+                (Key::F5, SHIFT, "\x1b[15;2~"),
+                (Key::F12, SHIFT | CTRL | ALT | SUPER, "\x1b[24;16~"),
+                (Key::Tab, NONE, "\t"),
+                (Key::Tab, SHIFT, "\x1b[Z"),
+                (Key::Tab, SHIFT | ALT, "\x1b\x1b[Z"),
+                (Key::Enter, ALT, "\x1b\r"),
+                (Key::Backspace, NONE, "\x7f"),
+                (Key::Backspace, CTRL, "\x08"),
+                (Key::KeyC, CTRL, "\x03"),
+                (Key::Space, CTRL, "\0"),
+                (Key::KeyX, ALT, "\x1bx"),
+                (Key::KeyA, SHIFT, "A"),
+                (Key::Slash, SHIFT, "?"),
+                (Key::ShiftLeft, SHIFT, ""),
+                (Key::CapsLock, NONE, ""),
+            ],
+        );
+    }
+
+    #[test]
+    fn vt220() {
+        check(
+            Terminal::Vt220,
+            &[
+                (Key::ArrowUp, SHIFT, "\x1b[A"),
+                (Key::Home, NONE, "\x1b[1~"),
+                (Key::End, NONE, "\x1b[4~"),
+                (Key::F1, CTRL, "\x1bOP"),
+                (Key::F6, SHIFT, "\x1b[17~"),
+                (Key::Tab, SHIFT, "\x1b[Z"),
+                (Key::KeyQ, ALT, "\x1bq"),
+            ],
+        );
+    }
+
+    #[test]
+    fn linux_console() {
+        check(
+            Terminal::LinuxConsole,
+            &[
+                (Key::ArrowRight, CTRL, "\x1b[C"),
+                (Key::Home, NONE, "\x1b[1~"),
+                (Key::F1, NONE, "\x1b[[A"),
+                (Key::F5, NONE, "\x1b[[E"),
+                (Key::F6, NONE, "\x1b[17~"),
+                (Key::Tab, SHIFT, "\t"),
+                (Key::KeyZ, CTRL, "\x1a"),
+            ],
+        );
+    }
+
+    #[test]
+    fn kitty() {
+        check(
+            Terminal::Kitty,
+            &[
+                (Key::KeyA, NONE, "a"),
+                (Key::KeyA, SHIFT, "A"),
+                (Key::KeyA, CTRL, "\x1b[97;5u"),
+                (Key::KeyA, CTRL | SHIFT, "\x1b[97;6u"),
+                (Key::KeyA, ALT, "\x1b[97;3u"),
+                (Key::KeyA, SUPER, "\x1b[97;9u"),
+                (Key::Escape, NONE, "\x1b[27u"),
+                (Key::Enter, NONE, "\r"),
+                (Key::Enter, CTRL, "\x1b[13;5u"),
+                (Key::Enter, SUPER, "\x1b[13;9u"),
+                (Key::Tab, SHIFT, "\x1b[9;2u"),
+                (Key::Backspace, ALT, "\x1b[127;3u"),
+                (Key::Numpad1, NONE, "\x1b[57400u"),
+                (Key::F1, NONE, "\x1bOP"),
+                (Key::F3, CTRL, "\x1b[13;5~"),
+                (Key::ArrowUp, SHIFT, "\x1b[1;2A"),
+            ],
+        );
+    }
+}
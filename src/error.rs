@@ -0,0 +1,45 @@
+use std::fmt;
+use std::io;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Error {
+    /// There is no controlling terminal; as an `io::Error` its kind is `NotConnected`, so it is
+    /// not mistaken for a missing keyboard device (`NotFound`).
+    NotATerminal,
+    Cancelled,
+    /// The backend cannot answer this query, e.g. key state on the terminal backend.
+    Unsupported,
+}
+
+impl Error {
+    /// Recovers the keypress error carried by an `io::Error` returned from this crate.
+    pub fn of(err: &io::Error) -> Option<Error> {
+        err.get_ref().and_then(|inner| inner.downcast_ref::<Error>()).copied()
+    }
+
+    fn io_kind(self) -> io::ErrorKind {
+        match self {
+            Error::NotATerminal => io::ErrorKind::NotConnected,
+            Error::Cancelled => io::ErrorKind::Other,
+            Error::Unsupported => io::ErrorKind::Unsupported,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NotATerminal => write!(f, "no controlling terminal"),
+            Error::Cancelled => write!(f, "wait cancelled"),
+            Error::Unsupported => write!(f, "not supported by this backend"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<Error> for io::Error {
+    fn from(err: Error) -> io::Error {
+        io::Error::new(err.io_kind(), err)
+    }
+}
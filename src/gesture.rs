@@ -0,0 +1,339 @@
+use crate::{Key, KeyEvent, KeyEventKind, Timestamp};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::Duration;
+
+/// What a key did, as classified by a `GestureDetector`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Gesture {
+    /// Pressed and released within the hold threshold, with no other key pressed meanwhile.
+    Tap,
+    /// Tapped twice within the double-tap window.
+    DoubleTap,
+    /// Held past the hold threshold with no other key pressed; reported as soon as the
+    /// threshold passes, not on release.
+    Hold,
+    /// Still held when another key was pressed, like the hold side of QMK's mod-tap; the key
+    /// is the one pressed.
+    HoldWith(Key),
+    /// A key reported as `Hold` or `HoldWith` was released.
+    HoldEnd,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GestureEvent {
+    pub key: Key,
+    pub gesture: Gesture,
+    /// When the gesture was decided: the release for taps, the moment the threshold passed
+    /// or the other key went down for holds.
+    pub timestamp: Timestamp,
+}
+
+/// Classifies key activity into taps, holds and double-taps.
+///
+/// Feed it every event of a stream, with releases, through `feed`; because a hold is decided
+/// by time passing rather than by an event, also call `advance` when `deadline` passes, or let
+/// `Session::next_gesture` do both. Time is taken from the event timestamps, so recordings and
+/// scripts classify the same way as live input.
+///
+/// ```no_run
+/// use keypress::{Backend, Gesture, GestureDetector, Key, Session};
+/// use std::time::Duration;
+///
+/// let mut session = Session::open(Backend::Auto)?;
+/// let mut gestures = GestureDetector::new().keys([Key::Escape]).hold_threshold(Duration::from_millis(800));
+/// loop {
+///     let event = session.next_gesture(&mut gestures)?;
+///     if event.gesture == Gesture::Hold {
+///         break; // long-press Escape quits
+///     }
+/// }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct GestureDetector {
+    hold_threshold: Duration,
+    double_tap: Option<Duration>,
+    keys: Option<HashSet<Key>>,
+    states: HashMap<Key, State>,
+    ready: VecDeque<GestureEvent>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum State {
+    /// Pressed, not yet a tap or a hold; `after_tap` is the earlier tap of a possible double-tap.
+    Down { since: Timestamp, after_tap: Option<Timestamp> },
+    /// Reported as a hold; waiting for the release.
+    Holding,
+    /// Released as a tap; waiting to see whether a second tap follows.
+    Tapped { at: Timestamp },
+}
+
+impl Default for GestureDetector {
+    fn default() -> Self {
+        GestureDetector {
+            hold_threshold: Duration::from_millis(200),
+            double_tap: None,
+            keys: None,
+            states: HashMap::new(),
+            ready: VecDeque::new(),
+        }
+    }
+}
+
+impl GestureDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How long a key must stay down, alone, to count as held. Defaults to 200ms.
+    pub fn hold_threshold(mut self, threshold: Duration) -> Self {
+        self.hold_threshold = threshold;
+        self
+    }
+
+    /// Enables `DoubleTap`: a second tap starting within `window` of the first.
+    ///
+    /// Off by default, because taps are then reported only once the window has passed.
+    pub fn double_tap(mut self, window: Duration) -> Self {
+        self.double_tap = Some(window);
+        self
+    }
+
+    /// Classifies only these keys; by default every key is. Other keys still turn a held key
+    /// into `HoldWith`.
+    pub fn keys(mut self, keys: impl IntoIterator<Item = Key>) -> Self {
+        self.keys = Some(keys.into_iter().collect());
+        self
+    }
+
+    /// Classifies one event, returning the gestures it completes in order.
+    pub fn feed(&mut self, event: &KeyEvent) -> Vec<GestureEvent> {
+        self.push(event);
+        self.ready.drain(..).collect()
+    }
+
+    /// Reports the holds and taps decided by time passing, up to the monotonic time `now`.
+    pub fn advance(&mut self, now: Duration) -> Vec<GestureEvent> {
+        self.expire(now);
+        self.ready.drain(..).collect()
+    }
+
+    /// The monotonic time at which `advance` will next have something to report.
+    pub fn deadline(&self) -> Option<Duration> {
+        self.states.values().filter_map(|state| self.due(state)).min()
+    }
+
+    /// Forgets held keys and pending taps.
+    pub fn reset(&mut self) {
+        self.states.clear();
+        self.ready.clear();
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<GestureEvent> {
+        self.ready.pop_front()
+    }
+
+    pub(crate) fn push(&mut self, event: &KeyEvent) {
+        self.expire(event.timestamp.monotonic);
+        let key = event.key;
+        match event.kind {
+            KeyEventKind::Press => {
+                self.interrupt(key, event.timestamp);
+                if self.keys.as_ref().is_some_and(|keys| !keys.contains(&key)) {
+                    return;
+                }
+                let after_tap = match self.states.get(&key) {
+                    Some(&State::Tapped { at }) => Some(at),
+                    _ => None,
+                };
+                self.states.insert(key, State::Down { since: event.timestamp, after_tap });
+            }
+            KeyEventKind::Repeat => {}
+            KeyEventKind::Release => match self.states.remove(&key) {
+                Some(State::Down { after_tap: Some(_), .. }) => self.emit(key, Gesture::DoubleTap, event.timestamp),
+                Some(State::Down { after_tap: None, .. }) if self.double_tap.is_some() => {
+                    self.states.insert(key, State::Tapped { at: event.timestamp });
+                }
+                Some(State::Down { .. }) => self.emit(key, Gesture::Tap, event.timestamp),
+                Some(State::Holding) => self.emit(key, Gesture::HoldEnd, event.timestamp),
+                Some(tapped @ State::Tapped { .. }) => {
+                    self.states.insert(key, tapped);
+                }
+                None => {}
+            },
+        }
+    }
+
+    /// A press of `other` turns every undecided key into a hold and ends pending double-taps.
+    fn interrupt(&mut self, other: Key, time: Timestamp) {
+        let mut keys: Vec<(Key, State)> = self.states.iter().map(|(&k, &s)| (k, s)).collect();
+        keys.sort_by_key(|(_, state)| match state {
+            State::Down { since, .. } => since.monotonic,
+            State::Tapped { at } => at.monotonic,
+            State::Holding => Duration::MAX,
+        });
+        for (key, state) in keys {
+            match state {
+                State::Down { after_tap, .. } if key != other => {
+                    if let Some(at) = after_tap {
+                        self.emit(key, Gesture::Tap, at);
+                    }
+                    self.states.insert(key, State::Holding);
+                    self.emit(key, Gesture::HoldWith(other), time);
+                }
+                State::Tapped { at } if key != other => {
+                    self.states.remove(&key);
+                    self.emit(key, Gesture::Tap, at);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    pub(crate) fn expire(&mut self, now: Duration) {
+        while let Some(deadline) = self.deadline().filter(|&d| d <= now) {
+            let (&key, &state) = self
+                .states
+                .iter()
+                .find(|(_, state)| self.due(state) == Some(deadline))
+                .expect("deadline belongs to a key");
+            match state {
+                State::Down { since, after_tap } => {
+                    if let Some(at) = after_tap {
+                        self.emit(key, Gesture::Tap, at);
+                    }
+                    self.states.insert(key, State::Holding);
+                    self.emit(key, Gesture::Hold, later(since, self.hold_threshold));
+                }
+                State::Tapped { at } => {
+                    self.states.remove(&key);
+                    self.emit(key, Gesture::Tap, at);
+                }
+                State::Holding => {}
+            }
+        }
+    }
+
+    /// When time alone decides `state`; `None` for a hold, or a threshold or window too long to
+    /// ever pass.
+    fn due(&self, state: &State) -> Option<Duration> {
+        match *state {
+            State::Down { since, .. } => since.monotonic.checked_add(self.hold_threshold),
+            State::Tapped { at } => self.double_tap.and_then(|window| at.monotonic.checked_add(window)),
+            State::Holding => None,
+        }
+    }
+
+    fn emit(&mut self, key: Key, gesture: Gesture, timestamp: Timestamp) {
+        self.ready.push_back(GestureEvent { key, gesture, timestamp });
+    }
+}
+
+fn later(time: Timestamp, by: Duration) -> Timestamp {
+    Timestamp { monotonic: time.monotonic + by, wall: time.wall.and_then(|wall| wall.checked_add(by)), latency: None }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: Duration = Duration::from_millis(1);
+
+    fn event(key: Key, kind: KeyEventKind, ms: u32) -> KeyEvent {
+        KeyEvent {
+            key,
+            kind,
+            shift: false,
+            ctrl: false,
+            alt: false,
+            meta: false,
+            right: crate::Modifiers::NONE,
+            timestamp: Timestamp { monotonic: MS * ms, wall: None, latency: None },
+            device: 0,
+        }
+    }
+
+    /// Feeds `(key, press?, ms)` steps and collects `(key, gesture, ms)` results.
+    fn run(detector: &mut GestureDetector, steps: &[(Key, bool, u32)]) -> Vec<(Key, Gesture, u32)> {
+        let mut out = Vec::new();
+        for &(key, press, ms) in steps {
+            let kind = if press { KeyEventKind::Press } else { KeyEventKind::Release };
+            out.extend(detector.feed(&event(key, kind, ms)));
+        }
+        out.into_iter().map(|g| (g.key, g.gesture, g.timestamp.monotonic.as_millis() as u32)).collect()
+    }
+
+    #[test]
+    fn tap_and_hold() {
+        let mut d = GestureDetector::new();
+        assert_eq!(run(&mut d, &[(Key::KeyA, true, 1000), (Key::KeyA, false, 1100)]), [(Key::KeyA, Gesture::Tap, 1100)]);
+        assert_eq!(run(&mut d, &[(Key::KeyA, true, 2000)]), []);
+        assert_eq!(d.deadline(), Some(MS * 2200));
+        assert_eq!(d.advance(MS * 2199), []);
+        let hold: Vec<_> = d.advance(MS * 2500).into_iter().map(|g| (g.gesture, g.timestamp.monotonic)).collect();
+        assert_eq!(hold, [(Gesture::Hold, MS * 2200)]);
+        assert_eq!(d.deadline(), None);
+        assert_eq!(run(&mut d, &[(Key::KeyA, false, 3000)]), [(Key::KeyA, Gesture::HoldEnd, 3000)]);
+    }
+
+    #[test]
+    fn hold_decided_by_a_later_event() {
+        let mut d = GestureDetector::new().keys([Key::Space]);
+        let out = run(&mut d, &[(Key::Space, true, 0), (Key::KeyB, false, 500), (Key::Space, false, 600)]);
+        assert_eq!(out, [(Key::Space, Gesture::Hold, 200), (Key::Space, Gesture::HoldEnd, 600)]);
+    }
+
+    #[test]
+    fn double_tap() {
+        let mut d = GestureDetector::new().double_tap(MS * 300);
+        assert_eq!(run(&mut d, &[(Key::KeyA, true, 0), (Key::KeyA, false, 50)]), []);
+        assert_eq!(d.deadline(), Some(MS * 350));
+        assert_eq!(run(&mut d, &[(Key::KeyA, true, 200), (Key::KeyA, false, 250)]), [(Key::KeyA, Gesture::DoubleTap, 250)]);
+
+        // A single tap is reported once the window has passed.
+        assert_eq!(run(&mut d, &[(Key::KeyA, true, 1000), (Key::KeyA, false, 1050)]), []);
+        let taps: Vec<_> = d.advance(MS * 1350).into_iter().map(|g| (g.gesture, g.timestamp.monotonic)).collect();
+        assert_eq!(taps, [(Gesture::Tap, MS * 1050)]);
+
+        // A tap followed by a hold is both.
+        let out = run(&mut d, &[(Key::KeyA, true, 2000), (Key::KeyA, false, 2050), (Key::KeyA, true, 2100), (Key::KeyA, false, 2400)]);
+        assert_eq!(out, [(Key::KeyA, Gesture::Tap, 2050), (Key::KeyA, Gesture::Hold, 2300), (Key::KeyA, Gesture::HoldEnd, 2400)]);
+    }
+
+    #[test]
+    fn interleaved_keys() {
+        let mut d = GestureDetector::new().double_tap(MS * 300);
+        let out = run(
+            &mut d,
+            &[
+                (Key::ShiftLeft, true, 0),
+                (Key::KeyA, true, 50),
+                (Key::KeyA, false, 80),
+                (Key::ShiftLeft, false, 120),
+                (Key::KeyB, true, 130),
+                (Key::KeyB, false, 140),
+            ],
+        );
+        assert_eq!(
+            out,
+            [
+                (Key::ShiftLeft, Gesture::HoldWith(Key::KeyA), 50),
+                (Key::ShiftLeft, Gesture::HoldEnd, 120),
+                (Key::KeyA, Gesture::Tap, 80),
+            ]
+        );
+        // KeyB's tap waits for a second one, which does not come.
+        assert_eq!(d.advance(MS * 440).len(), 1);
+    }
+
+    #[test]
+    fn huge_thresholds_never_expire() {
+        let mut d = GestureDetector::new().hold_threshold(Duration::MAX).double_tap(Duration::MAX);
+        assert_eq!(run(&mut d, &[(Key::KeyA, true, 10)]), []);
+        assert_eq!(d.deadline(), None);
+        assert_eq!(d.advance(Duration::MAX), []);
+        assert_eq!(run(&mut d, &[(Key::KeyA, false, 20)]), []);
+        assert_eq!(d.deadline(), None);
+        assert_eq!(run(&mut d, &[(Key::KeyB, true, 30)]), [(Key::KeyA, Gesture::Tap, 20)]);
+    }
+}
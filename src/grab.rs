@@ -0,0 +1,69 @@
+//! Exclusive keyboard input for remappers (Linux only).
+//!
+//! A `Grab` reads evdev devices directly, like `Backend::Native`, but is meant for programs
+//! that take the keyboards over and re-emit their keys through an `Injector`: it never touches
+//! the terminal, ignores `KEYPRESS_SCRIPT`, and also reports the keys `Key` has no variant for
+//! (media keys, SysRq, F13 and up), so they can be passed through instead of lost.
+
+use crate::platform::linux::{EvdevSource, Terminal, Tracked};
+use crate::platform::Source;
+use crate::{Key, KeyEvent, KeyEventKind};
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+/// An event read by a `Grab`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GrabEvent {
+    Key(KeyEvent),
+    /// A key with no `Key` variant, by its evdev code; `Injector::send_code` forwards it.
+    Unmapped { code: u16, kind: KeyEventKind, device: u16 },
+}
+
+/// Keyboards read directly from evdev, for taking over with `grab`.
+pub struct Grab {
+    source: EvdevSource,
+}
+
+impl Grab {
+    /// Opens the given event devices, or every keyboard when `paths` is empty.
+    ///
+    /// Device nodes in `skip` are never opened, whatever path names them; pass the
+    /// `Injector::device_nodes` of the virtual keyboard the keys are sent to, so its output is
+    /// not read back.
+    pub fn open<P: AsRef<Path>>(paths: &[P], skip: &[P]) -> io::Result<Grab> {
+        let paths: Vec<&Path> = paths.iter().map(|p| p.as_ref()).collect();
+        let skip: Vec<&Path> = skip.iter().map(|p| p.as_ref()).collect();
+        Ok(Grab { source: EvdevSource::open_with(&paths, &skip, Terminal::Untouched)? })
+    }
+
+    /// Takes the devices exclusively, so their keys reach only this reader until it is dropped.
+    pub fn grab(&mut self) -> io::Result<()> {
+        self.source.grab()
+    }
+
+    /// The names of the open devices.
+    pub fn devices(&self) -> Vec<String> {
+        self.source.devices()
+    }
+
+    /// Every mapped key held on the devices right now.
+    pub fn pressed_keys(&self) -> io::Result<Vec<Key>> {
+        self.source.pressed_keys()
+    }
+
+    /// Waits up to `timeout` for the next event; `Ok(None)` when it elapses first.
+    pub fn next_event(&mut self, timeout: Option<Duration>) -> io::Result<Option<GrabEvent>> {
+        Ok(self.source.next_tracked(timeout)?.map(|tracked| match tracked {
+            Tracked::Key(event) => GrabEvent::Key(event),
+            Tracked::Unmapped { code, kind, device } => GrabEvent::Unmapped { code, kind, device },
+        }))
+    }
+}
+
+impl fmt::Debug for Grab {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Grab").field("devices", &self.devices()).finish_non_exhaustive()
+    }
+}
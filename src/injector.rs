@@ -0,0 +1,204 @@
+//! Synthetic key input through a uinput virtual keyboard (Linux only).
+//!
+//! Events sent through an `Injector` reach every program, exactly like a physical keyboard,
+//! including this crate's own evdev backend. Creating the device needs write access to
+//! `/dev/uinput`. Programs may take a moment to notice a newly created device.
+
+use crate::names::char_key;
+use crate::platform::linux::{evdev_code, evdev_codes, keyboard_codes};
+use crate::record::{Recording, Timing};
+use crate::{Key, KeyEventKind, Modifiers};
+use evdev::uinput::VirtualDevice;
+use evdev::{AttributeSet, EventType, InputEvent, KeyCode};
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+/// Maps characters to the keys that type them, for `Injector::type_text`.
+#[derive(Debug, Clone, Copy, Default)]
+pub enum Layout {
+    /// US QWERTY.
+    #[default]
+    Us,
+    /// A caller-supplied mapping; characters it returns `None` for cannot be typed.
+    Custom(fn(char) -> Option<(Key, Modifiers)>),
+}
+
+impl Layout {
+    pub fn key_for(self, c: char) -> Option<(Key, Modifiers)> {
+        match self {
+            Layout::Us => char_key(c).map(|(key, shift)| (key, if shift { Modifiers::SHIFT } else { Modifiers::NONE })),
+            Layout::Custom(map) => map(c),
+        }
+    }
+}
+
+/// A virtual keyboard that sends key events to the whole system.
+///
+/// Keys still held when the injector is dropped are released.
+pub struct Injector {
+    device: VirtualDevice,
+    held: Vec<Key>,
+    key_delay: Duration,
+}
+
+impl Injector {
+    pub fn new() -> io::Result<Injector> {
+        Self::with_name("keypress virtual keyboard")
+    }
+
+    /// Creates the virtual keyboard under `name`, as shown by `evtest` or `xinput`.
+    ///
+    /// It declares every keyboard key code, not only those with a `Key`, so `send_code` can
+    /// forward any of them.
+    pub fn with_name(name: &str) -> io::Result<Injector> {
+        let mut keys = AttributeSet::<KeyCode>::new();
+        for code in evdev_codes().chain(keyboard_codes()) {
+            keys.insert(code);
+        }
+        let device = VirtualDevice::builder()?.name(name).with_keys(&keys)?.build()?;
+        Ok(Injector { device, held: Vec::new(), key_delay: Duration::ZERO })
+    }
+
+    /// Pauses after every tap and typed character, for programs that drop fast input.
+    pub fn key_delay(mut self, delay: Duration) -> Self {
+        self.key_delay = delay;
+        self
+    }
+
+    /// The `/dev/input/event*` nodes of the virtual keyboard, e.g. to read it back in a test.
+    pub fn device_nodes(&mut self) -> io::Result<Vec<PathBuf>> {
+        self.device.enumerate_dev_nodes_blocking()?.collect()
+    }
+
+    pub fn press(&mut self, key: Key) -> io::Result<()> {
+        self.send(key, KeyEventKind::Press)
+    }
+
+    pub fn release(&mut self, key: Key) -> io::Result<()> {
+        self.send(key, KeyEventKind::Release)
+    }
+
+    /// Sends an auto-repeat of a held key.
+    pub fn repeat(&mut self, key: Key) -> io::Result<()> {
+        self.send(key, KeyEventKind::Repeat)
+    }
+
+    /// Sends a key by its evdev code, for keys `Key` has no variant for, such as a
+    /// `grab::GrabEvent::Unmapped`. Codes sent this way are not in `held` and `release_all`
+    /// leaves them alone.
+    pub fn send_code(&mut self, code: u16, kind: KeyEventKind) -> io::Result<()> {
+        self.device.emit(&[InputEvent::new(EventType::KEY.0, code, value(kind))])
+    }
+
+    /// Keys pressed through this injector and not yet released, in press order.
+    pub fn held(&self) -> &[Key] {
+        &self.held
+    }
+
+    /// Presses and releases `key` while holding `modifiers`. Modifiers already held through
+    /// this injector, on either side, are left as they are.
+    pub fn tap(&mut self, key: Key, modifiers: Modifiers) -> io::Result<()> {
+        let (held, _) = Modifiers::held(self.held.iter().copied());
+        let mods: Vec<Key> = modifiers.difference(held).keys().collect();
+        for &m in &mods {
+            self.press(m)?;
+        }
+        self.press(key)?;
+        self.release(key)?;
+        for &m in mods.iter().rev() {
+            self.release(m)?;
+        }
+        self.pause();
+        Ok(())
+    }
+
+    /// Types `text` by tapping the keys `layout` maps each character to.
+    ///
+    /// Fails with `InvalidInput` before sending anything if a character has no key.
+    pub fn type_text(&mut self, text: &str, layout: Layout) -> io::Result<()> {
+        let strokes = text
+            .chars()
+            .map(|c| {
+                layout.key_for(c).ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidInput, format!("no key types {:?} on this layout", c))
+                })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        for (key, modifiers) in strokes {
+            self.tap(key, modifiers)?;
+        }
+        Ok(())
+    }
+
+    /// Sends a recorded session, sleeping between events according to `timing`.
+    ///
+    /// Recordings from backends without releases, such as the terminal, are replayed as taps
+    /// with each event's modifiers.
+    pub fn play(&mut self, recording: &Recording, timing: Timing) -> io::Result<()> {
+        let releases = recording.events.iter().any(|e| e.kind == KeyEventKind::Release);
+        let mut previous = None;
+        for event in &recording.events {
+            let gap = previous.map(|p| event.timestamp.monotonic.saturating_sub(p)).unwrap_or_default();
+            previous = Some(event.timestamp.monotonic);
+            match timing {
+                Timing::Original => thread::sleep(gap),
+                Timing::Scaled(factor) if factor.is_finite() && factor > 0.0 => thread::sleep(gap.mul_f64(factor)),
+                Timing::Scaled(_) | Timing::Immediate => {}
+            }
+            if releases {
+                self.send(event.key, event.kind)?;
+            } else if event.kind != KeyEventKind::Release {
+                self.tap(event.key, event.modifiers())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Releases every key this injector is holding down.
+    pub fn release_all(&mut self) -> io::Result<()> {
+        while let Some(&key) = self.held.last() {
+            self.release(key)?;
+        }
+        Ok(())
+    }
+
+    fn send(&mut self, key: Key, kind: KeyEventKind) -> io::Result<()> {
+        self.device.emit(&[InputEvent::new(EventType::KEY.0, evdev_code(key).0, value(kind))])?;
+        match kind {
+            KeyEventKind::Press if !self.held.contains(&key) => self.held.push(key),
+            KeyEventKind::Release => self.held.retain(|&k| k != key),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn pause(&self) {
+        if !self.key_delay.is_zero() {
+            thread::sleep(self.key_delay);
+        }
+    }
+}
+
+/// The evdev value of a key event of `kind`.
+fn value(kind: KeyEventKind) -> i32 {
+    match kind {
+        KeyEventKind::Press => 1,
+        KeyEventKind::Repeat => 2,
+        KeyEventKind::Release => 0,
+    }
+}
+
+impl Drop for Injector {
+    fn drop(&mut self) {
+        let _ = self.release_all();
+    }
+}
+
+impl fmt::Debug for Injector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Injector").field("held", &self.held).finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,85 @@
+use crate::time::monotonic_now;
+use crate::{Key, KeyEvent, KeyEventKind, Session};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::time::Duration;
+
+/// Polled, per-frame keyboard state for games and simulations.
+///
+/// Call `update` once per frame; the `just_*` queries then describe what changed since the
+/// previous frame. On backends that do not report releases (the terminal), a key counts as
+/// down only for the frame in which it was pressed.
+#[derive(Debug, Clone, Default)]
+pub struct InputState {
+    down: HashMap<Key, Duration>,
+    just_pressed: HashSet<Key>,
+    just_released: HashSet<Key>,
+    transient: HashSet<Key>,
+}
+
+impl InputState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts a new frame with every event the session has buffered.
+    pub fn update(&mut self, session: &mut Session) -> io::Result<()> {
+        let events = session.drain_ready()?;
+        self.update_from(events, session.reports_releases());
+        Ok(())
+    }
+
+    /// Starts a new frame from events obtained elsewhere.
+    ///
+    /// With `releases` false, pressed keys are released automatically on the next frame.
+    pub fn update_from(&mut self, events: impl IntoIterator<Item = KeyEvent>, releases: bool) {
+        self.just_pressed.clear();
+        self.just_released.clear();
+        for key in std::mem::take(&mut self.transient) {
+            self.down.remove(&key);
+            self.just_released.insert(key);
+        }
+
+        for event in events {
+            match event.kind {
+                KeyEventKind::Press => {
+                    if let Entry::Vacant(down) = self.down.entry(event.key) {
+                        down.insert(event.timestamp.monotonic);
+                        self.just_pressed.insert(event.key);
+                    }
+                    if !releases {
+                        self.transient.insert(event.key);
+                    }
+                }
+                KeyEventKind::Repeat => {}
+                KeyEventKind::Release => {
+                    if self.down.remove(&event.key).is_some() {
+                        self.just_released.insert(event.key);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn is_down(&self, key: Key) -> bool {
+        self.down.contains_key(&key)
+    }
+
+    pub fn just_pressed(&self, key: Key) -> bool {
+        self.just_pressed.contains(&key)
+    }
+
+    pub fn just_released(&self, key: Key) -> bool {
+        self.just_released.contains(&key)
+    }
+
+    /// How long `key` has been held, or zero if it is up.
+    pub fn held_for(&self, key: Key) -> Duration {
+        self.down.get(&key).map(|&since| monotonic_now().saturating_sub(since)).unwrap_or_default()
+    }
+
+    pub fn down_keys(&self) -> impl Iterator<Item = Key> + '_ {
+        self.down.keys().copied()
+    }
+}
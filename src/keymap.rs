@@ -0,0 +1,440 @@
+//! Declarative modal keymaps, shared between programs as a TOML file.
+//!
+//! ```toml
+//! leader = "Space"
+//! timeout_ms = 1000
+//!
+//! [modes.global.bindings]
+//! "Ctrl+Q" = "quit"
+//!
+//! [modes.normal]
+//! inherits = ["global"]
+//! [modes.normal.bindings]
+//! "i" = "enter-insert"
+//! "g g" = "top"
+//! "<leader> f f" = "find-file"
+//!
+//! [modes.insert]
+//! inherits = ["global"]
+//! [modes.insert.bindings]
+//! "Escape" = "enter-normal"
+//!
+//! # Optional: the remapping the keymap is used under, in the `Remapper` format.
+//! [remap.keys]
+//! CapsLock = "Escape"
+//! ```
+//!
+//! Binding keys use the `SequenceMatcher` syntax and map to action names. A mode inherits
+//! the bindings of the modes it lists, earlier ones first; its own bindings override them.
+//! `Keymap::conflicts` reports bindings that cannot work as written.
+
+use crate::{Remapper, SequenceMatcher, Shortcut, Stroke};
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    leader: Option<Shortcut>,
+    timeout: Duration,
+    modes: Vec<Mode>,
+    remapper: Option<Remapper>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Mode {
+    pub name: String,
+    pub inherits: Vec<String>,
+    /// The mode's own bindings.
+    pub bindings: Vec<Binding>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Binding {
+    pub keys: Vec<Stroke>,
+    pub action: String,
+    /// The mode whose table defines the binding.
+    pub mode: String,
+}
+
+/// A problem found by `Keymap::conflicts`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Conflict {
+    /// One mode binds the same sequence twice, spelled differently (`C-x` and `Ctrl+X`); the
+    /// second binding in the file is the one in effect.
+    Duplicate { mode: String, keys: String, first: String, second: String },
+    /// `prefix` is bound and is also the start of `longer`, so it fires only after the
+    /// timeout or when the next key rules `longer` out.
+    ShadowedPrefix { mode: String, prefix: String, longer: String },
+    /// No key produces `stroke` once the keymap's remapping is applied.
+    Unreachable { mode: String, keys: String, stroke: String },
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Conflict::Duplicate { mode, keys, first, second } => {
+                write!(f, "mode {}: {} is bound twice, to {:?} and {:?}", mode, keys, first, second)
+            }
+            Conflict::ShadowedPrefix { mode, prefix, longer } => {
+                write!(f, "mode {}: {} is a prefix of {} and waits for the timeout", mode, prefix, longer)
+            }
+            Conflict::Unreachable { mode, keys, stroke } => {
+                write!(f, "mode {}: {} is unreachable, remapping leaves no way to type {}", mode, keys, stroke)
+            }
+        }
+    }
+}
+
+impl Keymap {
+    pub fn from_toml(text: &str) -> io::Result<Keymap> {
+        let table: toml::Table = text.parse().map_err(|err| invalid(format!("{}", err)))?;
+        let mut keymap = Keymap {
+            leader: None,
+            timeout: Duration::from_secs(1),
+            modes: Vec::new(),
+            remapper: None,
+        };
+        for (name, value) in &table {
+            match (name.as_str(), value) {
+                ("leader", toml::Value::String(leader)) => {
+                    keymap.leader = Some(leader.parse().map_err(|err| invalid(format!("leader: {}", err)))?);
+                }
+                ("timeout_ms", toml::Value::Integer(ms)) if *ms >= 0 => keymap.timeout = Duration::from_millis(*ms as u64),
+                ("timeout_ms", toml::Value::Integer(ms)) => {
+                    return Err(invalid(format!("timeout_ms must not be negative, got {}", ms)));
+                }
+                ("timeout_ms", _) => return Err(invalid("timeout_ms must be a whole number of milliseconds".into())),
+                ("modes", toml::Value::Table(modes)) => {
+                    for (mode, value) in modes {
+                        keymap.modes.push(parse_mode(mode, value)?);
+                    }
+                }
+                ("remap", toml::Value::Table(remap)) => keymap.remapper = Some(Remapper::from_table(remap)?),
+                _ => return Err(invalid(format!("unexpected keymap entry {:?}", name))),
+            }
+        }
+
+        for mode in &keymap.modes {
+            for parent in &mode.inherits {
+                if keymap.mode(parent).is_none() {
+                    return Err(invalid(format!("mode {:?} inherits unknown mode {:?}", mode.name, parent)));
+                }
+            }
+            keymap.lineage(&mode.name, &mut Vec::new())?;
+        }
+        Ok(keymap)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Keymap> {
+        Self::from_toml(&std::fs::read_to_string(path)?)
+    }
+
+    /// The modes a mode draws bindings from, most distant ancestor first, ending with itself.
+    fn lineage<'a>(&'a self, name: &str, path: &mut Vec<&'a str>) -> io::Result<Vec<&'a Mode>> {
+        if path.contains(&name) {
+            return Err(invalid(format!("mode {:?} inherits from itself", name)));
+        }
+        let mode = self.mode(name).ok_or_else(|| invalid(format!("unknown mode {:?}", name)))?;
+        path.push(&mode.name);
+        let mut lineage = Vec::new();
+        for parent in &mode.inherits {
+            for ancestor in self.lineage(parent, path)? {
+                if !lineage.iter().any(|m: &&Mode| m.name == ancestor.name) {
+                    lineage.push(ancestor);
+                }
+            }
+        }
+        path.pop();
+        lineage.push(mode);
+        Ok(lineage)
+    }
+
+    pub fn leader(&self) -> Option<Shortcut> {
+        self.leader
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    pub fn modes(&self) -> &[Mode] {
+        &self.modes
+    }
+
+    pub fn mode(&self, name: &str) -> Option<&Mode> {
+        self.modes.iter().find(|m| m.name == name)
+    }
+
+    /// The `[remap]` section, if any.
+    pub fn remapper(&self) -> Option<&Remapper> {
+        self.remapper.as_ref()
+    }
+
+    /// Every binding in effect in `mode`, inherited ones included; `None` for an unknown mode.
+    pub fn bindings(&self, mode: &str) -> Option<Vec<&Binding>> {
+        let lineage = self.lineage(mode, &mut Vec::new()).ok()?;
+        let mut bindings: Vec<&Binding> = Vec::new();
+        for binding in lineage.into_iter().flat_map(|m| &m.bindings) {
+            bindings.retain(|b| !self.same(&b.keys, &binding.keys));
+            bindings.push(binding);
+        }
+        Some(bindings)
+    }
+
+    /// A matcher for `mode` that reports action names, with the keymap's leader and timeout.
+    pub fn matcher(&self, mode: &str) -> Option<SequenceMatcher<String>> {
+        let mut matcher = SequenceMatcher::new().timeout(self.timeout);
+        if let Some(leader) = self.leader {
+            matcher = matcher.leader(leader);
+        }
+        for binding in self.bindings(mode)? {
+            matcher = matcher.bind_strokes(binding.keys.iter().map(|&s| self.resolve(s)), binding.action.clone());
+        }
+        Some(matcher)
+    }
+
+    /// Duplicate bindings, bound prefixes of longer bindings and bindings the remapping makes
+    /// impossible to type, mode by mode.
+    pub fn conflicts(&self) -> Vec<Conflict> {
+        let mut conflicts = Vec::new();
+        for mode in &self.modes {
+            for (i, first) in mode.bindings.iter().enumerate() {
+                for second in mode.bindings[i + 1..].iter().filter(|b| self.same(&b.keys, &first.keys)) {
+                    conflicts.push(Conflict::Duplicate {
+                        mode: mode.name.clone(),
+                        keys: sequence_text(&second.keys),
+                        first: first.action.clone(),
+                        second: second.action.clone(),
+                    });
+                }
+            }
+
+            let bindings = self.bindings(&mode.name).unwrap_or_default();
+            for prefix in &bindings {
+                for longer in &bindings {
+                    let strokes = |b: &Binding| b.keys.iter().map(|&s| self.resolve(s)).collect::<Vec<_>>();
+                    let (p, l) = (strokes(prefix), strokes(longer));
+                    if p.len() < l.len() && l.starts_with(&p) {
+                        conflicts.push(Conflict::ShadowedPrefix {
+                            mode: mode.name.clone(),
+                            prefix: sequence_text(&prefix.keys),
+                            longer: sequence_text(&longer.keys),
+                        });
+                    }
+                }
+            }
+            if let Some(remapper) = &self.remapper {
+                for binding in &bindings {
+                    let blocked = binding.keys.iter().map(|&s| self.resolve(s)).find(|stroke| {
+                        matches!(stroke, Stroke::Key(shortcut) if !remapper.can_produce(shortcut.key, shortcut.modifiers))
+                    });
+                    if let Some(stroke) = blocked {
+                        conflicts.push(Conflict::Unreachable {
+                            mode: mode.name.clone(),
+                            keys: sequence_text(&binding.keys),
+                            stroke: stroke.to_string(),
+                        });
+                    }
+                }
+            }
+        }
+        conflicts
+    }
+
+    /// `<leader>` replaced by the leader key, when there is one.
+    fn resolve(&self, stroke: Stroke) -> Stroke {
+        match (stroke, self.leader) {
+            (Stroke::Leader, Some(leader)) => Stroke::Key(leader),
+            _ => stroke,
+        }
+    }
+
+    fn same(&self, a: &[Stroke], b: &[Stroke]) -> bool {
+        a.len() == b.len() && a.iter().zip(b).all(|(&x, &y)| self.resolve(x) == self.resolve(y))
+    }
+}
+
+fn parse_mode(name: &str, value: &toml::Value) -> io::Result<Mode> {
+    let table = value.as_table().ok_or_else(|| invalid(format!("mode {:?} must be a table", name)))?;
+    let mut mode = Mode { name: name.to_string(), inherits: Vec::new(), bindings: Vec::new() };
+    for (entry, value) in table {
+        match (entry.as_str(), value) {
+            ("inherits", toml::Value::String(parent)) => mode.inherits.push(parent.clone()),
+            ("inherits", toml::Value::Array(parents)) => {
+                for parent in parents {
+                    let parent = parent.as_str().ok_or_else(|| invalid(format!("modes.{}.inherits must list mode names", name)))?;
+                    mode.inherits.push(parent.to_string());
+                }
+            }
+            ("bindings", toml::Value::Table(bindings)) => {
+                for (keys, action) in bindings {
+                    let strokes = Stroke::parse_sequence(keys).map_err(|err| invalid(format!("modes.{}: {}", name, err)))?;
+                    let action = action
+                        .as_str()
+                        .ok_or_else(|| invalid(format!("modes.{}: {:?} must map to an action name", name, keys)))?;
+                    mode.bindings.push(Binding { keys: strokes, action: action.to_string(), mode: name.to_string() });
+                }
+            }
+            _ => return Err(invalid(format!("unexpected entry {:?} in mode {:?}", entry, name))),
+        }
+    }
+    Ok(mode)
+}
+
+fn sequence_text(strokes: &[Stroke]) -> String {
+    strokes.iter().map(Stroke::to_string).collect::<Vec<_>>().join(" ")
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Key, KeyEvent, KeyEventKind, Modifiers, SequenceStep, Timestamp};
+
+    fn keymap(text: &str) -> Keymap {
+        Keymap::from_toml(text).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    fn error(text: &str) -> String {
+        Keymap::from_toml(text).unwrap_err().to_string()
+    }
+
+    /// `keys = action (mode)` for every binding in effect in `mode`.
+    fn effective(keymap: &Keymap, mode: &str) -> Vec<String> {
+        let bindings = keymap.bindings(mode).unwrap();
+        bindings.iter().map(|b| format!("{} = {} ({})", sequence_text(&b.keys), b.action, b.mode)).collect()
+    }
+
+    #[test]
+    fn inherits_in_order_and_overrides() {
+        let keymap = keymap(
+            r#"
+            [modes.base.bindings]
+            "Ctrl+Q" = "quit"
+            "u" = "undo"
+
+            [modes.edit]
+            inherits = "base"
+            [modes.edit.bindings]
+            "u" = "upper"
+            "x" = "cut"
+
+            [modes.view]
+            inherits = "base"
+            [modes.view.bindings]
+            "x" = "close"
+
+            [modes.normal]
+            inherits = ["edit", "view"]
+            [modes.normal.bindings]
+            "C-q" = "really-quit"
+            "#,
+        );
+        assert_eq!(effective(&keymap, "base"), ["Ctrl+Q = quit (base)", "u = undo (base)"]);
+        assert_eq!(effective(&keymap, "edit"), ["Ctrl+Q = quit (base)", "u = upper (edit)", "x = cut (edit)"]);
+        // `base` is drawn on once; `view` comes after `edit`, and the mode's own bindings last.
+        assert_eq!(effective(&keymap, "normal"), ["u = upper (edit)", "x = close (view)", "Ctrl+Q = really-quit (normal)"]);
+        assert!(keymap.bindings("missing").is_none());
+        assert!(keymap.conflicts().is_empty());
+
+        let mut matcher = keymap.matcher("normal").unwrap();
+        let x = KeyEvent {
+            key: Key::KeyX,
+            kind: KeyEventKind::Press,
+            shift: false,
+            ctrl: false,
+            alt: false,
+            meta: false,
+            right: Modifiers::NONE,
+            timestamp: Timestamp::default(),
+            device: 0,
+        };
+        assert_eq!(matcher.feed(&x), SequenceStep::Matched("close".to_string()));
+    }
+
+    #[test]
+    fn rejects_bad_inheritance() {
+        assert_eq!(error("[modes.a]\ninherits = \"b\""), r#"mode "a" inherits unknown mode "b""#);
+        assert_eq!(
+            error("[modes.a]\ninherits = \"b\"\n[modes.b]\ninherits = [\"a\"]"),
+            r#"mode "a" inherits from itself"#
+        );
+        assert_eq!(error("[modes.a]\ninherits = [1]"), "modes.a.inherits must list mode names");
+        assert_eq!(error("[modes.a]\nparent = \"b\""), r#"unexpected entry "parent" in mode "a""#);
+        assert_eq!(error("[modes.a.bindings]\n\"C-x\" = 1"), r#"modes.a: "C-x" must map to an action name"#);
+        assert_eq!(error("timeout_ms = -1"), "timeout_ms must not be negative, got -1");
+        assert_eq!(error("colour = \"red\""), r#"unexpected keymap entry "colour""#);
+    }
+
+    #[test]
+    fn finds_duplicates() {
+        let keymap = keymap(
+            r#"
+            [modes.normal.bindings]
+            "C-x C-s" = "save"
+            "Ctrl+X Ctrl+S" = "save-all"
+            "#,
+        );
+        assert_eq!(
+            keymap.conflicts(),
+            [Conflict::Duplicate {
+                mode: "normal".into(),
+                keys: "Ctrl+X Ctrl+S".into(),
+                first: "save".into(),
+                second: "save-all".into(),
+            }]
+        );
+        assert_eq!(effective(&keymap, "normal"), ["Ctrl+X Ctrl+S = save-all (normal)"]);
+    }
+
+    #[test]
+    fn finds_shadowed_prefixes_through_the_leader_and_inheritance() {
+        let keymap = keymap(
+            r#"
+            leader = "Space"
+
+            [modes.base.bindings]
+            "g" = "go"
+
+            [modes.normal]
+            inherits = "base"
+            [modes.normal.bindings]
+            "g g" = "top"
+            "Space" = "page-down"
+            "<leader> f" = "find"
+            "#,
+        );
+        let conflicts: Vec<String> = keymap.conflicts().iter().map(Conflict::to_string).collect();
+        assert_eq!(
+            conflicts,
+            [
+                "mode normal: g is a prefix of g g and waits for the timeout",
+                "mode normal: Space is a prefix of <leader> f and waits for the timeout",
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_strokes_the_remapping_removes() {
+        let keymap = keymap(
+            r#"
+            [modes.insert.bindings]
+            "Escape" = "normal"
+            "Ctrl+C" = "normal"
+
+            [remap.keys]
+            Escape = "Grave"
+            CapsLock = "ControlLeft"
+            "#,
+        );
+        assert_eq!(
+            keymap.conflicts(),
+            [Conflict::Unreachable { mode: "insert".into(), keys: "Escape".into(), stroke: "Escape".into() }]
+        );
+    }
+}
@@ -1,6 +1,6 @@
-use std::io::{self, Write};
+use std::io;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Key {
     Escape,
     F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
@@ -17,6 +17,7 @@ pub enum Key {
     ShiftLeft, KeyZ, KeyX, KeyC, KeyV, KeyB, KeyN, KeyM,
     Comma, Period, Slash, ShiftRight,
     ControlLeft, AltLeft, Space, AltRight, ControlRight,
+    MetaLeft, MetaRight,
     ArrowLeft, ArrowRight, ArrowUp, ArrowDown,
     Insert, Delete, Home, End, PageUp, PageDown,
     NumLock,
@@ -25,17 +26,116 @@ pub enum Key {
     PrintScreen, ScrollLock, Pause,
 }
 
+impl Key {
+    pub const ALL: [Key; 103] = [
+        Key::Escape, Key::F1, Key::F2, Key::F3, Key::F4, Key::F5, Key::F6, Key::F7, Key::F8,
+        Key::F9, Key::F10, Key::F11, Key::F12, Key::Grave, Key::Digit1, Key::Digit2, Key::Digit3,
+        Key::Digit4, Key::Digit5, Key::Digit6, Key::Digit7, Key::Digit8, Key::Digit9, Key::Digit0,
+        Key::Minus, Key::Equal, Key::Backspace, Key::Tab, Key::KeyQ, Key::KeyW, Key::KeyE,
+        Key::KeyR, Key::KeyT, Key::KeyY, Key::KeyU, Key::KeyI, Key::KeyO, Key::KeyP,
+        Key::LeftBracket, Key::RightBracket, Key::Backslash, Key::CapsLock, Key::KeyA, Key::KeyS,
+        Key::KeyD, Key::KeyF, Key::KeyG, Key::KeyH, Key::KeyJ, Key::KeyK, Key::KeyL, Key::Semicolon,
+        Key::Quote, Key::Enter, Key::ShiftLeft, Key::KeyZ, Key::KeyX, Key::KeyC, Key::KeyV,
+        Key::KeyB, Key::KeyN, Key::KeyM, Key::Comma, Key::Period, Key::Slash, Key::ShiftRight,
+        Key::ControlLeft, Key::AltLeft, Key::Space, Key::AltRight, Key::ControlRight,
+        Key::ArrowLeft, Key::ArrowRight, Key::ArrowUp, Key::ArrowDown, Key::Insert, Key::Delete,
+        Key::Home, Key::End, Key::PageUp, Key::PageDown, Key::NumLock, Key::Numpad0, Key::Numpad1,
+        Key::Numpad2, Key::Numpad3, Key::Numpad4, Key::Numpad5, Key::Numpad6, Key::Numpad7,
+        Key::Numpad8, Key::Numpad9, Key::NumpadDivide, Key::NumpadMultiply, Key::NumpadSubtract,
+        Key::NumpadAdd, Key::NumpadEnter, Key::NumpadDecimal, Key::PrintScreen, Key::ScrollLock,
+        Key::Pause, Key::MetaLeft, Key::MetaRight,
+    ];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum KeyEventKind {
+    #[default]
+    Press,
+    Repeat,
+    Release,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct KeyEvent {
     pub key: Key,
+    pub kind: KeyEventKind,
     pub shift: bool,
     pub ctrl: bool,
     pub alt: bool,
+    /// Super: the Windows key, or Command on macOS.
+    pub meta: bool,
+    /// The modifiers held through a right-hand key such as `ControlRight`. Empty on backends
+    /// that cannot tell the sides apart, such as the terminal.
+    pub right: Modifiers,
+    pub timestamp: Timestamp,
+    /// Which of the session's devices produced the event, as an index into `Session::devices`.
+    pub device: u16,
 }
 
 pub fn get_key(prompt: &str) -> io::Result<KeyEvent> {
-    eprint!("{}", prompt);
-    io::stderr().flush()?;
-    platform::wait_key_with_mods()
+    KeyRequest::new().prompt(prompt).wait()
 }
 
-mod platform;
\ No newline at end of file
+/// Whether `key` is held right now, without waiting for input.
+///
+/// Backed by the evdev key-state ioctl on Linux and `GetAsyncKeyState` on Windows.
+/// Elsewhere, and where only a terminal is available, fails with `Error::Unsupported`.
+pub fn is_pressed(key: Key) -> io::Result<bool> {
+    Ok(pressed_keys()?.contains(&key))
+}
+
+/// Every key held right now; see `is_pressed`.
+pub fn pressed_keys() -> io::Result<Vec<Key>> {
+    platform::pressed_keys()
+}
+
+mod cancel;
+mod combo;
+pub mod encode;
+mod error;
+mod gesture;
+#[cfg(target_os = "linux")]
+pub mod grab;
+#[cfg(target_os = "linux")]
+pub mod injector;
+mod input_state;
+#[cfg(feature = "toml")]
+pub mod keymap;
+mod listener;
+mod modifiers;
+mod names;
+mod notation;
+mod platform;
+pub mod record;
+mod remap;
+mod request;
+mod sequence;
+mod session;
+mod shortcut;
+#[cfg(feature = "async")]
+mod stream;
+pub mod testing;
+mod time;
+
+pub use cancel::CancelHandle;
+pub use combo::{ComboDetector, ComboEvent};
+pub use encode::{encode, Terminal};
+pub use error::Error;
+pub use gesture::{Gesture, GestureDetector, GestureEvent};
+pub use input_state::InputState;
+#[cfg(feature = "toml")]
+pub use keymap::Keymap;
+pub use listener::{Listener, SubscriptionId};
+pub use modifiers::Modifiers;
+pub use names::ParseKeyError;
+pub use notation::{Notation, NotationError};
+pub use platform::Backend;
+pub use record::{RecordFormat, Recording, Timing};
+pub use remap::Remapper;
+pub use request::{InterruptPolicy, KeyRequest, ModifierPolicy, PromptSink};
+pub use sequence::{SequenceMatcher, SequenceStep, Stroke};
+pub use session::Session;
+pub use shortcut::{ExtraModifiers, MatchOptions, ParseShortcutError, Shortcut, Sides};
+pub use time::Timestamp;
+#[cfg(feature = "async")]
+pub use stream::{next_key, KeyStream};
\ No newline at end of file
@@ -0,0 +1,240 @@
+use crate::{Backend, CancelHandle, Error, KeyEvent, Session};
+use std::collections::VecDeque;
+use std::fmt;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+
+const RECENT_CAPACITY: usize = 256;
+
+type Callback = Arc<Mutex<Box<dyn FnMut(&KeyEvent) + Send>>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(u64);
+
+#[derive(Clone)]
+enum Sink {
+    Channel(Sender<KeyEvent>),
+    Callback(Callback),
+}
+
+struct Shared {
+    next_id: AtomicU64,
+    subscribers: Mutex<Vec<(SubscriptionId, Sink)>>,
+    recent: Mutex<VecDeque<KeyEvent>>,
+}
+
+/// Reads keys on a background thread and fans every event out to all subscribers.
+///
+/// Subscribers can be added and removed at any time from any thread. Callbacks run on the
+/// listener thread, one event at a time; a callback that panics is unsubscribed and the
+/// others carry on.
+///
+/// The terminal stays in raw mode until the listener stops, so Ctrl+C is delivered as a key
+/// and no longer raises SIGINT: a program that should still quit on Ctrl+C has to watch for
+/// it, for instance with an `on_key` callback.
+pub struct Listener {
+    shared: Arc<Shared>,
+    cancel: CancelHandle,
+    thread: Option<JoinHandle<io::Result<()>>>,
+}
+
+impl Listener {
+    pub fn start() -> io::Result<Listener> {
+        Self::start_with(Backend::Auto)
+    }
+
+    pub fn start_with(backend: Backend) -> io::Result<Listener> {
+        Self::spawn(move |cancel| Session::open_with(backend, Some(cancel)))
+    }
+
+    /// Starts the listener thread on the session `open` returns.
+    fn spawn(open: impl FnOnce(&CancelHandle) -> io::Result<Session> + Send + 'static) -> io::Result<Listener> {
+        let shared = Arc::new(Shared {
+            next_id: AtomicU64::new(0),
+            subscribers: Mutex::new(Vec::new()),
+            recent: Mutex::new(VecDeque::with_capacity(RECENT_CAPACITY)),
+        });
+        let cancel = CancelHandle::new()?;
+        let (ready_tx, ready_rx) = mpsc::channel();
+
+        let thread_shared = shared.clone();
+        let thread_cancel = cancel.clone();
+        let thread = thread::Builder::new().name("keypress-listener".into()).spawn(move || {
+            let mut session = match open(&thread_cancel) {
+                Ok(session) => {
+                    let _ = ready_tx.send(Ok(()));
+                    session
+                }
+                Err(err) => {
+                    let _ = ready_tx.send(Err(err));
+                    return Ok(());
+                }
+            };
+            loop {
+                match session.next_key() {
+                    Ok(event) => thread_shared.dispatch(&event),
+                    Err(err) if Error::of(&err) == Some(Error::Cancelled) => return Ok(()),
+                    Err(err) => return Err(err),
+                }
+            }
+        })?;
+
+        match ready_rx.recv() {
+            Ok(Ok(())) => Ok(Listener { shared, cancel, thread: Some(thread) }),
+            Ok(Err(err)) => Err(err),
+            Err(_) => Err(io::Error::other("listener thread exited")),
+        }
+    }
+
+    /// Returns a channel receiving every subsequent event.
+    ///
+    /// Dropping the receiver unsubscribes it automatically on the next event.
+    pub fn subscribe(&self) -> (SubscriptionId, Receiver<KeyEvent>) {
+        let (tx, rx) = mpsc::channel();
+        (self.shared.add(Sink::Channel(tx)), rx)
+    }
+
+    /// Registers a callback invoked on the listener thread for every subsequent event.
+    pub fn on_key(&self, callback: impl FnMut(&KeyEvent) + Send + 'static) -> SubscriptionId {
+        self.shared.add(Sink::Callback(Arc::new(Mutex::new(Box::new(callback)))))
+    }
+
+    pub fn unsubscribe(&self, id: SubscriptionId) {
+        lock(&self.shared.subscribers).retain(|(sub, _)| *sub != id);
+    }
+
+    /// The last `n` events, oldest first. At most 256 events are kept.
+    pub fn recent(&self, n: usize) -> Vec<KeyEvent> {
+        let recent = lock(&self.shared.recent);
+        recent.iter().skip(recent.len().saturating_sub(n)).copied().collect()
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.thread.as_ref().is_some_and(|t| !t.is_finished())
+    }
+
+    /// Stops the background thread, restores the terminal and reports any read error it hit.
+    ///
+    /// Called from a callback, on the listener thread itself, it only asks the thread to stop:
+    /// the thread finishes once the callback returns, and any error is lost.
+    pub fn stop(mut self) -> io::Result<()> {
+        self.shutdown()
+    }
+
+    fn shutdown(&mut self) -> io::Result<()> {
+        self.cancel.cancel();
+        match self.thread.take() {
+            // Joining itself would deadlock; the cancelled thread exits after the callback.
+            Some(thread) if thread.thread().id() == thread::current().id() => Ok(()),
+            Some(thread) => thread.join().unwrap_or_else(|_| Err(io::Error::other("listener thread panicked"))),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        let _ = self.shutdown();
+    }
+}
+
+impl fmt::Debug for Listener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Listener").field("running", &self.is_running()).finish_non_exhaustive()
+    }
+}
+
+impl Shared {
+    fn add(&self, sink: Sink) -> SubscriptionId {
+        let id = SubscriptionId(self.next_id.fetch_add(1, Ordering::Relaxed));
+        lock(&self.subscribers).push((id, sink));
+        id
+    }
+
+    fn dispatch(&self, event: &KeyEvent) {
+        {
+            let mut recent = lock(&self.recent);
+            if recent.len() == RECENT_CAPACITY {
+                recent.pop_front();
+            }
+            recent.push_back(*event);
+        }
+
+        let sinks = lock(&self.subscribers).clone();
+        let mut closed = Vec::new();
+        for (id, sink) in sinks {
+            match sink {
+                Sink::Channel(tx) => {
+                    if tx.send(*event).is_err() {
+                        closed.push(id);
+                    }
+                }
+                Sink::Callback(callback) => {
+                    let mut callback = lock(&callback);
+                    if panic::catch_unwind(AssertUnwindSafe(|| (callback)(event))).is_err() {
+                        closed.push(id);
+                    }
+                }
+            }
+        }
+        if !closed.is_empty() {
+            lock(&self.subscribers).retain(|(id, _)| !closed.contains(id));
+        }
+    }
+}
+
+/// Locks `mutex`, carrying on after a panic in another holder; the data it guards stays
+/// consistent because every critical section here is a single push, clone or retain.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::ScriptedBackend;
+    use crate::Key;
+    use std::time::Duration;
+
+    /// A listener replaying `script`, with `on_key` handed the listener itself on the first key.
+    fn stopped_from_callback(script: ScriptedBackend, on_key: impl FnOnce(Listener) + Send + 'static) {
+        let listener = Listener::spawn(move |cancel| Ok(Session::scripted_with(&script, Some(cancel)))).unwrap();
+        let slot = Arc::new(Mutex::new(None));
+        let (done_tx, done_rx) = mpsc::channel();
+        let mut on_key = Some(on_key);
+        let callback_slot = slot.clone();
+        listener.on_key(move |_| {
+            if let (Some(listener), Some(on_key)) = (lock(&callback_slot).take(), on_key.take()) {
+                on_key(listener);
+                let _ = done_tx.send(());
+            }
+        });
+        *lock(&slot) = Some(listener);
+        done_rx.recv_timeout(Duration::from_secs(5)).expect("callback deadlocked stopping the listener");
+    }
+
+    fn one_key() -> ScriptedBackend {
+        ScriptedBackend::new().delay(Duration::from_millis(200)).key(Key::KeyA).delay(Duration::from_secs(60)).key(Key::KeyB)
+    }
+
+    #[test]
+    fn stops_from_its_own_callback() {
+        stopped_from_callback(one_key(), |listener| listener.stop().unwrap());
+        stopped_from_callback(one_key(), drop);
+    }
+
+    #[test]
+    fn stop_joins_the_thread() {
+        let script = one_key();
+        let listener = Listener::spawn(move |cancel| Ok(Session::scripted_with(&script, Some(cancel)))).unwrap();
+        let (_, keys) = listener.subscribe();
+        assert_eq!(keys.recv_timeout(Duration::from_secs(5)).unwrap().key, Key::KeyA);
+        assert!(listener.is_running());
+        listener.stop().unwrap();
+        assert!(keys.recv().is_err());
+    }
+}
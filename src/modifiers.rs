@@ -0,0 +1,126 @@
+use crate::{Key, KeyEvent};
+use std::ops::{BitOr, BitOrAssign};
+
+/// A set of modifiers. It does not tell left and right apart; `KeyEvent::right` does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Modifiers {
+    bits: u8,
+}
+
+impl Modifiers {
+    pub const NONE: Modifiers = Modifiers { bits: 0 };
+    pub const SHIFT: Modifiers = Modifiers { bits: 1 };
+    pub const CTRL: Modifiers = Modifiers { bits: 2 };
+    pub const ALT: Modifiers = Modifiers { bits: 4 };
+    /// Super, the Windows key or Command.
+    pub const SUPER: Modifiers = Modifiers { bits: 8 };
+
+    pub const fn union(self, other: Modifiers) -> Modifiers {
+        Modifiers { bits: self.bits | other.bits }
+    }
+
+    pub const fn contains(self, other: Modifiers) -> bool {
+        self.bits & other.bits == other.bits
+    }
+
+    pub const fn is_empty(self) -> bool {
+        self.bits == 0
+    }
+
+    /// The modifiers in `self` but not in `other`.
+    pub const fn difference(self, other: Modifiers) -> Modifiers {
+        Modifiers { bits: self.bits & !other.bits }
+    }
+
+    pub const fn bits(self) -> u8 {
+        self.bits
+    }
+
+    /// The set with the given bits, dropping unknown ones.
+    pub const fn from_bits(bits: u8) -> Modifiers {
+        Modifiers { bits: bits & 0xf }
+    }
+
+    /// The modifier a key produces, and whether it is the right-hand one.
+    pub fn of_key(key: Key) -> Option<(Modifiers, bool)> {
+        match key {
+            Key::ShiftLeft => Some((Modifiers::SHIFT, false)),
+            Key::ShiftRight => Some((Modifiers::SHIFT, true)),
+            Key::ControlLeft => Some((Modifiers::CTRL, false)),
+            Key::ControlRight => Some((Modifiers::CTRL, true)),
+            Key::AltLeft => Some((Modifiers::ALT, false)),
+            Key::AltRight => Some((Modifiers::ALT, true)),
+            Key::MetaLeft => Some((Modifiers::SUPER, false)),
+            Key::MetaRight => Some((Modifiers::SUPER, true)),
+            _ => None,
+        }
+    }
+
+    /// The modifiers produced by `keys`, and those produced by a right-hand key among them.
+    pub(crate) fn held(keys: impl IntoIterator<Item = Key>) -> (Modifiers, Modifiers) {
+        let (mut all, mut right) = (Modifiers::NONE, Modifiers::NONE);
+        for (m, is_right) in keys.into_iter().filter_map(Modifiers::of_key) {
+            all |= m;
+            if is_right {
+                right |= m;
+            }
+        }
+        (all, right)
+    }
+
+    /// Whether `key` is one of the eight modifier keys, either side.
+    pub(crate) fn is_key(key: Key) -> bool {
+        Modifiers::of_key(key).is_some()
+    }
+
+    /// The modifiers held during `event`.
+    pub fn of(event: &KeyEvent) -> Modifiers {
+        let mut mods = Modifiers::NONE;
+        if event.shift {
+            mods |= Modifiers::SHIFT;
+        }
+        if event.ctrl {
+            mods |= Modifiers::CTRL;
+        }
+        if event.alt {
+            mods |= Modifiers::ALT;
+        }
+        if event.meta {
+            mods |= Modifiers::SUPER;
+        }
+        mods
+    }
+
+    /// The left-hand keys that produce these modifiers, in the order they are usually pressed.
+    pub fn keys(self) -> impl Iterator<Item = Key> {
+        [
+            (Modifiers::CTRL, Key::ControlLeft),
+            (Modifiers::ALT, Key::AltLeft),
+            (Modifiers::SHIFT, Key::ShiftLeft),
+            (Modifiers::SUPER, Key::MetaLeft),
+        ]
+        .into_iter()
+        .filter(move |&(m, _)| self.contains(m))
+        .map(|(_, key)| key)
+    }
+}
+
+impl BitOr for Modifiers {
+    type Output = Modifiers;
+
+    fn bitor(self, other: Modifiers) -> Modifiers {
+        self.union(other)
+    }
+}
+
+impl BitOrAssign for Modifiers {
+    fn bitor_assign(&mut self, other: Modifiers) {
+        *self = self.union(other);
+    }
+}
+
+impl KeyEvent {
+    pub fn modifiers(&self) -> Modifiers {
+        Modifiers::of(self)
+    }
+}
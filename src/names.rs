@@ -0,0 +1,402 @@
+use crate::encode::us_char;
+use crate::{Key, Modifiers};
+use std::fmt;
+use std::str::FromStr;
+
+/// Returned when a string does not name a key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseKeyError(String);
+
+impl fmt::Display for ParseKeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown key name {:?}", self.0)
+    }
+}
+
+impl std::error::Error for ParseKeyError {}
+
+/// Writes the variant name, e.g. `KeyA` or `ArrowUp`; `FromStr` accepts it back.
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// Parses a key name, ignoring case.
+///
+/// Accepts the variant names (`KeyA`, `Digit1`, `ArrowUp`), the same without the `Key`/`Digit`
+/// prefix (`a`, `1`), a single character on a US layout (`/`, `[`) and common short names
+/// such as `esc`, `ret`, `up`, `pgdn`, `ctrl` or `space`, and the macOS key symbols (`⏎`,
+/// `⌫`). `Shortcut` reads key names from the same table.
+impl FromStr for Key {
+    type Err = ParseKeyError;
+
+    fn from_str(s: &str) -> Result<Key, ParseKeyError> {
+        match key_named(s.as_bytes(), 0, s.len()) {
+            Some((key, _)) => Ok(key),
+            None => Err(ParseKeyError(s.to_string())),
+        }
+    }
+}
+
+/// The key producing `c` on a US layout, and whether Shift is needed for it.
+///
+/// Main-block keys win over the numpad.
+pub(crate) fn char_key(c: char) -> Option<(Key, bool)> {
+    match c {
+        '\n' | '\r' => return Some((Key::Enter, false)),
+        '\t' => return Some((Key::Tab, false)),
+        _ => {}
+    }
+    Key::ALL.iter().find_map(|&key| {
+        if us_char(key, false) == Some(c) {
+            Some((key, false))
+        } else if us_char(key, true) == Some(c) {
+            Some((key, true))
+        } else {
+            None
+        }
+    })
+}
+
+/// Parses `Ctrl+Alt+K`-style text: modifier names joined to a key name with `+`.
+pub(crate) fn parse_chord(text: &str) -> Result<(Key, Modifiers), String> {
+    let (mods, name) = if text.len() > 1 && text.ends_with("++") {
+        (&text[..text.len() - 2], "+")
+    } else {
+        match text.rsplit_once('+') {
+            Some((mods, name)) if !name.is_empty() => (mods, name),
+            _ => ("", text),
+        }
+    };
+    let mut modifiers = Modifiers::NONE;
+    for m in mods.split('+').filter(|m| !m.is_empty()) {
+        modifiers |= modifier_named(m.as_bytes(), 0, m.len()).ok_or_else(|| format!("unknown modifier {:?} in {:?}", m, text))?;
+    }
+    let key = name.parse::<Key>().map_err(|err| err.to_string())?;
+    Ok((key, modifiers))
+}
+
+/// The key named by `text[start..end]` in `KEY_NAMES`, and whether its character needs Shift.
+pub(crate) const fn key_named(text: &[u8], start: usize, end: usize) -> Option<(Key, bool)> {
+    let mut i = 0;
+    while i < KEY_NAMES.len() {
+        let (name, key, shifted) = KEY_NAMES[i];
+        if eq_ignore_case(text, start, end, name.as_bytes()) {
+            return Some((key, shifted));
+        }
+        i += 1;
+    }
+    None
+}
+
+/// The modifier named by `text[start..end]` in `MODIFIER_NAMES`.
+pub(crate) const fn modifier_named(text: &[u8], start: usize, end: usize) -> Option<Modifiers> {
+    let mut i = 0;
+    while i < MODIFIER_NAMES.len() {
+        let (name, m) = MODIFIER_NAMES[i];
+        if eq_ignore_case(text, start, end, name.as_bytes()) {
+            return Some(m);
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Whether `text[start..end]` equals `name`, ignoring ASCII case.
+pub(crate) const fn eq_ignore_case(text: &[u8], start: usize, end: usize, name: &[u8]) -> bool {
+    if end - start != name.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < name.len() {
+        if !text[start + i].eq_ignore_ascii_case(&name[i]) {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Modifier names, matched ignoring case.
+pub(crate) const MODIFIER_NAMES: [(&str, Modifiers); 14] = [
+    ("shift", Modifiers::SHIFT),
+    ("ctrl", Modifiers::CTRL),
+    ("control", Modifiers::CTRL),
+    ("ctl", Modifiers::CTRL),
+    ("alt", Modifiers::ALT),
+    ("option", Modifiers::ALT),
+    ("opt", Modifiers::ALT),
+    ("super", Modifiers::SUPER),
+    ("meta", Modifiers::SUPER),
+    ("win", Modifiers::SUPER),
+    ("windows", Modifiers::SUPER),
+    ("cmd", Modifiers::SUPER),
+    ("command", Modifiers::SUPER),
+    ("logo", Modifiers::SUPER),
+];
+
+/// Key names and symbols, matched ignoring case, and whether the character needs Shift (`?`,
+/// but not `K`): US-layout characters, the short names `Key::from_str` accepts, the variant
+/// names with and without their `Key`/`Digit` prefix, and the macOS key symbols. The first
+/// match wins, so main-block characters win over the numpad. `Key::from_str` and
+/// `Shortcut::parse_const` both read it.
+pub(crate) const KEY_NAMES: &[(&str, Key, bool)] = &[
+    ("`", Key::Grave, false),
+    ("~", Key::Grave, true),
+    ("1", Key::Digit1, false),
+    ("!", Key::Digit1, true),
+    ("2", Key::Digit2, false),
+    ("@", Key::Digit2, true),
+    ("3", Key::Digit3, false),
+    ("#", Key::Digit3, true),
+    ("4", Key::Digit4, false),
+    ("$", Key::Digit4, true),
+    ("5", Key::Digit5, false),
+    ("%", Key::Digit5, true),
+    ("6", Key::Digit6, false),
+    ("^", Key::Digit6, true),
+    ("7", Key::Digit7, false),
+    ("&", Key::Digit7, true),
+    ("8", Key::Digit8, false),
+    ("*", Key::Digit8, true),
+    ("9", Key::Digit9, false),
+    ("(", Key::Digit9, true),
+    ("0", Key::Digit0, false),
+    (")", Key::Digit0, true),
+    ("-", Key::Minus, false),
+    ("_", Key::Minus, true),
+    ("=", Key::Equal, false),
+    ("+", Key::Equal, true),
+    ("q", Key::KeyQ, false),
+    ("w", Key::KeyW, false),
+    ("e", Key::KeyE, false),
+    ("r", Key::KeyR, false),
+    ("t", Key::KeyT, false),
+    ("y", Key::KeyY, false),
+    ("u", Key::KeyU, false),
+    ("i", Key::KeyI, false),
+    ("o", Key::KeyO, false),
+    ("p", Key::KeyP, false),
+    ("[", Key::LeftBracket, false),
+    ("{", Key::LeftBracket, true),
+    ("]", Key::RightBracket, false),
+    ("}", Key::RightBracket, true),
+    ("\\", Key::Backslash, false),
+    ("|", Key::Backslash, true),
+    ("a", Key::KeyA, false),
+    ("s", Key::KeyS, false),
+    ("d", Key::KeyD, false),
+    ("f", Key::KeyF, false),
+    ("g", Key::KeyG, false),
+    ("h", Key::KeyH, false),
+    ("j", Key::KeyJ, false),
+    ("k", Key::KeyK, false),
+    ("l", Key::KeyL, false),
+    (";", Key::Semicolon, false),
+    (":", Key::Semicolon, true),
+    ("'", Key::Quote, false),
+    ("\"", Key::Quote, true),
+    ("z", Key::KeyZ, false),
+    ("x", Key::KeyX, false),
+    ("c", Key::KeyC, false),
+    ("v", Key::KeyV, false),
+    ("b", Key::KeyB, false),
+    ("n", Key::KeyN, false),
+    ("m", Key::KeyM, false),
+    (",", Key::Comma, false),
+    ("<", Key::Comma, true),
+    (".", Key::Period, false),
+    (">", Key::Period, true),
+    ("/", Key::Slash, false),
+    ("?", Key::Slash, true),
+    (" ", Key::Space, false),
+    ("\t", Key::Tab, false),
+    ("\n", Key::Enter, false),
+    ("\r", Key::Enter, false),
+    ("esc", Key::Escape, false),
+    ("return", Key::Enter, false),
+    ("ret", Key::Enter, false),
+    ("enter", Key::Enter, false),
+    ("cr", Key::Enter, false),
+    ("bs", Key::Backspace, false),
+    ("bksp", Key::Backspace, false),
+    ("spc", Key::Space, false),
+    ("del", Key::Delete, false),
+    ("ins", Key::Insert, false),
+    ("pgup", Key::PageUp, false),
+    ("prior", Key::PageUp, false),
+    ("pgdn", Key::PageDown, false),
+    ("pgdown", Key::PageDown, false),
+    ("next", Key::PageDown, false),
+    ("up", Key::ArrowUp, false),
+    ("down", Key::ArrowDown, false),
+    ("left", Key::ArrowLeft, false),
+    ("right", Key::ArrowRight, false),
+    ("caps", Key::CapsLock, false),
+    ("shift", Key::ShiftLeft, false),
+    ("ctrl", Key::ControlLeft, false),
+    ("control", Key::ControlLeft, false),
+    ("alt", Key::AltLeft, false),
+    ("option", Key::AltLeft, false),
+    ("altgr", Key::AltRight, false),
+    ("meta", Key::MetaLeft, false),
+    ("super", Key::MetaLeft, false),
+    ("win", Key::MetaLeft, false),
+    ("cmd", Key::MetaLeft, false),
+    ("command", Key::MetaLeft, false),
+    ("backtick", Key::Grave, false),
+    ("backquote", Key::Grave, false),
+    ("dash", Key::Minus, false),
+    ("hyphen", Key::Minus, false),
+    ("equals", Key::Equal, false),
+    ("plus", Key::Equal, false),
+    ("dot", Key::Period, false),
+    ("apostrophe", Key::Quote, false),
+    ("lbracket", Key::LeftBracket, false),
+    ("bracketleft", Key::LeftBracket, false),
+    ("rbracket", Key::RightBracket, false),
+    ("bracketright", Key::RightBracket, false),
+    ("print", Key::PrintScreen, false),
+    ("prtsc", Key::PrintScreen, false),
+    ("sysrq", Key::PrintScreen, false),
+    ("break", Key::Pause, false),
+    ("Escape", Key::Escape, false),
+    ("F1", Key::F1, false),
+    ("F2", Key::F2, false),
+    ("F3", Key::F3, false),
+    ("F4", Key::F4, false),
+    ("F5", Key::F5, false),
+    ("F6", Key::F6, false),
+    ("F7", Key::F7, false),
+    ("F8", Key::F8, false),
+    ("F9", Key::F9, false),
+    ("F10", Key::F10, false),
+    ("F11", Key::F11, false),
+    ("F12", Key::F12, false),
+    ("Grave", Key::Grave, false),
+    ("Digit1", Key::Digit1, false),
+    ("Digit2", Key::Digit2, false),
+    ("Digit3", Key::Digit3, false),
+    ("Digit4", Key::Digit4, false),
+    ("Digit5", Key::Digit5, false),
+    ("Digit6", Key::Digit6, false),
+    ("Digit7", Key::Digit7, false),
+    ("Digit8", Key::Digit8, false),
+    ("Digit9", Key::Digit9, false),
+    ("Digit0", Key::Digit0, false),
+    ("Minus", Key::Minus, false),
+    ("Equal", Key::Equal, false),
+    ("Backspace", Key::Backspace, false),
+    ("Tab", Key::Tab, false),
+    ("KeyQ", Key::KeyQ, false),
+    ("KeyW", Key::KeyW, false),
+    ("KeyE", Key::KeyE, false),
+    ("KeyR", Key::KeyR, false),
+    ("KeyT", Key::KeyT, false),
+    ("KeyY", Key::KeyY, false),
+    ("KeyU", Key::KeyU, false),
+    ("KeyI", Key::KeyI, false),
+    ("KeyO", Key::KeyO, false),
+    ("KeyP", Key::KeyP, false),
+    ("LeftBracket", Key::LeftBracket, false),
+    ("RightBracket", Key::RightBracket, false),
+    ("Backslash", Key::Backslash, false),
+    ("CapsLock", Key::CapsLock, false),
+    ("KeyA", Key::KeyA, false),
+    ("KeyS", Key::KeyS, false),
+    ("KeyD", Key::KeyD, false),
+    ("KeyF", Key::KeyF, false),
+    ("KeyG", Key::KeyG, false),
+    ("KeyH", Key::KeyH, false),
+    ("KeyJ", Key::KeyJ, false),
+    ("KeyK", Key::KeyK, false),
+    ("KeyL", Key::KeyL, false),
+    ("Semicolon", Key::Semicolon, false),
+    ("Quote", Key::Quote, false),
+    ("ShiftLeft", Key::ShiftLeft, false),
+    ("KeyZ", Key::KeyZ, false),
+    ("KeyX", Key::KeyX, false),
+    ("KeyC", Key::KeyC, false),
+    ("KeyV", Key::KeyV, false),
+    ("KeyB", Key::KeyB, false),
+    ("KeyN", Key::KeyN, false),
+    ("KeyM", Key::KeyM, false),
+    ("Comma", Key::Comma, false),
+    ("Period", Key::Period, false),
+    ("Slash", Key::Slash, false),
+    ("ShiftRight", Key::ShiftRight, false),
+    ("ControlLeft", Key::ControlLeft, false),
+    ("AltLeft", Key::AltLeft, false),
+    ("Space", Key::Space, false),
+    ("AltRight", Key::AltRight, false),
+    ("ControlRight", Key::ControlRight, false),
+    ("MetaLeft", Key::MetaLeft, false),
+    ("MetaRight", Key::MetaRight, false),
+    ("ArrowLeft", Key::ArrowLeft, false),
+    ("ArrowRight", Key::ArrowRight, false),
+    ("ArrowUp", Key::ArrowUp, false),
+    ("ArrowDown", Key::ArrowDown, false),
+    ("Insert", Key::Insert, false),
+    ("Delete", Key::Delete, false),
+    ("Home", Key::Home, false),
+    ("End", Key::End, false),
+    ("PageUp", Key::PageUp, false),
+    ("PageDown", Key::PageDown, false),
+    ("NumLock", Key::NumLock, false),
+    ("Numpad0", Key::Numpad0, false),
+    ("Numpad1", Key::Numpad1, false),
+    ("Numpad2", Key::Numpad2, false),
+    ("Numpad3", Key::Numpad3, false),
+    ("Numpad4", Key::Numpad4, false),
+    ("Numpad5", Key::Numpad5, false),
+    ("Numpad6", Key::Numpad6, false),
+    ("Numpad7", Key::Numpad7, false),
+    ("Numpad8", Key::Numpad8, false),
+    ("Numpad9", Key::Numpad9, false),
+    ("NumpadDivide", Key::NumpadDivide, false),
+    ("NumpadMultiply", Key::NumpadMultiply, false),
+    ("NumpadSubtract", Key::NumpadSubtract, false),
+    ("NumpadAdd", Key::NumpadAdd, false),
+    ("NumpadEnter", Key::NumpadEnter, false),
+    ("NumpadDecimal", Key::NumpadDecimal, false),
+    ("PrintScreen", Key::PrintScreen, false),
+    ("ScrollLock", Key::ScrollLock, false),
+    ("Pause", Key::Pause, false),
+    ("⏎", Key::Enter, false),
+    ("↩", Key::Enter, false),
+    ("↵", Key::Enter, false),
+    ("⌫", Key::Backspace, false),
+    ("⌦", Key::Delete, false),
+    ("⎋", Key::Escape, false),
+    ("⇥", Key::Tab, false),
+    ("␣", Key::Space, false),
+    ("←", Key::ArrowLeft, false),
+    ("→", Key::ArrowRight, false),
+    ("↑", Key::ArrowUp, false),
+    ("↓", Key::ArrowDown, false),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_key_parses_from_its_name() {
+        for key in Key::ALL {
+            assert_eq!(key.to_string().parse::<Key>(), Ok(key));
+            assert_eq!(crate::shortcut::key_name(key).parse::<Key>(), Ok(key));
+        }
+    }
+
+    #[test]
+    fn key_names_agree_with_the_us_layout() {
+        for c in (' '..='~').chain(['\t', '\n']) {
+            let named = key_named(c.to_string().as_bytes(), 0, 1);
+            match char_key(c) {
+                Some((key, _)) if c.is_ascii_uppercase() => assert_eq!(named, Some((key, false)), "{:?}", c),
+                expected => assert_eq!(named, expected, "{:?}", c),
+            }
+        }
+    }
+}
@@ -0,0 +1,896 @@
+use crate::encode::us_char;
+use crate::names::{char_key, modifier_named};
+use crate::{Key, Modifiers, Shortcut, Stroke};
+use std::fmt;
+
+/// A key notation used by another program, for importing and exporting bindings.
+///
+/// | Notation  | Example                 | Sequences                 |
+/// |-----------|-------------------------|---------------------------|
+/// | `Emacs`   | `C-M-x`, `C-x C-s`      | space-separated           |
+/// | `Vim`     | `<C-S-x>`, `<leader>ff` | written together          |
+/// | `VsCode`  | `ctrl+shift+k`          | space-separated chords    |
+/// | `Gtk`     | `<Control><Shift>k`     | none                      |
+/// | `Tmux`    | `C-b`, `M-Enter`        | none                      |
+/// | `Windows` | `Ctrl+Shift+K`          | comma-separated           |
+///
+/// Only Vim has `<leader>`, and only the Windows style tells left and right modifiers apart.
+/// In Emacs, Vim and tmux an upper-case letter means Shift except after Ctrl, as in those
+/// programs; the other notations ignore the case of letters and always spell Shift out.
+///
+/// ```
+/// use keypress::Notation;
+///
+/// assert_eq!(Notation::Emacs.convert("C-x C-s", Notation::VsCode)?, "ctrl+x ctrl+s");
+/// assert_eq!(Notation::Gtk.convert("<Control><Shift>k", Notation::Vim)?, "<C-S-k>");
+/// assert!(Notation::Vim.convert("<leader>f", Notation::Tmux).is_err());
+/// # Ok::<(), keypress::NotationError>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Notation {
+    Emacs,
+    Vim,
+    /// VS Code's `keybindings.json`.
+    VsCode,
+    /// GTK accelerators.
+    Gtk,
+    Tmux,
+    /// The style of Windows menus and documentation, `Ctrl+Shift+K`.
+    Windows,
+}
+
+/// Returned when key text cannot be read, or a binding cannot be written, in a `Notation`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotationError {
+    /// `text` is not a key description the notation accepts.
+    Invalid { notation: Notation, text: String, reason: String },
+    /// The notation has no way to write `stroke`.
+    Inexpressible { notation: Notation, stroke: Stroke, reason: String },
+}
+
+impl fmt::Display for Notation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Notation::Emacs => "Emacs",
+            Notation::Vim => "Vim",
+            Notation::VsCode => "VS Code",
+            Notation::Gtk => "GTK",
+            Notation::Tmux => "tmux",
+            Notation::Windows => "Windows",
+        })
+    }
+}
+
+impl fmt::Display for NotationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NotationError::Invalid { notation, text, reason } => {
+                write!(f, "invalid {} key description {:?}: {}", notation, text, reason)
+            }
+            NotationError::Inexpressible { notation, stroke, reason } => {
+                write!(f, "{} notation cannot express {}: {}", notation, stroke, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for NotationError {}
+
+impl Notation {
+    /// Reads a binding written in this notation.
+    pub fn parse(self, text: &str) -> Result<Vec<Stroke>, NotationError> {
+        let strokes = match self {
+            Notation::Emacs => text.split_whitespace().map(|t| self.parse_emacs(t)).collect::<Result<Vec<_>, _>>()?,
+            Notation::Vim => self.parse_vim(text)?,
+            Notation::VsCode => text.split_whitespace().map(|t| self.parse_vscode(t)).collect::<Result<Vec<_>, _>>()?,
+            Notation::Gtk => vec![self.parse_gtk(text.trim())?],
+            Notation::Tmux => vec![self.parse_tmux(text.trim())?],
+            Notation::Windows => {
+                windows_strokes(text).into_iter().map(|t| self.parse_windows(t)).collect::<Result<Vec<_>, _>>()?
+            }
+        };
+        if strokes.is_empty() {
+            return Err(self.invalid(text, "no keys"));
+        }
+        Ok(strokes)
+    }
+
+    /// Writes a binding in this notation, or reports the first stroke it cannot express.
+    pub fn format(self, strokes: &[Stroke]) -> Result<String, NotationError> {
+        if strokes.len() > 1 && matches!(self, Notation::Gtk | Notation::Tmux) {
+            return Err(self.inexpressible(strokes[1], "it binds single keys, not sequences"));
+        }
+        let written = strokes.iter().map(|&s| self.format_stroke(s)).collect::<Result<Vec<_>, _>>()?;
+        Ok(written.join(match self {
+            Notation::Vim => "",
+            Notation::Windows => ", ",
+            _ => " ",
+        }))
+    }
+
+    /// Rewrites a binding from this notation into another.
+    pub fn convert(self, text: &str, to: Notation) -> Result<String, NotationError> {
+        to.format(&self.parse(text)?)
+    }
+
+    fn invalid(self, text: &str, reason: impl Into<String>) -> NotationError {
+        NotationError::Invalid { notation: self, text: text.to_string(), reason: reason.into() }
+    }
+
+    fn inexpressible(self, stroke: Stroke, reason: impl Into<String>) -> NotationError {
+        NotationError::Inexpressible { notation: self, stroke, reason: reason.into() }
+    }
+
+    fn format_stroke(self, stroke: Stroke) -> Result<String, NotationError> {
+        let shortcut = match stroke {
+            Stroke::Key(shortcut) => shortcut,
+            Stroke::Leader if self == Notation::Vim => return Ok("<leader>".to_string()),
+            Stroke::Leader => return Err(self.inexpressible(stroke, "it has no leader key")),
+        };
+        if self != Notation::Windows && !(shortcut.left.is_empty() && shortcut.right.is_empty()) {
+            return Err(self.inexpressible(stroke, "it does not tell left and right modifiers apart"));
+        }
+        let written = match self {
+            Notation::Emacs => format_emacs(shortcut),
+            Notation::Vim => format_vim(shortcut),
+            Notation::VsCode => format_vscode(shortcut),
+            Notation::Gtk => format_gtk(shortcut),
+            Notation::Tmux => {
+                if shortcut.modifiers.contains(Modifiers::SUPER) {
+                    return Err(self.inexpressible(stroke, "it has no Super modifier"));
+                }
+                if shortcut.modifiers.contains(Modifiers::CTRL | Modifiers::SHIFT) && is_letter(shortcut.key) {
+                    return Err(self.inexpressible(stroke, "terminals send Ctrl+Shift+letter as Ctrl+letter"));
+                }
+                format_tmux(shortcut)
+            }
+            Notation::Windows => format_windows(shortcut),
+        };
+        written.ok_or_else(|| self.inexpressible(stroke, format!("it has no name for {}", shortcut.key)))
+    }
+
+    /// `C-x`, `M-RET`, `C-<f1>`, `<C-f1>`, `S-<up>`.
+    fn parse_emacs(self, text: &str) -> Result<Stroke, NotationError> {
+        if text.starts_with("H-") && text.len() > 2 {
+            return Err(self.invalid(text, "Hyper is not supported"));
+        }
+        let (mut mods, rest) = strip_prefixes(text, EMACS_PREFIXES, false);
+        let name = match rest.strip_prefix('<').and_then(|r| r.strip_suffix('>')) {
+            Some(inner) if !inner.is_empty() => {
+                let (inner_mods, inner) = strip_prefixes(inner, EMACS_PREFIXES, false);
+                mods |= inner_mods;
+                format!("<{}>", inner)
+            }
+            _ => rest.to_string(),
+        };
+        let (key, shift) = named_or_char(EMACS_KEYS, &name, mods).ok_or_else(|| self.invalid(text, format!("unknown key {:?}", name)))?;
+        Ok(stroke(key, mods, shift))
+    }
+
+    /// Keys written together: `gg`, `<C-x><C-s>`, `<leader>ff`, `<M-lt>`.
+    fn parse_vim(self, text: &str) -> Result<Vec<Stroke>, NotationError> {
+        let mut strokes = Vec::new();
+        let mut rest = text;
+        while let Some(c) = rest.chars().next() {
+            if c == '<' {
+                if let Some((stroke, len)) = self.vim_bracket(rest)? {
+                    strokes.push(stroke);
+                    rest = &rest[len..];
+                    continue;
+                }
+            }
+            let (key, shift) = char_key(c).ok_or_else(|| self.invalid(text, format!("unknown key {:?}", c)))?;
+            strokes.push(stroke(key, Modifiers::NONE, shift));
+            rest = &rest[c.len_utf8()..];
+        }
+        Ok(strokes)
+    }
+
+    /// The `<...>` form at the start of `text` and its length, or `None` for a literal `<`.
+    fn vim_bracket(self, text: &str) -> Result<Option<(Stroke, usize)>, NotationError> {
+        // The key may itself be `>`, as in `<M->>`.
+        let Some(close) = text.get(2..).and_then(|t| t.find('>')).map(|i| i + 2) else {
+            return Ok(None);
+        };
+        let close = if text[..close].ends_with('-') && text[close + 1..].starts_with('>') { close + 1 } else { close };
+        let inner = &text[1..close];
+        if inner.eq_ignore_ascii_case("leader") {
+            return Ok(Some((Stroke::Leader, close + 1)));
+        }
+        let (mods, name) = strip_prefixes(inner, VIM_PREFIXES, true);
+        let name = match name {
+            _ if name.eq_ignore_ascii_case("lt") => "<",
+            _ if name.eq_ignore_ascii_case("bar") => "|",
+            _ if name.eq_ignore_ascii_case("bslash") => "\\",
+            _ => name,
+        };
+        match named_or_char(VIM_KEYS, name, mods) {
+            Some((key, shift)) => Ok(Some((stroke(key, mods, shift), close + 1))),
+            None if mods.is_empty() => Ok(None),
+            None => Err(self.invalid(&text[..close + 1], format!("unknown key {:?}", name))),
+        }
+    }
+
+    /// `ctrl+shift+k`, `shift+alt+down`, `ctrl+[Minus]`.
+    fn parse_vscode(self, text: &str) -> Result<Stroke, NotationError> {
+        let (mods, name) = match text.strip_suffix("++") {
+            Some(mods) => (mods, "+"),
+            None => text.rsplit_once('+').unwrap_or(("", text)),
+        };
+        let mut modifiers = Modifiers::NONE;
+        for m in mods.split('+').filter(|m| !m.is_empty()) {
+            modifiers |= modifier_named(m.as_bytes(), 0, m.len()).ok_or_else(|| self.invalid(text, format!("unknown modifier {:?}", m)))?;
+        }
+        // `[KeyK]`: a scan code, named like `Key`'s variants.
+        let scan = name.strip_prefix('[').and_then(|n| n.strip_suffix(']')).and_then(|n| n.parse::<Key>().ok());
+        let (key, shift) = match scan {
+            Some(key) => (key, false),
+            None => named_or_char(VSCODE_KEYS, name, Modifiers::CTRL)
+                .ok_or_else(|| self.invalid(text, format!("unknown key {:?}", name)))?,
+        };
+        Ok(stroke(key, modifiers, shift))
+    }
+
+    /// `<Control><Shift>k`, `<Primary>plus`, `<Alt>F4`.
+    fn parse_gtk(self, text: &str) -> Result<Stroke, NotationError> {
+        let mut mods = Modifiers::NONE;
+        let mut rest = text;
+        while let Some((m, tail)) = rest.strip_prefix('<').and_then(|r| r.split_once('>')) {
+            mods |= match m.to_ascii_lowercase().as_str() {
+                "primary" => Modifiers::CTRL,
+                "shft" => Modifiers::SHIFT,
+                "mod1" => Modifiers::ALT,
+                _ => modifier_named(m.as_bytes(), 0, m.len()).ok_or_else(|| self.invalid(text, format!("unsupported modifier <{}>", m)))?,
+            };
+            rest = tail;
+        }
+        // GTK lower-cases letter keyvals, so `<Control>K` is Ctrl+K; Shift is always spelled out.
+        let key = match GTK_CHARS.iter().find(|(_, name)| *name == rest) {
+            Some(&(c, _)) => char_key(c),
+            None if rest.len() == 1 && rest.as_bytes()[0].is_ascii_alphabetic() => char_key(rest.as_bytes()[0].to_ascii_lowercase() as char),
+            None => named_or_char(GTK_KEYS, rest, Modifiers::NONE),
+        };
+        let (key, shift) = key.ok_or_else(|| self.invalid(text, format!("unknown keysym {:?}", rest)))?;
+        Ok(stroke(key, mods, shift))
+    }
+
+    /// `C-b`, `M-Enter`, `^a`, `S-Up`, `BTab`.
+    fn parse_tmux(self, text: &str) -> Result<Stroke, NotationError> {
+        let (mods, name) = strip_prefixes(text, TMUX_PREFIXES, false);
+        if name.eq_ignore_ascii_case("btab") {
+            return Ok(stroke(Key::Tab, mods, true));
+        }
+        let (key, shift) = named_or_char(TMUX_KEYS, name, mods).ok_or_else(|| self.invalid(text, format!("unknown key {:?}", name)))?;
+        Ok(stroke(key, mods, shift))
+    }
+
+    /// `Ctrl+Shift+K`, `Alt+F4`, `Win+Left`: whatever `Shortcut` reads.
+    fn parse_windows(self, text: &str) -> Result<Stroke, NotationError> {
+        text.parse().map(Stroke::Key).map_err(|err: crate::ParseShortcutError| self.invalid(text, err.to_string()))
+    }
+}
+
+/// Splits `Ctrl+K, Ctrl+C` into its strokes; a comma right after `+`, or on its own, is the
+/// comma key.
+fn windows_strokes(text: &str) -> Vec<&str> {
+    let mut strokes = Vec::new();
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        let current = text[start..i].trim();
+        if c == ',' && !current.is_empty() && !current.ends_with('+') {
+            strokes.push(current);
+            start = i + 1;
+        }
+    }
+    strokes.push(text[start..].trim());
+    strokes.retain(|s| !s.is_empty());
+    strokes
+}
+
+fn stroke(key: Key, mods: Modifiers, shift: bool) -> Stroke {
+    let mods = if shift { mods | Modifiers::SHIFT } else { mods };
+    Stroke::Key(Shortcut::new(key, mods))
+}
+
+/// Strips modifier prefixes such as `C-` or `M-`, leaving at least one character.
+fn strip_prefixes<'a>(text: &'a str, prefixes: &[(&str, Modifiers)], ignore_case: bool) -> (Modifiers, &'a str) {
+    let mut mods = Modifiers::NONE;
+    let mut rest = text;
+    'outer: loop {
+        for &(prefix, m) in prefixes {
+            let found = match rest.get(..prefix.len()) {
+                Some(head) if ignore_case => head.eq_ignore_ascii_case(prefix),
+                Some(head) => head == prefix,
+                None => false,
+            };
+            if found && rest.len() > prefix.len() {
+                mods |= m;
+                rest = &rest[prefix.len()..];
+                continue 'outer;
+            }
+        }
+        return (mods, rest);
+    }
+}
+
+/// A key by its name in `table` or as the single character it types, and whether that
+/// character needs Shift. With Ctrl in `mods` letters ignore case.
+fn named_or_char(table: &[(Key, &str)], name: &str, mods: Modifiers) -> Option<(Key, bool)> {
+    if let Some(&(key, _)) = table.iter().find(|(_, n)| n.eq_ignore_ascii_case(name)) {
+        return Some((key, false));
+    }
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if mods.contains(Modifiers::CTRL) && c.is_ascii_alphabetic() => char_key(c.to_ascii_lowercase()),
+        (Some(c), None) => char_key(c),
+        _ => None,
+    }
+}
+
+fn name_of(table: &[(Key, &'static str)], key: Key) -> Option<&'static str> {
+    table.iter().find(|(k, _)| *k == key).map(|&(_, name)| name)
+}
+
+fn is_letter(key: Key) -> bool {
+    us_char(key, false).is_some_and(|c| c.is_ascii_lowercase())
+}
+
+/// The character a main-block key types, with Shift folded into it: `?` for Shift+/, and for
+/// letters `K` when `upper` allows it or `k` with Shift left in the modifiers.
+fn typed(key: Key, mods: Modifiers, upper: bool) -> Option<(char, Modifiers)> {
+    let plain = us_char(key, false).filter(|&c| c != ' ' && char_key(c) == Some((key, false)))?;
+    if !mods.contains(Modifiers::SHIFT) || (plain.is_ascii_lowercase() && !upper) {
+        return Some((plain, mods));
+    }
+    Some((us_char(key, true)?, mods.difference(Modifiers::SHIFT)))
+}
+
+fn prefixed(mods: Modifiers, order: &[(Modifiers, &str)], name: &str) -> String {
+    let mut text: String = order.iter().filter(|(m, _)| mods.contains(*m)).map(|(_, p)| *p).collect();
+    text.push_str(name);
+    text
+}
+
+fn format_emacs(shortcut: Shortcut) -> Option<String> {
+    let (name, mods) = match name_of(EMACS_KEYS, shortcut.key) {
+        Some(name) => (name.to_string(), shortcut.modifiers),
+        None => {
+            let (c, mods) = typed(shortcut.key, shortcut.modifiers, !shortcut.modifiers.contains(Modifiers::CTRL))?;
+            (c.to_string(), mods)
+        }
+    };
+    let order = [(Modifiers::CTRL, "C-"), (Modifiers::ALT, "M-"), (Modifiers::SHIFT, "S-"), (Modifiers::SUPER, "s-")];
+    Some(prefixed(mods, &order, &name))
+}
+
+fn format_vim(shortcut: Shortcut) -> Option<String> {
+    let (name, mods) = match name_of(VIM_KEYS, shortcut.key) {
+        Some(name) => (name.to_string(), shortcut.modifiers),
+        None => {
+            let (c, mods) = typed(shortcut.key, shortcut.modifiers, !shortcut.modifiers.contains(Modifiers::CTRL))?;
+            let name = match c {
+                '<' => "lt".to_string(),
+                '|' => "Bar".to_string(),
+                '\\' => "Bslash".to_string(),
+                _ if mods.is_empty() => return Some(c.to_string()),
+                _ => c.to_string(),
+            };
+            (name, mods)
+        }
+    };
+    let order = [(Modifiers::CTRL, "C-"), (Modifiers::ALT, "M-"), (Modifiers::SHIFT, "S-"), (Modifiers::SUPER, "D-")];
+    Some(format!("<{}>", prefixed(mods, &order, &name)))
+}
+
+fn format_vscode(shortcut: Shortcut) -> Option<String> {
+    let name = match name_of(VSCODE_KEYS, shortcut.key) {
+        Some(name) => name.to_string(),
+        None => typed(shortcut.key, Modifiers::NONE, false)?.0.to_string(),
+    };
+    let order = [(Modifiers::CTRL, "ctrl+"), (Modifiers::SHIFT, "shift+"), (Modifiers::ALT, "alt+"), (Modifiers::SUPER, "meta+")];
+    Some(prefixed(shortcut.modifiers, &order, &name))
+}
+
+fn format_gtk(shortcut: Shortcut) -> Option<String> {
+    let (name, mods) = match name_of(GTK_KEYS, shortcut.key) {
+        Some(name) => (name.to_string(), shortcut.modifiers),
+        None => {
+            let (c, mods) = typed(shortcut.key, shortcut.modifiers, false)?;
+            match GTK_CHARS.iter().find(|(ch, _)| *ch == c) {
+                Some((_, name)) => (name.to_string(), mods),
+                None => (c.to_string(), mods),
+            }
+        }
+    };
+    let order = [(Modifiers::CTRL, "<Control>"), (Modifiers::SHIFT, "<Shift>"), (Modifiers::ALT, "<Alt>"), (Modifiers::SUPER, "<Super>")];
+    Some(prefixed(mods, &order, &name))
+}
+
+fn format_tmux(shortcut: Shortcut) -> Option<String> {
+    let (name, mods) = match (shortcut.key, name_of(TMUX_KEYS, shortcut.key)) {
+        (Key::Tab, _) if shortcut.modifiers.contains(Modifiers::SHIFT) => {
+            ("BTab".to_string(), shortcut.modifiers.difference(Modifiers::SHIFT))
+        }
+        (_, Some(name)) => (name.to_string(), shortcut.modifiers),
+        (_, None) => {
+            let (c, mods) = typed(shortcut.key, shortcut.modifiers, true)?;
+            (c.to_string(), mods)
+        }
+    };
+    let order = [(Modifiers::CTRL, "C-"), (Modifiers::ALT, "M-"), (Modifiers::SHIFT, "S-")];
+    Some(prefixed(mods, &order, &name))
+}
+
+fn format_windows(shortcut: Shortcut) -> Option<String> {
+    let name = match name_of(WINDOWS_KEYS, shortcut.key) {
+        Some(name) => name.to_string(),
+        None => match typed(shortcut.key, Modifiers::NONE, false) {
+            Some((c, _)) => c.to_ascii_uppercase().to_string(),
+            None => shortcut.key.to_string(),
+        },
+    };
+    let mut text = String::new();
+    for (m, word) in [(Modifiers::CTRL, "Ctrl"), (Modifiers::ALT, "Alt"), (Modifiers::SHIFT, "Shift"), (Modifiers::SUPER, "Win")] {
+        if shortcut.right.contains(m) {
+            text.push_str("Right");
+        } else if shortcut.left.contains(m) {
+            text.push_str("Left");
+        } else if !shortcut.modifiers.contains(m) {
+            continue;
+        }
+        text.push_str(word);
+        text.push('+');
+    }
+    text.push_str(&name);
+    Some(text)
+}
+
+const EMACS_PREFIXES: &[(&str, Modifiers)] = &[
+    ("C-", Modifiers::CTRL),
+    ("M-", Modifiers::ALT),
+    ("A-", Modifiers::ALT),
+    ("S-", Modifiers::SHIFT),
+    ("s-", Modifiers::SUPER),
+];
+
+const VIM_PREFIXES: &[(&str, Modifiers)] = &[
+    ("C-", Modifiers::CTRL),
+    ("M-", Modifiers::ALT),
+    ("A-", Modifiers::ALT),
+    ("S-", Modifiers::SHIFT),
+    ("D-", Modifiers::SUPER),
+];
+
+const TMUX_PREFIXES: &[(&str, Modifiers)] =
+    &[("C-", Modifiers::CTRL), ("^", Modifiers::CTRL), ("M-", Modifiers::ALT), ("S-", Modifiers::SHIFT)];
+
+// Key name tables: the first name of a key is the one written, all are read.
+
+const EMACS_KEYS: &[(Key, &str)] = &[
+    (Key::Enter, "RET"),
+    (Key::Tab, "TAB"),
+    (Key::Space, "SPC"),
+    (Key::Escape, "ESC"),
+    (Key::Backspace, "DEL"),
+    (Key::Delete, "<delete>"),
+    (Key::Insert, "<insert>"),
+    (Key::Home, "<home>"),
+    (Key::End, "<end>"),
+    (Key::PageUp, "<prior>"),
+    (Key::PageDown, "<next>"),
+    (Key::ArrowLeft, "<left>"),
+    (Key::ArrowRight, "<right>"),
+    (Key::ArrowUp, "<up>"),
+    (Key::ArrowDown, "<down>"),
+    (Key::F1, "<f1>"),
+    (Key::F2, "<f2>"),
+    (Key::F3, "<f3>"),
+    (Key::F4, "<f4>"),
+    (Key::F5, "<f5>"),
+    (Key::F6, "<f6>"),
+    (Key::F7, "<f7>"),
+    (Key::F8, "<f8>"),
+    (Key::F9, "<f9>"),
+    (Key::F10, "<f10>"),
+    (Key::F11, "<f11>"),
+    (Key::F12, "<f12>"),
+    (Key::Numpad0, "<kp-0>"),
+    (Key::Numpad1, "<kp-1>"),
+    (Key::Numpad2, "<kp-2>"),
+    (Key::Numpad3, "<kp-3>"),
+    (Key::Numpad4, "<kp-4>"),
+    (Key::Numpad5, "<kp-5>"),
+    (Key::Numpad6, "<kp-6>"),
+    (Key::Numpad7, "<kp-7>"),
+    (Key::Numpad8, "<kp-8>"),
+    (Key::Numpad9, "<kp-9>"),
+    (Key::NumpadAdd, "<kp-add>"),
+    (Key::NumpadSubtract, "<kp-subtract>"),
+    (Key::NumpadMultiply, "<kp-multiply>"),
+    (Key::NumpadDivide, "<kp-divide>"),
+    (Key::NumpadDecimal, "<kp-decimal>"),
+    (Key::NumpadEnter, "<kp-enter>"),
+    (Key::PrintScreen, "<print>"),
+    (Key::Pause, "<pause>"),
+    (Key::Enter, "<return>"),
+    (Key::Tab, "<tab>"),
+    (Key::Escape, "<escape>"),
+    (Key::Backspace, "<backspace>"),
+    (Key::Delete, "<deletechar>"),
+    (Key::Space, "<space>"),
+];
+
+const VIM_KEYS: &[(Key, &str)] = &[
+    (Key::Enter, "CR"),
+    (Key::Tab, "Tab"),
+    (Key::Space, "Space"),
+    (Key::Escape, "Esc"),
+    (Key::Backspace, "BS"),
+    (Key::Delete, "Del"),
+    (Key::Insert, "Insert"),
+    (Key::Home, "Home"),
+    (Key::End, "End"),
+    (Key::PageUp, "PageUp"),
+    (Key::PageDown, "PageDown"),
+    (Key::ArrowLeft, "Left"),
+    (Key::ArrowRight, "Right"),
+    (Key::ArrowUp, "Up"),
+    (Key::ArrowDown, "Down"),
+    (Key::F1, "F1"),
+    (Key::F2, "F2"),
+    (Key::F3, "F3"),
+    (Key::F4, "F4"),
+    (Key::F5, "F5"),
+    (Key::F6, "F6"),
+    (Key::F7, "F7"),
+    (Key::F8, "F8"),
+    (Key::F9, "F9"),
+    (Key::F10, "F10"),
+    (Key::F11, "F11"),
+    (Key::F12, "F12"),
+    (Key::Numpad0, "k0"),
+    (Key::Numpad1, "k1"),
+    (Key::Numpad2, "k2"),
+    (Key::Numpad3, "k3"),
+    (Key::Numpad4, "k4"),
+    (Key::Numpad5, "k5"),
+    (Key::Numpad6, "k6"),
+    (Key::Numpad7, "k7"),
+    (Key::Numpad8, "k8"),
+    (Key::Numpad9, "k9"),
+    (Key::NumpadAdd, "kPlus"),
+    (Key::NumpadSubtract, "kMinus"),
+    (Key::NumpadMultiply, "kMultiply"),
+    (Key::NumpadDivide, "kDivide"),
+    (Key::NumpadDecimal, "kPoint"),
+    (Key::NumpadEnter, "kEnter"),
+    (Key::Enter, "Enter"),
+    (Key::Enter, "Return"),
+    (Key::Escape, "Escape"),
+    (Key::Backspace, "BackSpace"),
+    (Key::Delete, "Delete"),
+    (Key::Insert, "Ins"),
+];
+
+const VSCODE_KEYS: &[(Key, &str)] = &[
+    (Key::Enter, "enter"),
+    (Key::Tab, "tab"),
+    (Key::Space, "space"),
+    (Key::Escape, "escape"),
+    (Key::Backspace, "backspace"),
+    (Key::Delete, "delete"),
+    (Key::Insert, "insert"),
+    (Key::Home, "home"),
+    (Key::End, "end"),
+    (Key::PageUp, "pageup"),
+    (Key::PageDown, "pagedown"),
+    (Key::ArrowLeft, "left"),
+    (Key::ArrowRight, "right"),
+    (Key::ArrowUp, "up"),
+    (Key::ArrowDown, "down"),
+    (Key::F1, "f1"),
+    (Key::F2, "f2"),
+    (Key::F3, "f3"),
+    (Key::F4, "f4"),
+    (Key::F5, "f5"),
+    (Key::F6, "f6"),
+    (Key::F7, "f7"),
+    (Key::F8, "f8"),
+    (Key::F9, "f9"),
+    (Key::F10, "f10"),
+    (Key::F11, "f11"),
+    (Key::F12, "f12"),
+    (Key::Numpad0, "numpad0"),
+    (Key::Numpad1, "numpad1"),
+    (Key::Numpad2, "numpad2"),
+    (Key::Numpad3, "numpad3"),
+    (Key::Numpad4, "numpad4"),
+    (Key::Numpad5, "numpad5"),
+    (Key::Numpad6, "numpad6"),
+    (Key::Numpad7, "numpad7"),
+    (Key::Numpad8, "numpad8"),
+    (Key::Numpad9, "numpad9"),
+    (Key::NumpadAdd, "numpad_add"),
+    (Key::NumpadSubtract, "numpad_subtract"),
+    (Key::NumpadMultiply, "numpad_multiply"),
+    (Key::NumpadDivide, "numpad_divide"),
+    (Key::NumpadDecimal, "numpad_decimal"),
+    (Key::CapsLock, "capslock"),
+    (Key::Pause, "pausebreak"),
+    (Key::Escape, "esc"),
+];
+
+const GTK_KEYS: &[(Key, &str)] = &[
+    (Key::Enter, "Return"),
+    (Key::Tab, "Tab"),
+    (Key::Space, "space"),
+    (Key::Escape, "Escape"),
+    (Key::Backspace, "BackSpace"),
+    (Key::Delete, "Delete"),
+    (Key::Insert, "Insert"),
+    (Key::Home, "Home"),
+    (Key::End, "End"),
+    (Key::PageUp, "Page_Up"),
+    (Key::PageDown, "Page_Down"),
+    (Key::ArrowLeft, "Left"),
+    (Key::ArrowRight, "Right"),
+    (Key::ArrowUp, "Up"),
+    (Key::ArrowDown, "Down"),
+    (Key::F1, "F1"),
+    (Key::F2, "F2"),
+    (Key::F3, "F3"),
+    (Key::F4, "F4"),
+    (Key::F5, "F5"),
+    (Key::F6, "F6"),
+    (Key::F7, "F7"),
+    (Key::F8, "F8"),
+    (Key::F9, "F9"),
+    (Key::F10, "F10"),
+    (Key::F11, "F11"),
+    (Key::F12, "F12"),
+    (Key::Numpad0, "KP_0"),
+    (Key::Numpad1, "KP_1"),
+    (Key::Numpad2, "KP_2"),
+    (Key::Numpad3, "KP_3"),
+    (Key::Numpad4, "KP_4"),
+    (Key::Numpad5, "KP_5"),
+    (Key::Numpad6, "KP_6"),
+    (Key::Numpad7, "KP_7"),
+    (Key::Numpad8, "KP_8"),
+    (Key::Numpad9, "KP_9"),
+    (Key::NumpadAdd, "KP_Add"),
+    (Key::NumpadSubtract, "KP_Subtract"),
+    (Key::NumpadMultiply, "KP_Multiply"),
+    (Key::NumpadDivide, "KP_Divide"),
+    (Key::NumpadDecimal, "KP_Decimal"),
+    (Key::NumpadEnter, "KP_Enter"),
+    (Key::PrintScreen, "Print"),
+    (Key::Pause, "Pause"),
+    (Key::ScrollLock, "Scroll_Lock"),
+    (Key::NumLock, "Num_Lock"),
+    (Key::CapsLock, "Caps_Lock"),
+    (Key::ShiftLeft, "Shift_L"),
+    (Key::ShiftRight, "Shift_R"),
+    (Key::ControlLeft, "Control_L"),
+    (Key::ControlRight, "Control_R"),
+    (Key::AltLeft, "Alt_L"),
+    (Key::AltRight, "Alt_R"),
+    (Key::MetaLeft, "Super_L"),
+    (Key::MetaRight, "Super_R"),
+    (Key::PageUp, "Prior"),
+    (Key::PageDown, "Next"),
+    (Key::AltRight, "ISO_Level3_Shift"),
+];
+
+/// X keysym names of the US-layout punctuation characters.
+const GTK_CHARS: &[(char, &str)] = &[
+    ('`', "grave"),
+    ('~', "asciitilde"),
+    ('!', "exclam"),
+    ('@', "at"),
+    ('#', "numbersign"),
+    ('$', "dollar"),
+    ('%', "percent"),
+    ('^', "asciicircum"),
+    ('&', "ampersand"),
+    ('*', "asterisk"),
+    ('(', "parenleft"),
+    (')', "parenright"),
+    ('-', "minus"),
+    ('_', "underscore"),
+    ('=', "equal"),
+    ('+', "plus"),
+    ('[', "bracketleft"),
+    ('{', "braceleft"),
+    (']', "bracketright"),
+    ('}', "braceright"),
+    ('\\', "backslash"),
+    ('|', "bar"),
+    (';', "semicolon"),
+    (':', "colon"),
+    ('\'', "apostrophe"),
+    ('"', "quotedbl"),
+    (',', "comma"),
+    ('<', "less"),
+    ('.', "period"),
+    ('>', "greater"),
+    ('/', "slash"),
+    ('?', "question"),
+];
+
+const TMUX_KEYS: &[(Key, &str)] = &[
+    (Key::Enter, "Enter"),
+    (Key::Tab, "Tab"),
+    (Key::Space, "Space"),
+    (Key::Escape, "Escape"),
+    (Key::Backspace, "BSpace"),
+    (Key::Delete, "DC"),
+    (Key::Insert, "IC"),
+    (Key::Home, "Home"),
+    (Key::End, "End"),
+    (Key::PageUp, "PPage"),
+    (Key::PageDown, "NPage"),
+    (Key::ArrowLeft, "Left"),
+    (Key::ArrowRight, "Right"),
+    (Key::ArrowUp, "Up"),
+    (Key::ArrowDown, "Down"),
+    (Key::F1, "F1"),
+    (Key::F2, "F2"),
+    (Key::F3, "F3"),
+    (Key::F4, "F4"),
+    (Key::F5, "F5"),
+    (Key::F6, "F6"),
+    (Key::F7, "F7"),
+    (Key::F8, "F8"),
+    (Key::F9, "F9"),
+    (Key::F10, "F10"),
+    (Key::F11, "F11"),
+    (Key::F12, "F12"),
+    (Key::Numpad0, "KP0"),
+    (Key::Numpad1, "KP1"),
+    (Key::Numpad2, "KP2"),
+    (Key::Numpad3, "KP3"),
+    (Key::Numpad4, "KP4"),
+    (Key::Numpad5, "KP5"),
+    (Key::Numpad6, "KP6"),
+    (Key::Numpad7, "KP7"),
+    (Key::Numpad8, "KP8"),
+    (Key::Numpad9, "KP9"),
+    (Key::NumpadAdd, "KP+"),
+    (Key::NumpadSubtract, "KP-"),
+    (Key::NumpadMultiply, "KP*"),
+    (Key::NumpadDivide, "KP/"),
+    (Key::NumpadDecimal, "KP."),
+    (Key::NumpadEnter, "KPEnter"),
+    (Key::PageUp, "PageUp"),
+    (Key::PageUp, "PgUp"),
+    (Key::PageDown, "PageDown"),
+    (Key::PageDown, "PgDn"),
+];
+
+const WINDOWS_KEYS: &[(Key, &str)] = &[
+    (Key::Escape, "Esc"),
+    (Key::Delete, "Del"),
+    (Key::Insert, "Ins"),
+    (Key::PageUp, "PgUp"),
+    (Key::PageDown, "PgDn"),
+    (Key::ArrowLeft, "Left"),
+    (Key::ArrowRight, "Right"),
+    (Key::ArrowUp, "Up"),
+    (Key::ArrowDown, "Down"),
+    (Key::PrintScreen, "PrtSc"),
+    (Key::Space, "Space"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reads each text, writes it back in canonical form and reads that again to the same strokes.
+    fn round_trip(notation: Notation, cases: &[(&str, &str)]) {
+        for &(text, canonical) in cases {
+            let strokes = notation.parse(text).unwrap_or_else(|err| panic!("{}: {}", text, err));
+            let written = notation.format(&strokes).unwrap_or_else(|err| panic!("{}: {}", text, err));
+            assert_eq!(written, canonical, "{} in {}", text, notation);
+            assert_eq!(notation.parse(&written).unwrap(), strokes, "{} in {}", written, notation);
+        }
+    }
+
+    #[test]
+    fn emacs_round_trip() {
+        round_trip(
+            Notation::Emacs,
+            &[
+                ("C-x C-s", "C-x C-s"),
+                ("C-M-x", "C-M-x"),
+                ("M-RET", "M-RET"),
+                ("C-<f1>", "C-<f1>"),
+                ("<C-f1>", "C-<f1>"),
+                ("S-<up>", "S-<up>"),
+                ("C-X", "C-x"),
+                ("M-X", "M-X"),
+                ("?", "?"),
+            ],
+        );
+    }
+
+    #[test]
+    fn vim_round_trip() {
+        round_trip(
+            Notation::Vim,
+            &[
+                ("gg", "gg"),
+                ("<C-x><C-s>", "<C-x><C-s>"),
+                ("<leader>ff", "<leader>ff"),
+                ("<M-lt>", "<M-lt>"),
+                ("<lt>", "<lt>"),
+                ("<c-S-x>", "<C-S-x>"),
+                ("<C-X>", "<C-x>"),
+                ("G", "G"),
+                ("<F5>", "<F5>"),
+                ("<S-Tab>", "<S-Tab>"),
+            ],
+        );
+    }
+
+    #[test]
+    fn vscode_round_trip() {
+        round_trip(
+            Notation::VsCode,
+            &[
+                ("ctrl+shift+k", "ctrl+shift+k"),
+                ("shift+alt+down", "shift+alt+down"),
+                ("ctrl+K ctrl+C", "ctrl+k ctrl+c"),
+                ("cmd+s", "meta+s"),
+                ("ctrl+[Minus]", "ctrl+-"),
+                ("ctrl++", "ctrl+shift+="),
+                ("f5", "f5"),
+            ],
+        );
+    }
+
+    #[test]
+    fn gtk_round_trip() {
+        round_trip(
+            Notation::Gtk,
+            &[
+                ("<Control><Shift>k", "<Control><Shift>k"),
+                ("<Control>K", "<Control>k"),
+                ("<Primary>plus", "<Control>plus"),
+                ("<Alt>F4", "<Alt>F4"),
+                ("<Super>Return", "<Super>Return"),
+                ("question", "question"),
+                ("K", "k"),
+            ],
+        );
+    }
+
+    #[test]
+    fn tmux_round_trip() {
+        round_trip(
+            Notation::Tmux,
+            &[("C-b", "C-b"), ("M-Enter", "M-Enter"), ("^a", "C-a"), ("S-Up", "S-Up"), ("BTab", "BTab"), ("C-B", "C-b"), ("B", "B")],
+        );
+    }
+
+    #[test]
+    fn windows_round_trip() {
+        round_trip(
+            Notation::Windows,
+            &[
+                ("Ctrl+Shift+K", "Ctrl+Shift+K"),
+                ("alt+f4", "Alt+F4"),
+                ("Ctrl+K, Ctrl+C", "Ctrl+K, Ctrl+C"),
+                ("Ctrl+,", "Ctrl+,"),
+                ("RCtrl+Enter", "RightCtrl+Enter"),
+                ("Win+Left", "Win+Left"),
+            ],
+        );
+    }
+
+    #[test]
+    fn gtk_ignores_the_case_of_letters() {
+        assert_eq!(Notation::Gtk.parse("<Control>K").unwrap(), Notation::Gtk.parse("<Control>k").unwrap());
+        assert_eq!(Notation::Gtk.convert("<Control>K", Notation::Windows).unwrap(), "Ctrl+K");
+    }
+}
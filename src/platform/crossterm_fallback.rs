@@ -1,29 +1,163 @@
-use crate::{Key, KeyEvent};
-use crossterm::event::{self, Event, KeyCode, KeyEvent as CrosstermKeyEvent, KeyModifiers};
+use super::tty::RawMode;
+use super::Source;
+use crate::names::char_key;
+use crate::{CancelHandle, Key, KeyEvent, KeyEventKind, Modifiers, Timestamp};
+use crossterm::event::{self, Event, KeyCode, KeyEvent as CrosstermKeyEvent, KeyModifiers, ModifierKeyCode};
 use std::io;
+#[cfg(feature = "async")]
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
 
-pub fn wait_key_with_mods() -> io::Result<KeyEvent> {
-    loop {
-        match event::read()? {
-            Event::Key(CrosstermKeyEvent {
-                code,
-                modifiers,
-                kind: event::KeyEventKind::Press,
-                ..
-            }) => {
-                let key = map_code(code);
-                let shift = modifiers.contains(KeyModifiers::SHIFT);
-                let ctrl = modifiers.contains(KeyModifiers::CONTROL);
-                let alt = modifiers.contains(KeyModifiers::ALT);
-                return Ok(KeyEvent { key, shift, ctrl, alt });
+pub struct TerminalSource {
+    raw: RawMode,
+    cancel: Option<CancelHandle>,
+}
+
+impl TerminalSource {
+    pub fn open(cancel: Option<CancelHandle>) -> io::Result<Self> {
+        Ok(TerminalSource { raw: RawMode::enable()?, cancel })
+    }
+
+    /// Blocks until crossterm has input to parse, the deadline passes or the wait is cancelled.
+    #[cfg(unix)]
+    fn wait_input(&self, deadline: Option<Instant>) -> io::Result<bool> {
+        use std::os::fd::AsRawFd;
+
+        if event::poll(Duration::ZERO)? {
+            return Ok(true);
+        }
+        let mut fds = vec![self.raw.as_raw_fd()];
+        if let Some(cancel) = &self.cancel {
+            fds.push(cancel.waker().as_raw_fd());
+        }
+        let ready = super::poll::wait_readable(&fds, deadline)?;
+        if let Some(cancel) = &self.cancel {
+            cancel.check()?;
+        }
+        Ok(ready[0])
+    }
+
+    #[cfg(not(unix))]
+    fn wait_input(&self, deadline: Option<Instant>) -> io::Result<bool> {
+        const SLICE: Duration = Duration::from_millis(50);
+        loop {
+            if let Some(cancel) = &self.cancel {
+                cancel.check()?;
+            }
+            let remaining = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+            let slice = match (&self.cancel, remaining) {
+                (Some(_), Some(remaining)) => remaining.min(SLICE),
+                (Some(_), None) => SLICE,
+                (None, Some(remaining)) => remaining,
+                (None, None) => return Ok(true),
+            };
+            if event::poll(slice)? {
+                return Ok(true);
+            }
+            if remaining.is_some_and(|r| r <= slice) {
+                return Ok(false);
             }
-            _ => continue,
         }
     }
 }
 
-fn map_code(code: KeyCode) -> Key {
-    match code {
+impl Source for TerminalSource {
+    fn next_event(&mut self, timeout: Option<Duration>) -> io::Result<Option<KeyEvent>> {
+        let deadline = timeout.map(|t| Instant::now() + t);
+        loop {
+            if let Some(cancel) = &self.cancel {
+                cancel.check()?;
+            }
+            if !self.wait_input(deadline)? {
+                return Ok(None);
+            }
+            if cfg!(unix) && !event::poll(Duration::ZERO)? {
+                continue;
+            }
+            if let Some(event) = map_event(event::read()?) {
+                return Ok(Some(event));
+            }
+        }
+    }
+
+    fn devices(&self) -> Vec<String> {
+        vec!["terminal".to_string()]
+    }
+
+    #[cfg(unix)]
+    fn raw_fd(&self) -> Option<std::os::fd::RawFd> {
+        use std::os::fd::AsRawFd;
+
+        Some(self.raw.as_raw_fd())
+    }
+}
+
+#[cfg(feature = "async")]
+pub struct TerminalStream {
+    events: event::EventStream,
+    _raw: RawMode,
+}
+
+#[cfg(feature = "async")]
+impl TerminalStream {
+    pub fn open() -> io::Result<Self> {
+        let raw = RawMode::enable()?;
+        Ok(TerminalStream { events: event::EventStream::new(), _raw: raw })
+    }
+}
+
+#[cfg(feature = "async")]
+impl super::AsyncSource for TerminalStream {
+    fn poll_next_event(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<KeyEvent>> {
+        use futures_core::Stream;
+
+        loop {
+            match std::pin::Pin::new(&mut self.events).poll_next(cx) {
+                Poll::Ready(Some(Ok(event))) => {
+                    if let Some(event) = map_event(event) {
+                        return Poll::Ready(Ok(event));
+                    }
+                }
+                Poll::Ready(Some(Err(err))) => return Poll::Ready(Err(err)),
+                Poll::Ready(None) => return Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into())),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+fn map_event(event: Event) -> Option<KeyEvent> {
+    match event {
+        Event::Key(CrosstermKeyEvent { code, modifiers, kind, .. }) => {
+            let (key, shifted) = map_code(code)?;
+            let kind = match kind {
+                event::KeyEventKind::Press => KeyEventKind::Press,
+                event::KeyEventKind::Repeat => KeyEventKind::Repeat,
+                event::KeyEventKind::Release => KeyEventKind::Release,
+            };
+            let shift = shifted || modifiers.contains(KeyModifiers::SHIFT);
+            let ctrl = modifiers.contains(KeyModifiers::CONTROL);
+            let alt = modifiers.contains(KeyModifiers::ALT);
+            let meta = modifiers.contains(KeyModifiers::SUPER);
+            Some(KeyEvent {
+                key,
+                kind,
+                shift,
+                ctrl,
+                alt,
+                meta,
+                right: Modifiers::NONE,
+                timestamp: Timestamp::now(),
+                device: 0,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// The key behind a crossterm key code, and whether the character it typed needs Shift.
+fn map_code(code: KeyCode) -> Option<(Key, bool)> {
+    let key = match code {
         KeyCode::Esc => Key::Escape,
         KeyCode::F(n) => match n {
             1 => Key::F1,
@@ -59,69 +193,38 @@ fn map_code(code: KeyCode) -> Key {
         KeyCode::PrintScreen => Key::PrintScreen,
         KeyCode::Pause => Key::Pause,
         KeyCode::Menu => Key::AltRight,
-        KeyCode::Char(' ') => Key::Space,
-        KeyCode::Char(c) => {
-            if c.is_ascii_digit() {
-                match c {
-                    '1' => Key::Digit1,
-                    '2' => Key::Digit2,
-                    '3' => Key::Digit3,
-                    '4' => Key::Digit4,
-                    '5' => Key::Digit5,
-                    '6' => Key::Digit6,
-                    '7' => Key::Digit7,
-                    '8' => Key::Digit8,
-                    '9' => Key::Digit9,
-                    '0' => Key::Digit0,
-                    _ => unreachable!(),
-                }
-            } else if c.is_ascii_lowercase() {
-                match c {
-                    'a' => Key::KeyA,
-                    'b' => Key::KeyB,
-                    'c' => Key::KeyC,
-                    'd' => Key::KeyD,
-                    'e' => Key::KeyE,
-                    'f' => Key::KeyF,
-                    'g' => Key::KeyG,
-                    'h' => Key::KeyH,
-                    'i' => Key::KeyI,
-                    'j' => Key::KeyJ,
-                    'k' => Key::KeyK,
-                    'l' => Key::KeyL,
-                    'm' => Key::KeyM,
-                    'n' => Key::KeyN,
-                    'o' => Key::KeyO,
-                    'p' => Key::KeyP,
-                    'q' => Key::KeyQ,
-                    'r' => Key::KeyR,
-                    's' => Key::KeyS,
-                    't' => Key::KeyT,
-                    'u' => Key::KeyU,
-                    'v' => Key::KeyV,
-                    'w' => Key::KeyW,
-                    'x' => Key::KeyX,
-                    'y' => Key::KeyY,
-                    'z' => Key::KeyZ,
-                    _ => Key::KeyZ,
-                }
-            } else {
-                match c {
-                    '`' | '~' => Key::Grave,
-                    '-' | '_' => Key::Minus,
-                    '=' | '+' => Key::Equal,
-                    '[' | '{' => Key::LeftBracket,
-                    ']' | '}' => Key::RightBracket,
-                    '\\' | '|' => Key::Backslash,
-                    ';' | ':' => Key::Semicolon,
-                    '\'' | '"' => Key::Quote,
-                    ',' | '<' => Key::Comma,
-                    '.' | '>' => Key::Period,
-                    '/' | '?' => Key::Slash,
-                    _ => Key::Space,
-                }
-            }
-        }
-        _ => Key::Space,
+        KeyCode::Modifier(ModifierKeyCode::LeftSuper | ModifierKeyCode::LeftMeta) => Key::MetaLeft,
+        KeyCode::Modifier(ModifierKeyCode::RightSuper | ModifierKeyCode::RightMeta) => Key::MetaRight,
+        KeyCode::BackTab => return Some((Key::Tab, true)),
+        KeyCode::Char(c) => return char_key(c),
+        _ => return None,
+    };
+    Some((key, false))
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(code: KeyCode, modifiers: KeyModifiers) -> Option<(Key, bool, bool)> {
+        map_event(Event::Key(CrosstermKeyEvent::new(code, modifiers))).map(|e| (e.key, e.shift, e.ctrl))
+    }
+
+    #[test]
+    fn maps_characters_with_their_shift() {
+        assert_eq!(map(KeyCode::Char('a'), KeyModifiers::NONE), Some((Key::KeyA, false, false)));
+        assert_eq!(map(KeyCode::Char('A'), KeyModifiers::NONE), Some((Key::KeyA, true, false)));
+        assert_eq!(map(KeyCode::Char('A'), KeyModifiers::SHIFT), Some((Key::KeyA, true, false)));
+        assert_eq!(map(KeyCode::Char('?'), KeyModifiers::NONE), Some((Key::Slash, true, false)));
+        assert_eq!(map(KeyCode::Char('/'), KeyModifiers::NONE), Some((Key::Slash, false, false)));
+        assert_eq!(map(KeyCode::Char(' '), KeyModifiers::NONE), Some((Key::Space, false, false)));
+        assert_eq!(map(KeyCode::Char('c'), KeyModifiers::CONTROL), Some((Key::KeyC, false, true)));
+        assert_eq!(map(KeyCode::BackTab, KeyModifiers::SHIFT), Some((Key::Tab, true, false)));
+        assert_eq!(map(KeyCode::F(5), KeyModifiers::NONE), Some((Key::F5, false, false)));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn drops_what_it_cannot_map() {
+        assert_eq!(map(KeyCode::Char('é'), KeyModifiers::NONE), None);
+        assert_eq!(map(KeyCode::Null, KeyModifiers::NONE), None);
+    }
+}
@@ -0,0 +1,213 @@
+//! Decoding of captured evdev streams with the same logic as the live backend.
+
+use super::linux::{Frame, FrameDecoder, KeyTracker};
+use crate::{Recording, Timestamp};
+use evdev::InputEvent;
+use std::collections::HashSet;
+use std::io::{self, BufRead, Read};
+use std::mem::size_of;
+use std::time::{Duration, UNIX_EPOCH};
+
+/// Parses `evemu-record` output: the `N:` line names the device and every `E:` line is one
+/// event (`E: <sec>.<usec> <type hex> <code hex> <value>`). Other lines are ignored.
+pub(crate) fn read_evemu(reader: impl BufRead) -> io::Result<Recording> {
+    let mut name = None;
+    let mut events = Vec::new();
+    for (n, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if let Some(device) = line.strip_prefix("N:") {
+            name.get_or_insert_with(|| device.trim().to_string());
+        } else if let Some(event) = line.strip_prefix("E:") {
+            let invalid = || io::Error::new(io::ErrorKind::InvalidData, format!("line {}: bad event {:?}", n + 1, line));
+            let mut fields = event.split_whitespace();
+            let time = fields.next().and_then(parse_seconds).ok_or_else(invalid)?;
+            let type_ = fields.next().and_then(|f| u16::from_str_radix(f, 16).ok()).ok_or_else(invalid)?;
+            let code = fields.next().and_then(|f| u16::from_str_radix(f, 16).ok()).ok_or_else(invalid)?;
+            let value = fields.next().and_then(|f| f.parse::<i32>().ok()).ok_or_else(invalid)?;
+            events.push((InputEvent::new(type_, code, value), time));
+        }
+    }
+    Ok(decode(name.unwrap_or_else(|| "evemu".to_string()), events, false))
+}
+
+/// Parses a raw dump of `struct input_event` records, as produced by `cat /dev/input/eventX`,
+/// in this machine's layout and byte order. The times are taken as wall-clock times, the
+/// kernel's default for an unconfigured device.
+pub(crate) fn read_input_events(mut reader: impl Read) -> io::Result<Recording> {
+    const LONG: usize = size_of::<libc::c_long>();
+    let mut record = [0u8; size_of::<libc::input_event>()];
+    let mut events = Vec::new();
+    loop {
+        match reader.read_exact(&mut record) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(err) => return Err(err),
+        }
+        let long = |at: usize| libc::c_long::from_ne_bytes(record[at..at + LONG].try_into().unwrap());
+        let sec = long(0).max(0) as u64;
+        let usec = long(LONG).clamp(0, 999_999) as u32;
+        let type_ = u16::from_ne_bytes([record[2 * LONG], record[2 * LONG + 1]]);
+        let code = u16::from_ne_bytes([record[2 * LONG + 2], record[2 * LONG + 3]]);
+        let value = i32::from_ne_bytes(record[2 * LONG + 4..2 * LONG + 8].try_into().unwrap());
+        events.push((InputEvent::new(type_, code, value), Duration::new(sec, usec * 1000)));
+    }
+    Ok(decode("input_event dump".to_string(), events, true))
+}
+
+/// Runs the events through frame decoding and key tracking as for a live device.
+///
+/// A dump has no kernel key state to resync from, so SYN_DROPPED releases every held key.
+fn decode(name: String, events: Vec<(InputEvent, Duration)>, wall: bool) -> Recording {
+    let mut frames = FrameDecoder::default();
+    let mut tracker = KeyTracker::new(1);
+    for (ev, time) in events {
+        let timestamp = Timestamp {
+            monotonic: time,
+            wall: if wall { UNIX_EPOCH.checked_add(time) } else { None },
+            latency: None,
+        };
+        match frames.feed(ev, timestamp) {
+            Some(Frame::Events(events)) => tracker.apply(0, &events),
+            Some(Frame::Resync) => tracker.resync(0, HashSet::new(), timestamp),
+            None => {}
+        }
+    }
+    Recording { devices: vec![name], events: std::iter::from_fn(|| tracker.pop_key()).collect() }
+}
+
+fn parse_seconds(text: &str) -> Option<Duration> {
+    let (sec, usec) = text.split_once('.').unwrap_or((text, "0"));
+    let usec: u32 = format!("{:0<6}", usec).get(..6)?.parse().ok()?;
+    Some(Duration::new(sec.parse().ok()?, usec * 1000))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::linux::Tracked;
+    use crate::{Key, KeyEvent, KeyEventKind};
+
+    fn keys(events: &[KeyEvent]) -> Vec<(Key, KeyEventKind)> {
+        events.iter().map(|e| (e.key, e.kind)).collect()
+    }
+
+    fn at(ms: u64) -> Timestamp {
+        Timestamp { monotonic: Duration::from_millis(ms), wall: None, latency: None }
+    }
+
+    #[test]
+    fn evemu_capture_decodes_modifiers_repeats_and_skips_unknown_codes() {
+        let recording = read_evemu(&include_bytes!("../../tests/fixtures/evdev/shift_a.evemu")[..]).unwrap();
+        assert_eq!(recording.devices, ["Test Keyboard"]);
+        assert_eq!(
+            keys(&recording.events),
+            [
+                (Key::ShiftLeft, KeyEventKind::Press),
+                (Key::KeyA, KeyEventKind::Press),
+                (Key::KeyA, KeyEventKind::Repeat),
+                (Key::KeyA, KeyEventKind::Release),
+                (Key::ShiftLeft, KeyEventKind::Release),
+            ]
+        );
+        assert!(recording.events[1..4].iter().all(|e| e.shift && !e.ctrl));
+        assert!(!recording.events[4].shift);
+        assert_eq!(recording.events[1].timestamp.monotonic, Duration::from_millis(10_100));
+    }
+
+    #[test]
+    fn evemu_capture_with_syn_dropped_discards_the_dropped_frames() {
+        let recording = read_evemu(&include_bytes!("../../tests/fixtures/evdev/syn_dropped.evemu")[..]).unwrap();
+        assert_eq!(
+            keys(&recording.events),
+            [(Key::KeyA, KeyEventKind::Press), (Key::KeyA, KeyEventKind::Release), (Key::KeyD, KeyEventKind::Press)]
+        );
+        assert_eq!(recording.events[1].timestamp.monotonic, Duration::from_millis(1_200));
+    }
+
+    #[test]
+    fn raw_input_event_dump_matches_evemu() {
+        let mut dump = Vec::new();
+        for line in include_str!("../../tests/fixtures/evdev/shift_a.evemu").lines() {
+            let Some(event) = line.split('#').next().unwrap().trim().strip_prefix("E:") else { continue };
+            let fields: Vec<&str> = event.split_whitespace().collect();
+            let (sec, usec) = fields[0].split_once('.').unwrap();
+            let mut record: libc::input_event = unsafe { std::mem::zeroed() };
+            record.time.tv_sec = sec.parse().unwrap();
+            record.time.tv_usec = usec.parse().unwrap();
+            record.type_ = u16::from_str_radix(fields[1], 16).unwrap();
+            record.code = u16::from_str_radix(fields[2], 16).unwrap();
+            record.value = fields[3].parse().unwrap();
+            let bytes: [u8; size_of::<libc::input_event>()] = unsafe { std::mem::transmute(record) };
+            dump.extend_from_slice(&bytes);
+        }
+        let dumped = read_input_events(&dump[..]).unwrap();
+        let captured = read_evemu(&include_bytes!("../../tests/fixtures/evdev/shift_a.evemu")[..]).unwrap();
+        assert_eq!(keys(&dumped.events), keys(&captured.events));
+        assert_eq!(dumped.events[0].timestamp.wall, UNIX_EPOCH.checked_add(Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn frame_decoder_emits_whole_frames_only() {
+        let mut frames = FrameDecoder::default();
+        assert!(frames.feed(InputEvent::new(1, 30, 1), at(0)).is_none());
+        assert!(frames.feed(InputEvent::new(1, 48, 1), at(0)).is_none());
+        match frames.feed(InputEvent::new(0, 0, 0), at(0)) {
+            Some(Frame::Events(events)) => assert_eq!(events.iter().map(|e| (e.0, e.1)).collect::<Vec<_>>(), [(30, 1), (48, 1)]),
+            _ => panic!("expected a frame"),
+        }
+        assert!(frames.feed(InputEvent::new(0, 0, 0), at(1)).is_none(), "empty frames are skipped");
+
+        assert!(frames.feed(InputEvent::new(1, 30, 0), at(2)).is_none());
+        assert!(frames.feed(InputEvent::new(0, 3, 0), at(2)).is_none());
+        assert!(frames.feed(InputEvent::new(1, 46, 1), at(2)).is_none());
+        assert!(matches!(frames.feed(InputEvent::new(0, 0, 0), at(3)), Some(Frame::Resync)));
+    }
+
+    /// A resync reads the kernel state when SYN_DROPPED is decoded, and that state already
+    /// includes frames decoded after it; applying those frames must not repeat their events.
+    #[test]
+    fn tracker_does_not_repeat_events_the_resync_state_contains() {
+        let (a, b) = (30, 48);
+        let mut tracker = KeyTracker::new(1);
+        tracker.apply(0, &[(a, 1, at(0))]);
+        tracker.resync(0, HashSet::from([a, b]), at(10));
+        tracker.apply(0, &[(b, 1, at(11))]);
+        tracker.apply(0, &[(a, 0, at(12)), (a, 0, at(12))]);
+        tracker.apply(0, &[(b, 0, at(13))]);
+        assert_eq!(
+            keys(&std::iter::from_fn(|| tracker.pop_key()).collect::<Vec<_>>()),
+            [
+                (Key::KeyA, KeyEventKind::Press),
+                (Key::KeyB, KeyEventKind::Press),
+                (Key::KeyA, KeyEventKind::Release),
+                (Key::KeyB, KeyEventKind::Release),
+            ]
+        );
+    }
+
+    #[test]
+    fn tracker_keeps_unmapped_codes_in_order_for_passthrough() {
+        let volume_up = 0x73;
+        let mut tracker = KeyTracker::new(1);
+        tracker.apply(0, &[(42, 1, at(0)), (volume_up, 1, at(1)), (volume_up, 0, at(2)), (42, 0, at(3))]);
+        let tracked: Vec<Tracked> = tracker.pending.iter().copied().collect();
+        assert!(matches!(tracked[0], Tracked::Key(KeyEvent { key: Key::ShiftLeft, .. })));
+        assert_eq!(tracked[1], Tracked::Unmapped { code: volume_up, kind: KeyEventKind::Press, device: 0 });
+        assert_eq!(tracked[2], Tracked::Unmapped { code: volume_up, kind: KeyEventKind::Release, device: 0 });
+        assert!(matches!(tracked[3], Tracked::Key(KeyEvent { key: Key::ShiftLeft, kind: KeyEventKind::Release, .. })));
+        assert_eq!(keys(&std::iter::from_fn(|| tracker.pop_key()).collect::<Vec<_>>()).len(), 2);
+    }
+
+    #[test]
+    fn tracker_resync_releases_keys_let_go_during_the_drop() {
+        let mut tracker = KeyTracker::new(2);
+        tracker.seed(1, HashSet::from([42]));
+        tracker.apply(0, &[(30, 1, at(0))]);
+        tracker.resync(1, HashSet::new(), at(5));
+        let events: Vec<KeyEvent> = std::iter::from_fn(|| tracker.pop_key()).collect();
+        assert_eq!(keys(&events), [(Key::KeyA, KeyEventKind::Press), (Key::ShiftLeft, KeyEventKind::Release)]);
+        assert_eq!((events[0].device, events[1].device), (0, 1));
+        assert!(events[0].shift, "Shift held on the other keyboard applies");
+    }
+}
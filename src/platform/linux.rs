@@ -1,184 +1,628 @@
-use crate::{Key, KeyEvent};
-use evdev::{Device, InputEvent, KeyCode};
-use std::collections::HashSet;
+use super::poll;
+use super::tty::RawMode;
+use super::Source;
+use crate::time::since_epoch;
+use crate::{CancelHandle, Key, KeyEvent, KeyEventKind, Modifiers, Timestamp};
+use evdev::raw_stream::RawDevice;
+use evdev::{EventType, InputEvent, KeyCode, SynchronizationCode};
+use std::collections::{HashSet, VecDeque};
 use std::fs::File;
 use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
 use std::os::unix::fs::FileTypeExt;
-use std::path::Path;
-
-pub fn wait_key_with_mods() -> io::Result<KeyEvent> {
-    let device = match find_keyboard() {
-        Some(Ok(dev)) => dev,
-        _ => return super::crossterm_fallback::wait_key_with_mods(),
-    };
-
-    let mut device = device;
-    let mut shift = false;
-    let mut ctrl = false;
-    let mut alt = false;
-    let mut pressed = HashSet::new();
-
-    loop {
-        let events = device.fetch_events()?;
-        for ev in events {
-            if ev.event_type() != evdev::EventType::KEY {
-                continue;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+#[cfg(feature = "async")]
+use std::task::{Context, Poll};
+
+/// Reads every keyboard found under /dev/input.
+///
+/// The devices are non-blocking and registered with one epoll instance, whose
+/// descriptor becomes readable whenever any of them has events.
+pub struct EvdevSource {
+    keyboards: Vec<Keyboard>,
+    epoll: OwnedFd,
+    tracker: KeyTracker,
+    cancel: Option<CancelHandle>,
+    _raw: Option<RawMode>,
+}
+
+/// Whether an `EvdevSource` puts the controlling terminal into raw mode while it reads.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Terminal {
+    /// Raw mode, so keys read from the devices are not echoed into the terminal as well.
+    Raw,
+    /// The terminal is left alone, for daemons without one.
+    Untouched,
+}
+
+struct Keyboard {
+    device: RawDevice,
+    name: String,
+    monotonic_clock: bool,
+    frames: FrameDecoder,
+}
+
+impl EvdevSource {
+    pub fn open(cancel: Option<CancelHandle>) -> io::Result<Self> {
+        Self::with_devices(find_keyboards()?, cancel, Terminal::Raw)
+    }
+
+    /// Reads the given event devices instead of every keyboard found.
+    pub fn open_paths(paths: &[&Path], cancel: Option<CancelHandle>) -> io::Result<Self> {
+        Self::with_devices(open_devices(paths)?, cancel, Terminal::Raw)
+    }
+
+    /// Reads the given event devices, or every keyboard but those in `skip` when `paths` is
+    /// empty, with the terminal set as `terminal` says.
+    pub(crate) fn open_with(paths: &[&Path], skip: &[&Path], terminal: Terminal) -> io::Result<Self> {
+        let devices = if paths.is_empty() { find_keyboards_except(skip)? } else { open_devices(paths)? };
+        Self::with_devices(devices, None, terminal)
+    }
+
+    fn with_devices(devices: Vec<RawDevice>, cancel: Option<CancelHandle>, terminal: Terminal) -> io::Result<Self> {
+        if devices.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "no keyboard device"));
+        }
+
+        let epoll = unsafe { libc::epoll_create1(libc::EPOLL_CLOEXEC) };
+        if epoll < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let epoll = unsafe { OwnedFd::from_raw_fd(epoll) };
+        let mut keyboards = Vec::with_capacity(devices.len());
+        for (i, device) in devices.into_iter().enumerate() {
+            set_nonblocking(&device)?;
+            let mut ev = libc::epoll_event { events: libc::EPOLLIN as u32, u64: i as u64 };
+            if unsafe { libc::epoll_ctl(epoll.as_raw_fd(), libc::EPOLL_CTL_ADD, device.as_raw_fd(), &mut ev) } != 0 {
+                return Err(io::Error::last_os_error());
             }
-            let code = ev.code();
-            let value = ev.value();
-            let key = map_evdev_key(code);
-
-            match value {
-                1 => {
-                    match key {
-                        Key::ShiftLeft | Key::ShiftRight => shift = true,
-                        Key::ControlLeft | Key::ControlRight => ctrl = true,
-                        Key::AltLeft | Key::AltRight => alt = true,
-                        _ => {}
-                    }
-                    pressed.insert(code);
-                    return Ok(KeyEvent { key, shift, ctrl, alt });
+            keyboards.push(Keyboard {
+                name: device.name().unwrap_or("evdev keyboard").to_string(),
+                monotonic_clock: set_monotonic_clock(&device),
+                device,
+                frames: FrameDecoder::default(),
+            });
+        }
+
+        let mut source = EvdevSource {
+            tracker: KeyTracker::new(keyboards.len()),
+            keyboards,
+            epoll,
+            cancel,
+            _raw: if terminal == Terminal::Raw { RawMode::enable().ok() } else { None },
+        };
+        for i in 0..source.keyboards.len() {
+            let held = kernel_key_state(&source.keyboards[i].device)?;
+            source.tracker.seed(i, held);
+        }
+        Ok(source)
+    }
+
+    /// Drains whatever the devices have buffered without blocking.
+    ///
+    /// Events are decoded one SYN_REPORT frame at a time; frames from different keyboards are
+    /// applied in timestamp order. After SYN_DROPPED the key state is re-read from the kernel
+    /// straight away and replaces the device's earlier frames; frames decoded after it that
+    /// the state already reflects change nothing.
+    fn fetch(&mut self) -> io::Result<()> {
+        let mut updates: Vec<(Timestamp, usize, Update)> = Vec::new();
+        for (i, keyboard) in self.keyboards.iter_mut().enumerate() {
+            loop {
+                // Collected first: a resync reads the device while the batch is decoded.
+                let fetched: Vec<InputEvent> = match keyboard.device.fetch_events() {
+                    Ok(fetched) => fetched.collect(),
+                    Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                    Err(err) => return Err(err),
+                };
+                if fetched.is_empty() {
+                    break;
                 }
-                0 => {
-                    match key {
-                        Key::ShiftLeft | Key::ShiftRight => shift = false,
-                        Key::ControlLeft | Key::ControlRight => ctrl = false,
-                        Key::AltLeft | Key::AltRight => alt = false,
-                        _ => {}
+                for ev in fetched {
+                    let time = event_time(&ev, keyboard.monotonic_clock);
+                    match keyboard.frames.feed(ev, time) {
+                        Some(Frame::Events(events)) => updates.push((time, i, Update::Events(events))),
+                        Some(Frame::Resync) => {
+                            updates.retain(|(_, device, _)| *device != i);
+                            let held = kernel_key_state(&keyboard.device)?;
+                            updates.push((time, i, Update::Resync(held)));
+                        }
+                        None => {}
                     }
-                    pressed.remove(&code);
                 }
-                _ => {}
+            }
+        }
+
+        updates.sort_by_key(|(time, _, _)| time.monotonic);
+        for (time, i, update) in updates {
+            match update {
+                Update::Events(events) => self.tracker.apply(i, &events),
+                Update::Resync(held) => self.tracker.resync(i, held, time),
+            }
+        }
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Option<KeyEvent> {
+        let mut event = self.tracker.pop_key()?;
+        event.timestamp = event.timestamp.delivered();
+        Some(event)
+    }
+
+    /// Like `next_event`, but also returns the key codes `Key` has no variant for.
+    pub(crate) fn next_tracked(&mut self, timeout: Option<Duration>) -> io::Result<Option<Tracked>> {
+        let deadline = timeout.map(|t| Instant::now() + t);
+        loop {
+            if let Some(mut tracked) = self.tracker.pending.pop_front() {
+                if let Tracked::Key(event) = &mut tracked {
+                    event.timestamp = event.timestamp.delivered();
+                }
+                return Ok(Some(tracked));
+            }
+            if !self.wait(deadline)? {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Waits for the devices to become readable and fetches what they have; `false` when
+    /// `deadline` passed first.
+    fn wait(&mut self, deadline: Option<Instant>) -> io::Result<bool> {
+        let mut fds = vec![self.epoll.as_raw_fd()];
+        if let Some(cancel) = &self.cancel {
+            cancel.check()?;
+            fds.push(cancel.waker().as_raw_fd());
+        }
+        let ready = poll::wait_readable(&fds, deadline)?;
+        if let Some(cancel) = &self.cancel {
+            cancel.check()?;
+        }
+        if !ready[0] {
+            return Ok(false);
+        }
+        self.fetch()?;
+        Ok(true)
+    }
+}
+
+/// An event `KeyTracker` derived from a frame: a key, or a key code with no `Key` variant.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Tracked {
+    Key(KeyEvent),
+    Unmapped { code: u16, kind: KeyEventKind, device: u16 },
+}
+
+/// A decoded frame, or a resync with the key state read when it was decoded.
+enum Update {
+    Events(Vec<(u16, i32, Timestamp)>),
+    Resync(HashSet<u16>),
+}
+
+/// A completed unit of input from one device.
+pub(crate) enum Frame {
+    /// The key events between two SYN_REPORTs, with their timestamps.
+    Events(Vec<(u16, i32, Timestamp)>),
+    /// Events were dropped; the device's key state must be re-read.
+    Resync,
+}
+
+/// Splits a device's raw stream at SYN_REPORT and discards the partial frames around SYN_DROPPED.
+#[derive(Default)]
+pub(crate) struct FrameDecoder {
+    frame: Vec<(u16, i32, Timestamp)>,
+    dropping: bool,
+}
+
+impl FrameDecoder {
+    pub(crate) fn feed(&mut self, ev: InputEvent, time: Timestamp) -> Option<Frame> {
+        match ev.event_type() {
+            EventType::SYNCHRONIZATION if ev.code() == SynchronizationCode::SYN_DROPPED.0 => {
+                self.frame.clear();
+                self.dropping = true;
+                None
+            }
+            EventType::SYNCHRONIZATION if ev.code() == SynchronizationCode::SYN_REPORT.0 => {
+                if std::mem::take(&mut self.dropping) {
+                    self.frame.clear();
+                    Some(Frame::Resync)
+                } else if self.frame.is_empty() {
+                    None
+                } else {
+                    Some(Frame::Events(std::mem::take(&mut self.frame)))
+                }
+            }
+            EventType::KEY if !self.dropping => {
+                self.frame.push((ev.code(), ev.value(), time));
+                None
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Held keys per device and the key events derived from them.
+pub(crate) struct KeyTracker {
+    pressed: Vec<HashSet<u16>>,
+    pub(crate) pending: VecDeque<Tracked>,
+}
+
+impl KeyTracker {
+    pub(crate) fn new(devices: usize) -> Self {
+        KeyTracker { pressed: vec![HashSet::new(); devices], pending: VecDeque::new() }
+    }
+
+    /// Records keys already held when reading starts, without emitting events.
+    pub(crate) fn seed(&mut self, device: usize, held: HashSet<u16>) {
+        self.pressed[device] = held;
+    }
+
+    /// Applies a frame. Presses of keys already held and releases of keys not held are
+    /// skipped; after a resync they describe changes the re-read state already contains.
+    pub(crate) fn apply(&mut self, device: usize, events: &[(u16, i32, Timestamp)]) {
+        for &(code, value, time) in events {
+            let kind = match value {
+                1 if self.pressed[device].insert(code) => KeyEventKind::Press,
+                2 => KeyEventKind::Repeat,
+                0 if self.pressed[device].remove(&code) => KeyEventKind::Release,
+                _ => continue,
+            };
+            self.push(device, code, kind, time);
+        }
+    }
+
+    /// Reconciles with the kernel's key state after SYN_DROPPED: keys that went up during the
+    /// drop get synthetic releases, keys that went down and are still held get synthetic presses.
+    pub(crate) fn resync(&mut self, device: usize, held: HashSet<u16>, time: Timestamp) {
+        let mut released: Vec<u16> = self.pressed[device].difference(&held).copied().collect();
+        let mut pressed: Vec<u16> = held.difference(&self.pressed[device]).copied().collect();
+        released.sort_unstable();
+        pressed.sort_unstable();
+        for code in released {
+            self.pressed[device].remove(&code);
+            self.push(device, code, KeyEventKind::Release, time);
+        }
+        for code in pressed {
+            self.pressed[device].insert(code);
+            self.push(device, code, KeyEventKind::Press, time);
+        }
+    }
+
+    /// The next key event, skipping unmapped codes.
+    pub(crate) fn pop_key(&mut self) -> Option<KeyEvent> {
+        while let Some(tracked) = self.pending.pop_front() {
+            if let Tracked::Key(event) = tracked {
+                return Some(event);
+            }
+        }
+        None
+    }
+
+    fn push(&mut self, device: usize, code: u16, kind: KeyEventKind, timestamp: Timestamp) {
+        let key = match map_evdev_key(KeyCode(code)) {
+            Some(key) => key,
+            None => {
+                self.pending.push_back(Tracked::Unmapped { code, kind, device: device as u16 });
+                return;
+            }
+        };
+        let (mods, right) = Modifiers::held(self.pressed.iter().flatten().filter_map(|&c| map_evdev_key(KeyCode(c))));
+        self.pending.push_back(Tracked::Key(KeyEvent {
+            key,
+            kind,
+            shift: mods.contains(Modifiers::SHIFT),
+            ctrl: mods.contains(Modifiers::CTRL),
+            alt: mods.contains(Modifiers::ALT),
+            meta: mods.contains(Modifiers::SUPER),
+            right,
+            timestamp,
+            device: device as u16,
+        }));
+    }
+}
+
+impl Source for EvdevSource {
+    fn grab(&mut self) -> io::Result<()> {
+        for keyboard in &mut self.keyboards {
+            keyboard.device.grab()?;
+        }
+        Ok(())
+    }
+
+    fn devices(&self) -> Vec<String> {
+        self.keyboards.iter().map(|k| k.name.clone()).collect()
+    }
+
+    fn next_event(&mut self, timeout: Option<Duration>) -> io::Result<Option<KeyEvent>> {
+        let deadline = timeout.map(|t| Instant::now() + t);
+        loop {
+            if let Some(event) = self.pop() {
+                return Ok(Some(event));
+            }
+            if !self.wait(deadline)? {
+                return Ok(None);
+            }
+        }
+    }
+
+    fn drain_ready(&mut self) -> io::Result<Vec<KeyEvent>> {
+        self.fetch()?;
+        Ok(std::iter::from_fn(|| self.pop()).collect())
+    }
+
+    fn raw_fd(&self) -> Option<RawFd> {
+        Some(self.epoll.as_raw_fd())
+    }
+
+    fn reports_releases(&self) -> bool {
+        true
+    }
+
+    fn pressed_keys(&self) -> io::Result<Vec<Key>> {
+        key_state(self.keyboards.iter().map(|k| &k.device))
+    }
+}
+
+impl AsRawFd for EvdevSource {
+    fn as_raw_fd(&self) -> RawFd {
+        self.epoll.as_raw_fd()
+    }
+}
+
+#[cfg(feature = "async")]
+pub struct AsyncEvdevSource {
+    inner: tokio::io::unix::AsyncFd<EvdevSource>,
+}
+
+#[cfg(feature = "async")]
+impl AsyncEvdevSource {
+    pub fn open() -> io::Result<Self> {
+        let source = EvdevSource::open(None)?;
+        Ok(AsyncEvdevSource { inner: tokio::io::unix::AsyncFd::new(source)? })
+    }
+}
+
+#[cfg(feature = "async")]
+impl super::AsyncSource for AsyncEvdevSource {
+    fn poll_next_event(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<KeyEvent>> {
+        loop {
+            if let Some(event) = self.inner.get_mut().pop() {
+                return Poll::Ready(Ok(event));
+            }
+            let mut guard = match self.inner.poll_read_ready_mut(cx) {
+                Poll::Ready(Ok(guard)) => guard,
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            };
+            if let Err(err) = guard.get_inner_mut().fetch() {
+                return Poll::Ready(Err(err));
+            }
+            guard.clear_ready();
+        }
+    }
+}
+
+const EVIOCSCLOCKID: libc::c_ulong = 0x4004_45a0;
+
+/// Switches the device's event timestamps to CLOCK_MONOTONIC; returns false if the kernel refused.
+fn set_monotonic_clock(device: &RawDevice) -> bool {
+    let clock: libc::c_int = libc::CLOCK_MONOTONIC;
+    unsafe { libc::ioctl(device.as_raw_fd(), EVIOCSCLOCKID as _, &clock) == 0 }
+}
+
+fn set_nonblocking(device: &RawDevice) -> io::Result<()> {
+    let fd = device.as_raw_fd();
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+    if flags < 0 || unsafe { libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+pub(crate) fn event_time(ev: &InputEvent, monotonic: bool) -> Timestamp {
+    if monotonic {
+        Timestamp::from_monotonic(since_epoch(ev.timestamp()))
+    } else {
+        Timestamp::from_wall(ev.timestamp())
+    }
+}
+
+fn kernel_key_state(device: &RawDevice) -> io::Result<HashSet<u16>> {
+    Ok(device.get_key_state()?.iter().map(|code| code.0).collect())
+}
+
+/// Keys currently held on any keyboard, read from the kernel's key state.
+pub fn pressed_keys() -> io::Result<Vec<Key>> {
+    let devices = find_keyboards()?;
+    if devices.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "no keyboard device"));
+    }
+    key_state(devices.iter())
+}
+
+fn key_state<'a>(devices: impl Iterator<Item = &'a RawDevice>) -> io::Result<Vec<Key>> {
+    let mut keys = Vec::new();
+    for device in devices {
+        for code in device.get_key_state()?.iter() {
+            if let Some(key) = map_evdev_key(code) {
+                if !keys.contains(&key) {
+                    keys.push(key);
+                }
             }
         }
     }
+    Ok(keys)
 }
 
-fn find_keyboard() -> Option<io::Result<Device>> {
-    for entry in std::fs::read_dir("/dev/input/by-path").ok()? {
-        let entry = entry.ok()?;
-        let path = entry.path();
-        if path.file_name().and_then(|s| s.to_str()).map(|s| s.contains("-kbd")).unwrap_or(false) {
-            return Some(Device::open(&path));
+fn open_devices(paths: &[&Path]) -> io::Result<Vec<RawDevice>> {
+    paths
+        .iter()
+        .map(|path| RawDevice::open(path).map_err(|err| io::Error::new(err.kind(), format!("{}: {}", path.display(), err))))
+        .collect()
+}
+
+fn find_keyboards() -> io::Result<Vec<RawDevice>> {
+    find_keyboards_except(&[])
+}
+
+/// Every keyboard except the device nodes in `skip`, however their paths are spelled.
+fn find_keyboards_except(skip: &[&Path]) -> io::Result<Vec<RawDevice>> {
+    let skip: Vec<PathBuf> = skip.iter().filter_map(|path| path.canonicalize().ok()).collect();
+    let skipped = |path: &Path| path.canonicalize().is_ok_and(|path| skip.contains(&path));
+    let mut devices = Vec::new();
+    if let Ok(entries) = std::fs::read_dir("/dev/input/by-path") {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if skipped(&path) {
+                continue;
+            }
+            if path.file_name().and_then(|s| s.to_str()).map(|s| s.ends_with("-event-kbd")).unwrap_or(false) {
+                if let Ok(dev) = RawDevice::open(&path) {
+                    devices.push(dev);
+                }
+            }
         }
     }
+    if !devices.is_empty() {
+        return Ok(devices);
+    }
     for i in 0..32 {
         let path = format!("/dev/input/event{}", i);
-        if Path::new(&path).exists() {
-            if let Ok(mut dev) = Device::open(&path) {
+        if Path::new(&path).exists() && !skipped(Path::new(&path)) {
+            if let Ok(dev) = RawDevice::open(&path) {
                 if dev.supported_keys().map(|keys| keys.contains(KeyCode::KEY_A)).unwrap_or(false) {
-                    return Some(Ok(dev));
+                    devices.push(dev);
                 }
             }
         }
     }
-    None
-}
-
-fn map_evdev_key(key: KeyCode) -> Key {
-    use evdev::KeyCode::*;
-    match key {
-        KEY_ESC => Key::Escape,
-        KEY_F1 => Key::F1,
-        KEY_F2 => Key::F2,
-        KEY_F3 => Key::F3,
-        KEY_F4 => Key::F4,
-        KEY_F5 => Key::F5,
-        KEY_F6 => Key::F6,
-        KEY_F7 => Key::F7,
-        KEY_F8 => Key::F8,
-        KEY_F9 => Key::F9,
-        KEY_F10 => Key::F10,
-        KEY_F11 => Key::F11,
-        KEY_F12 => Key::F12,
-        KEY_GRAVE => Key::Grave,
-        KEY_1 => Key::Digit1,
-        KEY_2 => Key::Digit2,
-        KEY_3 => Key::Digit3,
-        KEY_4 => Key::Digit4,
-        KEY_5 => Key::Digit5,
-        KEY_6 => Key::Digit6,
-        KEY_7 => Key::Digit7,
-        KEY_8 => Key::Digit8,
-        KEY_9 => Key::Digit9,
-        KEY_0 => Key::Digit0,
-        KEY_MINUS => Key::Minus,
-        KEY_EQUAL => Key::Equal,
-        KEY_BACKSPACE => Key::Backspace,
-        KEY_TAB => Key::Tab,
-        KEY_Q => Key::KeyQ,
-        KEY_W => Key::KeyW,
-        KEY_E => Key::KeyE,
-        KEY_R => Key::KeyR,
-        KEY_T => Key::KeyT,
-        KEY_Y => Key::KeyY,
-        KEY_U => Key::KeyU,
-        KEY_I => Key::KeyI,
-        KEY_O => Key::KeyO,
-        KEY_P => Key::KeyP,
-        KEY_LEFTBRACE => Key::LeftBracket,
-        KEY_RIGHTBRACE => Key::RightBracket,
-        KEY_BACKSLASH => Key::Backslash,
-        KEY_CAPSLOCK => Key::CapsLock,
-        KEY_A => Key::KeyA,
-        KEY_S => Key::KeyS,
-        KEY_D => Key::KeyD,
-        KEY_F => Key::KeyF,
-        KEY_G => Key::KeyG,
-        KEY_H => Key::KeyH,
-        KEY_J => Key::KeyJ,
-        KEY_K => Key::KeyK,
-        KEY_L => Key::KeyL,
-        KEY_SEMICOLON => Key::Semicolon,
-        KEY_APOSTROPHE => Key::Quote,
-        KEY_ENTER => Key::Enter,
-        KEY_LEFTSHIFT => Key::ShiftLeft,
-        KEY_Z => Key::KeyZ,
-        KEY_X => Key::KeyX,
-        KEY_C => Key::KeyC,
-        KEY_V => Key::KeyV,
-        KEY_B => Key::KeyB,
-        KEY_N => Key::KeyN,
-        KEY_M => Key::KeyM,
-        KEY_COMMA => Key::Comma,
-        KEY_DOT => Key::Period,
-        KEY_SLASH => Key::Slash,
-        KEY_RIGHTSHIFT => Key::ShiftRight,
-        KEY_LEFTCTRL => Key::ControlLeft,
-        KEY_LEFTALT => Key::AltLeft,
-        KEY_SPACE => Key::Space,
-        KEY_RIGHTALT => Key::AltRight,
-        KEY_RIGHTCTRL => Key::ControlRight,
-        KEY_LEFT => Key::ArrowLeft,
-        KEY_RIGHT => Key::ArrowRight,
-        KEY_UP => Key::ArrowUp,
-        KEY_DOWN => Key::ArrowDown,
-        KEY_INSERT => Key::Insert,
-        KEY_DELETE => Key::Delete,
-        KEY_HOME => Key::Home,
-        KEY_END => Key::End,
-        KEY_PAGEUP => Key::PageUp,
-        KEY_PAGEDOWN => Key::PageDown,
-        KEY_NUMLOCK => Key::NumLock,
-        KEY_KP0 => Key::Numpad0,
-        KEY_KP1 => Key::Numpad1,
-        KEY_KP2 => Key::Numpad2,
-        KEY_KP3 => Key::Numpad3,
-        KEY_KP4 => Key::Numpad4,
-        KEY_KP5 => Key::Numpad5,
-        KEY_KP6 => Key::Numpad6,
-        KEY_KP7 => Key::Numpad7,
-        KEY_KP8 => Key::Numpad8,
-        KEY_KP9 => Key::Numpad9,
-        KEY_KPSLASH => Key::NumpadDivide,
-        KEY_KPASTERISK => Key::NumpadMultiply,
-        KEY_KPMINUS => Key::NumpadSubtract,
-        KEY_KPPLUS => Key::NumpadAdd,
-        KEY_KPENTER => Key::NumpadEnter,
-        KEY_KPDOT => Key::NumpadDecimal,
-        KEY_PAUSE => Key::Pause,
-        KEY_SCROLLLOCK => Key::ScrollLock,
-        KEY_PRINT => Key::PrintScreen,
-        _ => Key::Space,
-    }
-}
\ No newline at end of file
+    Ok(devices)
+}
+
+/// evdev codes for every `Key`; read in one direction by `map_evdev_key`, in the other by `evdev_code`.
+const KEYMAP: [(KeyCode, Key); 103] = [
+    (KeyCode::KEY_ESC, Key::Escape),
+    (KeyCode::KEY_F1, Key::F1),
+    (KeyCode::KEY_F2, Key::F2),
+    (KeyCode::KEY_F3, Key::F3),
+    (KeyCode::KEY_F4, Key::F4),
+    (KeyCode::KEY_F5, Key::F5),
+    (KeyCode::KEY_F6, Key::F6),
+    (KeyCode::KEY_F7, Key::F7),
+    (KeyCode::KEY_F8, Key::F8),
+    (KeyCode::KEY_F9, Key::F9),
+    (KeyCode::KEY_F10, Key::F10),
+    (KeyCode::KEY_F11, Key::F11),
+    (KeyCode::KEY_F12, Key::F12),
+    (KeyCode::KEY_GRAVE, Key::Grave),
+    (KeyCode::KEY_1, Key::Digit1),
+    (KeyCode::KEY_2, Key::Digit2),
+    (KeyCode::KEY_3, Key::Digit3),
+    (KeyCode::KEY_4, Key::Digit4),
+    (KeyCode::KEY_5, Key::Digit5),
+    (KeyCode::KEY_6, Key::Digit6),
+    (KeyCode::KEY_7, Key::Digit7),
+    (KeyCode::KEY_8, Key::Digit8),
+    (KeyCode::KEY_9, Key::Digit9),
+    (KeyCode::KEY_0, Key::Digit0),
+    (KeyCode::KEY_MINUS, Key::Minus),
+    (KeyCode::KEY_EQUAL, Key::Equal),
+    (KeyCode::KEY_BACKSPACE, Key::Backspace),
+    (KeyCode::KEY_TAB, Key::Tab),
+    (KeyCode::KEY_Q, Key::KeyQ),
+    (KeyCode::KEY_W, Key::KeyW),
+    (KeyCode::KEY_E, Key::KeyE),
+    (KeyCode::KEY_R, Key::KeyR),
+    (KeyCode::KEY_T, Key::KeyT),
+    (KeyCode::KEY_Y, Key::KeyY),
+    (KeyCode::KEY_U, Key::KeyU),
+    (KeyCode::KEY_I, Key::KeyI),
+    (KeyCode::KEY_O, Key::KeyO),
+    (KeyCode::KEY_P, Key::KeyP),
+    (KeyCode::KEY_LEFTBRACE, Key::LeftBracket),
+    (KeyCode::KEY_RIGHTBRACE, Key::RightBracket),
+    (KeyCode::KEY_BACKSLASH, Key::Backslash),
+    (KeyCode::KEY_CAPSLOCK, Key::CapsLock),
+    (KeyCode::KEY_A, Key::KeyA),
+    (KeyCode::KEY_S, Key::KeyS),
+    (KeyCode::KEY_D, Key::KeyD),
+    (KeyCode::KEY_F, Key::KeyF),
+    (KeyCode::KEY_G, Key::KeyG),
+    (KeyCode::KEY_H, Key::KeyH),
+    (KeyCode::KEY_J, Key::KeyJ),
+    (KeyCode::KEY_K, Key::KeyK),
+    (KeyCode::KEY_L, Key::KeyL),
+    (KeyCode::KEY_SEMICOLON, Key::Semicolon),
+    (KeyCode::KEY_APOSTROPHE, Key::Quote),
+    (KeyCode::KEY_ENTER, Key::Enter),
+    (KeyCode::KEY_LEFTSHIFT, Key::ShiftLeft),
+    (KeyCode::KEY_Z, Key::KeyZ),
+    (KeyCode::KEY_X, Key::KeyX),
+    (KeyCode::KEY_C, Key::KeyC),
+    (KeyCode::KEY_V, Key::KeyV),
+    (KeyCode::KEY_B, Key::KeyB),
+    (KeyCode::KEY_N, Key::KeyN),
+    (KeyCode::KEY_M, Key::KeyM),
+    (KeyCode::KEY_COMMA, Key::Comma),
+    (KeyCode::KEY_DOT, Key::Period),
+    (KeyCode::KEY_SLASH, Key::Slash),
+    (KeyCode::KEY_RIGHTSHIFT, Key::ShiftRight),
+    (KeyCode::KEY_LEFTCTRL, Key::ControlLeft),
+    (KeyCode::KEY_LEFTALT, Key::AltLeft),
+    (KeyCode::KEY_SPACE, Key::Space),
+    (KeyCode::KEY_RIGHTALT, Key::AltRight),
+    (KeyCode::KEY_RIGHTCTRL, Key::ControlRight),
+    (KeyCode::KEY_LEFTMETA, Key::MetaLeft),
+    (KeyCode::KEY_RIGHTMETA, Key::MetaRight),
+    (KeyCode::KEY_LEFT, Key::ArrowLeft),
+    (KeyCode::KEY_RIGHT, Key::ArrowRight),
+    (KeyCode::KEY_UP, Key::ArrowUp),
+    (KeyCode::KEY_DOWN, Key::ArrowDown),
+    (KeyCode::KEY_INSERT, Key::Insert),
+    (KeyCode::KEY_DELETE, Key::Delete),
+    (KeyCode::KEY_HOME, Key::Home),
+    (KeyCode::KEY_END, Key::End),
+    (KeyCode::KEY_PAGEUP, Key::PageUp),
+    (KeyCode::KEY_PAGEDOWN, Key::PageDown),
+    (KeyCode::KEY_NUMLOCK, Key::NumLock),
+    (KeyCode::KEY_KP0, Key::Numpad0),
+    (KeyCode::KEY_KP1, Key::Numpad1),
+    (KeyCode::KEY_KP2, Key::Numpad2),
+    (KeyCode::KEY_KP3, Key::Numpad3),
+    (KeyCode::KEY_KP4, Key::Numpad4),
+    (KeyCode::KEY_KP5, Key::Numpad5),
+    (KeyCode::KEY_KP6, Key::Numpad6),
+    (KeyCode::KEY_KP7, Key::Numpad7),
+    (KeyCode::KEY_KP8, Key::Numpad8),
+    (KeyCode::KEY_KP9, Key::Numpad9),
+    (KeyCode::KEY_KPSLASH, Key::NumpadDivide),
+    (KeyCode::KEY_KPASTERISK, Key::NumpadMultiply),
+    (KeyCode::KEY_KPMINUS, Key::NumpadSubtract),
+    (KeyCode::KEY_KPPLUS, Key::NumpadAdd),
+    (KeyCode::KEY_KPENTER, Key::NumpadEnter),
+    (KeyCode::KEY_KPDOT, Key::NumpadDecimal),
+    (KeyCode::KEY_PAUSE, Key::Pause),
+    (KeyCode::KEY_SCROLLLOCK, Key::ScrollLock),
+    (KeyCode::KEY_PRINT, Key::PrintScreen),
+];
+
+/// The `Key` for an evdev code, or `None` for codes `Key` has no variant for (media keys,
+/// F13 and up, ...).
+fn map_evdev_key(key: KeyCode) -> Option<Key> {
+    KEYMAP.iter().find(|(code, _)| *code == key).map(|&(_, key)| key)
+}
+
+/// The evdev code the kernel reports for `key`; the inverse of `map_evdev_key`.
+pub(crate) fn evdev_code(key: Key) -> KeyCode {
+    KEYMAP.iter().find(|(_, k)| *k == key).map(|&(code, _)| code).expect("every key has an evdev code")
+}
+
+/// Every code in the key map, for declaring the keys a virtual keyboard can send.
+pub(crate) fn evdev_codes() -> impl Iterator<Item = KeyCode> {
+    KEYMAP.iter().map(|&(code, _)| code)
+}
+
+/// Every keyboard key code evdev defines, mapped or not: the codes from KEY_ESC and from
+/// KEY_OK, leaving out the mouse, joystick and gamepad buttons that follow each range.
+pub(crate) fn keyboard_codes() -> impl Iterator<Item = KeyCode> {
+    (KeyCode::KEY_ESC.0..KeyCode::BTN_0.0).chain(KeyCode::KEY_OK.0..KeyCode::BTN_TRIGGER_HAPPY1.0).map(KeyCode)
+}
@@ -1,50 +1,172 @@
-use crate::{Key, KeyEvent};
+use super::tty::RawMode;
+use super::Source;
+use crate::{CancelHandle, Key, KeyEvent, KeyEventKind, Modifiers, Timestamp};
 use core_foundation::base::TCFType;
-use core_foundation::runloop::{CFRunLoop, CFRunLoopRun, CFRunLoopStop, kCFRunLoopDefaultMode};
+use core_foundation::mach_port::CFMachPortRef;
+use core_foundation::runloop::{kCFRunLoopDefaultMode, CFRunLoop, CFRunLoopStop};
 use core_graphics::event::{CGEvent, CGEventTap, CGEventTapLocation, CGEventTapPlaceholder, CGEventTapOptions, CGEventType};
 use core_graphics::event_source::CGEventSourceStateID;
-use std::sync::mpsc::{channel, Receiver, Sender};
-use std::thread;
 use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
-pub fn wait_key_with_mods() -> io::Result<KeyEvent> {
-    let (tx, rx): (Sender<KeyEvent>, Receiver<KeyEvent>) = channel();
+pub struct EventTapSource {
+    rx: Receiver<KeyEvent>,
+    stop: Arc<AtomicBool>,
+    run_loop: RunLoop,
+    thread: Option<JoinHandle<()>>,
+    cancel: Option<CancelHandle>,
+    _raw: Option<RawMode>,
+}
+
+/// The tap thread's run loop, kept to stop it from `drop`.
+struct RunLoop(CFRunLoop);
+
+// CFRunLoopStop may be called from any thread.
+unsafe impl Send for RunLoop {}
+
+#[link(name = "CoreGraphics", kind = "framework")]
+extern "C" {
+    fn CGEventTapEnable(tap: CFMachPortRef, enable: bool);
+}
+
+impl EventTapSource {
+    pub fn open(cancel: Option<CancelHandle>) -> io::Result<Self> {
+        /// How long the run loop runs between checks of the stop flag, should `CFRunLoopStop`
+        /// arrive before it started.
+        const SLICE: Duration = Duration::from_millis(100);
+
+        let (tx, rx): (Sender<KeyEvent>, Receiver<KeyEvent>) = channel();
+        let (ready_tx, ready_rx) = channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_tap = stop.clone();
+        let stop_loop = stop.clone();
 
-    let tap = match CGEventTap::new(
-        CGEventTapLocation::HID,
-        CGEventTapPlaceholder::Tap,
-        CGEventTapOptions::Default,
-        vec![CGEventType::KeyDown],
-        |_, event_type, event| {
-            if event_type == CGEventType::KeyDown {
-                let keycode = event.get_integer_value_field(::core_graphics::event::kCGKeyboardEventKeycode) as u16;
-                let flags = event.get_flags();
-                let shift = flags.contains(::core_graphics::event::CGEventFlags::CGEventFlagMaskShift);
-                let ctrl = flags.contains(::core_graphics::event::CGEventFlags::CGEventFlagMaskControl);
-                let alt = flags.contains(::core_graphics::event::CGEventFlags::CGEventFlagMaskAlternate);
-                let key = map_keycode(keycode);
+        let thread = thread::spawn(move || {
+            let tap = match CGEventTap::new(
+                CGEventTapLocation::HID,
+                CGEventTapPlaceholder::Tap,
+                CGEventTapOptions::Default,
+                vec![CGEventType::KeyDown],
+                |_, event_type, event| {
+                    if stop_tap.load(Ordering::Relaxed) {
+                        unsafe {
+                            CFRunLoopStop(CFRunLoop::get_current().as_concrete_TypeRef());
+                        }
+                        return None;
+                    }
+                    if event_type == CGEventType::KeyDown {
+                        let keycode = event.get_integer_value_field(::core_graphics::event::kCGKeyboardEventKeycode) as u16;
+                        let flags = event.get_flags();
+                        let shift = flags.contains(::core_graphics::event::CGEventFlags::CGEventFlagMaskShift);
+                        let ctrl = flags.contains(::core_graphics::event::CGEventFlags::CGEventFlagMaskControl);
+                        let alt = flags.contains(::core_graphics::event::CGEventFlags::CGEventFlagMaskAlternate);
+                        let meta = flags.contains(::core_graphics::event::CGEventFlags::CGEventFlagMaskCommand);
+                        let right = right_modifiers(flags.bits());
+                        let key = map_keycode(keycode);
 
-                let _ = tx.send(KeyEvent { key, shift, ctrl, alt });
-                unsafe {
-                    CFRunLoopStop(CFRunLoop::get_current().as_concrete_TypeRef());
+                        let timestamp = Timestamp::now();
+                        let _ = tx.send(KeyEvent { key, kind: KeyEventKind::Press, shift, ctrl, alt, meta, right, timestamp, device: 0 });
+                    }
+                    None
+                },
+            ) {
+                Ok(tap) => tap,
+                Err(_) => {
+                    let _ = ready_tx.send(Err(io::Error::new(io::ErrorKind::PermissionDenied, "cannot create event tap")));
+                    return;
                 }
+            };
+            let run_loop = CFRunLoop::get_current();
+            let source = match tap.mach_port.create_runloop_source(0) {
+                Ok(source) => source,
+                Err(()) => {
+                    let _ = ready_tx.send(Err(io::Error::new(io::ErrorKind::Other, "cannot add the event tap to a run loop")));
+                    return;
+                }
+            };
+            run_loop.add_source(&source, unsafe { kCFRunLoopDefaultMode });
+            tap.enable();
+            let _ = ready_tx.send(Ok(RunLoop(run_loop.clone())));
+            while !stop_loop.load(Ordering::Relaxed) {
+                CFRunLoop::run_in_mode(unsafe { kCFRunLoopDefaultMode }, SLICE, false);
+            }
+            unsafe {
+                CGEventTapEnable(tap.mach_port.as_concrete_TypeRef(), false);
             }
-            None
-        },
-    ) {
-        Ok(tap) => tap,
-        Err(_) => return super::crossterm_fallback::wait_key_with_mods(),
-    };
+            run_loop.remove_source(&source, unsafe { kCFRunLoopDefaultMode });
+        });
 
-    let handle = thread::spawn(move || {
-        let run_loop = CFRunLoop::get_current();
-        tap.enable();
-        run_loop.run();
-    });
+        match ready_rx.recv() {
+            Ok(Ok(run_loop)) => Ok(EventTapSource { rx, stop, run_loop, thread: Some(thread), cancel, _raw: RawMode::enable().ok() }),
+            Ok(Err(err)) => {
+                let _ = thread.join();
+                Err(err)
+            }
+            Err(_) => {
+                let _ = thread.join();
+                Err(io::Error::new(io::ErrorKind::Other, "event tap thread panicked"))
+            }
+        }
+    }
+}
 
-    let event = rx.recv().map_err(|_| io::Error::new(io::ErrorKind::Other, "no event"))?;
-    handle.join().ok();
-    Ok(event)
+impl Drop for EventTapSource {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        unsafe {
+            CFRunLoopStop(self.run_loop.0.as_concrete_TypeRef());
+        }
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Source for EventTapSource {
+    fn devices(&self) -> Vec<String> {
+        vec!["event tap".to_string()]
+    }
+
+    fn next_event(&mut self, timeout: Option<Duration>) -> io::Result<Option<KeyEvent>> {
+        const SLICE: Duration = Duration::from_millis(50);
+        let deadline = timeout.map(|t| Instant::now() + t);
+        loop {
+            if let Some(cancel) = &self.cancel {
+                cancel.check()?;
+            }
+            let remaining = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+            let slice = match (&self.cancel, remaining) {
+                (Some(_), Some(remaining)) => remaining.min(SLICE),
+                (Some(_), None) => SLICE,
+                (None, Some(remaining)) => remaining,
+                (None, None) => {
+                    return self.rx.recv().map(Some).map_err(|_| io::Error::new(io::ErrorKind::Other, "no event"))
+                }
+            };
+            match self.rx.recv_timeout(slice) {
+                Ok(event) => return Ok(Some(event)),
+                Err(RecvTimeoutError::Timeout) if remaining.is_some_and(|r| r <= slice) => return Ok(None),
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => return Err(io::Error::new(io::ErrorKind::Other, "no event")),
+            }
+        }
+    }
+}
+
+/// The right-hand modifiers, from the device-dependent bits of the event flags (IOKit's
+/// `NX_DEVICER*KEYMASK`).
+fn right_modifiers(flags: u64) -> Modifiers {
+    let mut right = Modifiers::NONE;
+    for (mask, m) in [(0x04, Modifiers::SHIFT), (0x2000, Modifiers::CTRL), (0x40, Modifiers::ALT), (0x10, Modifiers::SUPER)] {
+        if flags & mask != 0 {
+            right |= m;
+        }
+    }
+    right
 }
 
 fn map_keycode(code: u16) -> Key {
@@ -1,19 +1,151 @@
+use crate::testing;
+use crate::{CancelHandle, Error, Key, KeyEvent};
+use std::io;
+use std::time::Duration;
+
 #[cfg(windows)]
 mod windows;
-#[cfg(windows)]
-pub use windows::wait_key_with_mods;
 
 #[cfg(target_os = "linux")]
-mod linux;
+pub(crate) mod linux;
 #[cfg(target_os = "linux")]
-pub use linux::wait_key_with_mods;
+pub(crate) mod evdev_dump;
 
 #[cfg(target_os = "macos")]
 mod macos;
-#[cfg(target_os = "macos")]
-pub use macos::wait_key_with_mods;
 
 pub(crate) mod crossterm_fallback;
+#[cfg(unix)]
+pub(crate) mod poll;
+pub(crate) mod tty;
+pub(crate) mod waker;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Backend {
+    /// The native backend, falling back to the terminal when it is unavailable.
+    #[default]
+    Auto,
+    /// evdev on Linux, the console API on Windows, an event tap on macOS.
+    Native,
+    /// Escape sequences read from the controlling terminal.
+    Terminal,
+}
+
+pub(crate) trait Source {
+    /// Waits for the next key event; `Ok(None)` means the timeout elapsed first.
+    fn next_event(&mut self, timeout: Option<Duration>) -> io::Result<Option<KeyEvent>>;
+
+    /// Returns the events that can be read without blocking.
+    fn drain_ready(&mut self) -> io::Result<Vec<KeyEvent>> {
+        let mut events = Vec::new();
+        while let Some(event) = self.next_event(Some(Duration::ZERO))? {
+            events.push(event);
+        }
+        Ok(events)
+    }
+
+    /// Names of the devices events come from, indexed by `KeyEvent::device`.
+    fn devices(&self) -> Vec<String>;
+
+    /// Whether key releases are reported; when false only presses (and maybe repeats) arrive.
+    fn reports_releases(&self) -> bool {
+        false
+    }
+
+    /// Keys held right now according to the OS, independent of buffered events.
+    fn pressed_keys(&self) -> io::Result<Vec<Key>> {
+        Err(Error::Unsupported.into())
+    }
+
+    /// Takes exclusive access to the devices, so no other program receives their input.
+    fn grab(&mut self) -> io::Result<()> {
+        Err(Error::Unsupported.into())
+    }
+
+    /// A descriptor that polls readable when events are waiting, if the backend has one.
+    #[cfg(unix)]
+    fn raw_fd(&self) -> Option<std::os::fd::RawFd> {
+        None
+    }
+}
+
+#[cfg(feature = "async")]
+pub(crate) trait AsyncSource: Send {
+    fn poll_next_event(&mut self, cx: &mut std::task::Context<'_>) -> std::task::Poll<io::Result<KeyEvent>>;
+}
+
+#[cfg(feature = "async")]
+pub(crate) fn open_async(backend: Backend) -> io::Result<Box<dyn AsyncSource>> {
+    if let Some(script) = testing::env_script()? {
+        return Ok(Box::new(script.source(None)));
+    }
+    match backend {
+        Backend::Native => open_native_async(),
+        Backend::Terminal => Ok(Box::new(crossterm_fallback::TerminalStream::open()?)),
+        Backend::Auto => open_native_async().or_else(|_| open_async(Backend::Terminal)),
+    }
+}
+
+#[cfg(all(feature = "async", target_os = "linux"))]
+fn open_native_async() -> io::Result<Box<dyn AsyncSource>> {
+    Ok(Box::new(linux::AsyncEvdevSource::open()?))
+}
+
+#[cfg(all(feature = "async", not(target_os = "linux")))]
+fn open_native_async() -> io::Result<Box<dyn AsyncSource>> {
+    Err(Error::Unsupported.into())
+}
+
+/// Opens `backend`, unless `KEYPRESS_SCRIPT` supplies a script to replay instead.
+pub(crate) fn open(backend: Backend, cancel: Option<&CancelHandle>) -> io::Result<Box<dyn Source>> {
+    let cancel = cancel.cloned();
+    if let Some(script) = testing::env_script()? {
+        return Ok(Box::new(script.source(cancel)));
+    }
+    match backend {
+        Backend::Native => open_native(cancel),
+        Backend::Terminal => Ok(Box::new(crossterm_fallback::TerminalSource::open(cancel)?)),
+        Backend::Auto => open_native(cancel.clone())
+            .or_else(|_| Ok(Box::new(crossterm_fallback::TerminalSource::open(cancel)?))),
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub(crate) fn open_evdev(paths: &[&std::path::Path], cancel: Option<&CancelHandle>) -> io::Result<Box<dyn Source>> {
+    Ok(Box::new(linux::EvdevSource::open_paths(paths, cancel.cloned())?))
+}
+
+#[cfg(windows)]
+fn open_native(cancel: Option<CancelHandle>) -> io::Result<Box<dyn Source>> {
+    Ok(Box::new(windows::ConsoleSource::open(cancel)?))
+}
+
+#[cfg(target_os = "linux")]
+fn open_native(cancel: Option<CancelHandle>) -> io::Result<Box<dyn Source>> {
+    Ok(Box::new(linux::EvdevSource::open(cancel)?))
+}
+
+#[cfg(target_os = "macos")]
+fn open_native(cancel: Option<CancelHandle>) -> io::Result<Box<dyn Source>> {
+    Ok(Box::new(macos::EventTapSource::open(cancel)?))
+}
 
 #[cfg(not(any(windows, target_os = "linux", target_os = "macos")))]
-pub use crossterm_fallback::wait_key_with_mods;
\ No newline at end of file
+fn open_native(cancel: Option<CancelHandle>) -> io::Result<Box<dyn Source>> {
+    Err(Error::Unsupported.into())
+}
+
+#[cfg(target_os = "linux")]
+pub(crate) fn pressed_keys() -> io::Result<Vec<Key>> {
+    linux::pressed_keys()
+}
+
+#[cfg(windows)]
+pub(crate) fn pressed_keys() -> io::Result<Vec<Key>> {
+    Ok(windows::pressed_keys())
+}
+
+#[cfg(not(any(windows, target_os = "linux")))]
+pub(crate) fn pressed_keys() -> io::Result<Vec<Key>> {
+    Err(Error::Unsupported.into())
+}
@@ -0,0 +1,30 @@
+use std::io;
+use std::os::fd::RawFd;
+use std::time::Instant;
+
+/// Waits until one of `fds` is readable or `deadline` passes, reporting readiness per fd.
+///
+/// On timeout every entry is `false`.
+pub fn wait_readable(fds: &[RawFd], deadline: Option<Instant>) -> io::Result<Vec<bool>> {
+    let mut pollfds: Vec<libc::pollfd> = fds
+        .iter()
+        .map(|&fd| libc::pollfd { fd, events: libc::POLLIN, revents: 0 })
+        .collect();
+    loop {
+        let timeout_ms = match deadline {
+            Some(deadline) => deadline
+                .saturating_duration_since(Instant::now())
+                .as_millis()
+                .min(i32::MAX as u128) as i32,
+            None => -1,
+        };
+        let n = unsafe { libc::poll(pollfds.as_mut_ptr(), pollfds.len() as libc::nfds_t, timeout_ms) };
+        if n >= 0 {
+            return Ok(pollfds.iter().map(|p| p.revents != 0).collect());
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+}
@@ -0,0 +1,234 @@
+use crate::Error;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+
+#[cfg(unix)]
+const TTY_PATH: &str = "/dev/tty";
+/// The console input buffer, which raw mode and key reads need.
+#[cfg(windows)]
+const TTY_PATH: &str = "CONIN$";
+
+/// Where prompts are written: the same terminal on Unix, the console screen buffer on Windows.
+#[cfg(unix)]
+const PROMPT_PATH: &str = TTY_PATH;
+#[cfg(windows)]
+const PROMPT_PATH: &str = "CONOUT$";
+
+/// Opens the controlling terminal regardless of where stdin and stdout point.
+pub fn open() -> io::Result<File> {
+    open_path(TTY_PATH)
+}
+
+fn open_path(path: &str) -> io::Result<File> {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+        .map_err(|err| if is_missing_terminal(&err) { Error::NotATerminal.into() } else { err })
+}
+
+#[cfg(unix)]
+fn is_missing_terminal(err: &io::Error) -> bool {
+    matches!(err.raw_os_error(), Some(libc::ENXIO) | Some(libc::ENOENT) | Some(libc::ENODEV) | Some(libc::ENOTTY))
+}
+
+#[cfg(windows)]
+fn is_missing_terminal(err: &io::Error) -> bool {
+    use winapi::shared::winerror::{ERROR_FILE_NOT_FOUND, ERROR_INVALID_HANDLE};
+    matches!(err.raw_os_error(), Some(code) if code == ERROR_FILE_NOT_FOUND as i32 || code == ERROR_INVALID_HANDLE as i32)
+}
+
+pub fn write_prompt(prompt: &str) -> io::Result<()> {
+    if prompt.is_empty() {
+        return Ok(());
+    }
+    match open_path(PROMPT_PATH) {
+        Ok(mut tty) => {
+            tty.write_all(prompt.as_bytes())?;
+            tty.flush()
+        }
+        Err(_) => {
+            eprint!("{}", prompt);
+            io::stderr().flush()
+        }
+    }
+}
+
+/// Puts the controlling terminal into raw mode until dropped.
+///
+/// While active, Ctrl+C arrives as a key instead of raising SIGINT. A signal that still
+/// terminates the process restores the terminal before the default action runs.
+///
+/// Guards nest: only the first saves the terminal settings and signal handlers, and only
+/// the last to be dropped restores them.
+#[cfg(unix)]
+pub struct RawMode {
+    tty: File,
+}
+
+#[cfg(unix)]
+const RESTORE_SIGNALS: [libc::c_int; 4] = [libc::SIGINT, libc::SIGTERM, libc::SIGHUP, libc::SIGQUIT];
+
+/// What the outermost `RawMode` replaced, and how many guards are alive.
+#[cfg(unix)]
+struct Saved {
+    depth: usize,
+    tty: File,
+    original: libc::termios,
+    previous: [(libc::c_int, libc::sigaction); 4],
+}
+
+#[cfg(unix)]
+static SAVED: std::sync::Mutex<Option<Saved>> = std::sync::Mutex::new(None);
+
+// Copies of `SAVED` for the signal handler, which cannot take a lock.
+#[cfg(unix)]
+static SAVED_FD: std::sync::atomic::AtomicI32 = std::sync::atomic::AtomicI32::new(-1);
+#[cfg(unix)]
+static mut SAVED_TERMIOS: std::mem::MaybeUninit<libc::termios> = std::mem::MaybeUninit::uninit();
+#[cfg(unix)]
+static mut SAVED_ACTIONS: [std::mem::MaybeUninit<libc::sigaction>; 4] = [std::mem::MaybeUninit::uninit(); 4];
+
+#[cfg(unix)]
+impl RawMode {
+    pub fn enable() -> io::Result<RawMode> {
+        use std::os::fd::AsRawFd;
+        use std::sync::atomic::Ordering;
+
+        let tty = open()?;
+        let mut saved = SAVED.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(saved) = saved.as_mut() {
+            saved.depth += 1;
+            return Ok(RawMode { tty });
+        }
+
+        let own = tty.try_clone()?;
+        let fd = own.as_raw_fd();
+        let mut original: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(fd, &mut original) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut raw = original;
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG | libc::IEXTEN);
+        raw.c_iflag &= !(libc::IXON | libc::ICRNL);
+        raw.c_cc[libc::VMIN] = 1;
+        raw.c_cc[libc::VTIME] = 0;
+
+        let mut previous: [(libc::c_int, libc::sigaction); 4] = unsafe { std::mem::zeroed() };
+        unsafe {
+            (*std::ptr::addr_of_mut!(SAVED_TERMIOS)).write(original);
+            SAVED_FD.store(fd, Ordering::SeqCst);
+            for (i, &sig) in RESTORE_SIGNALS.iter().enumerate() {
+                let mut action: libc::sigaction = std::mem::zeroed();
+                action.sa_sigaction = restore_and_reraise as extern "C" fn(libc::c_int) as libc::sighandler_t;
+                libc::sigemptyset(&mut action.sa_mask);
+                previous[i].0 = sig;
+                libc::sigaction(sig, &action, &mut previous[i].1);
+                (*std::ptr::addr_of_mut!(SAVED_ACTIONS))[i].write(previous[i].1);
+            }
+        }
+
+        let state = Saved { depth: 1, tty: own, original, previous };
+        if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &raw) } != 0 {
+            let err = io::Error::last_os_error();
+            restore(&state);
+            return Err(err);
+        }
+        *saved = Some(state);
+        Ok(RawMode { tty })
+    }
+}
+
+#[cfg(unix)]
+fn restore(saved: &Saved) {
+    use std::os::fd::AsRawFd;
+    use std::sync::atomic::Ordering;
+
+    unsafe {
+        let fd = saved.tty.as_raw_fd();
+        libc::tcsetattr(fd, libc::TCSANOW, &saved.original);
+        SAVED_FD.store(-1, Ordering::SeqCst);
+        for (sig, action) in &saved.previous {
+            libc::sigaction(*sig, action, std::ptr::null_mut());
+        }
+    }
+}
+
+#[cfg(unix)]
+impl std::os::fd::AsRawFd for RawMode {
+    fn as_raw_fd(&self) -> std::os::fd::RawFd {
+        self.tty.as_raw_fd()
+    }
+}
+
+#[cfg(unix)]
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let mut saved = SAVED.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(state) = saved.as_mut() {
+            state.depth -= 1;
+            if state.depth == 0 {
+                restore(state);
+                *saved = None;
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+extern "C" fn restore_and_reraise(sig: libc::c_int) {
+    use std::sync::atomic::Ordering;
+
+    unsafe {
+        let fd = SAVED_FD.swap(-1, Ordering::SeqCst);
+        if fd >= 0 {
+            libc::tcsetattr(fd, libc::TCSANOW, (*std::ptr::addr_of!(SAVED_TERMIOS)).as_ptr());
+        }
+        if let Some(i) = RESTORE_SIGNALS.iter().position(|&s| s == sig) {
+            libc::sigaction(sig, (*std::ptr::addr_of!(SAVED_ACTIONS))[i].as_ptr(), std::ptr::null_mut());
+        }
+        libc::raise(sig);
+    }
+}
+
+#[cfg(windows)]
+pub struct RawMode(());
+
+#[cfg(windows)]
+static DEPTH: std::sync::Mutex<usize> = std::sync::Mutex::new(0);
+
+#[cfg(windows)]
+impl RawMode {
+    pub fn enable() -> io::Result<RawMode> {
+        open()?;
+        let mut depth = DEPTH.lock().unwrap_or_else(|e| e.into_inner());
+        if *depth == 0 {
+            crossterm::terminal::enable_raw_mode()?;
+        }
+        *depth += 1;
+        Ok(RawMode(()))
+    }
+}
+
+#[cfg(windows)]
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let mut depth = DEPTH.lock().unwrap_or_else(|e| e.into_inner());
+        *depth -= 1;
+        if *depth == 0 {
+            let _ = crossterm::terminal::disable_raw_mode();
+        }
+    }
+}
+
+/// Delivers SIGINT (or a console Ctrl+C event) to the current process.
+pub fn raise_interrupt() {
+    #[cfg(unix)]
+    unsafe {
+        libc::raise(libc::SIGINT);
+    }
+    #[cfg(windows)]
+    unsafe {
+        winapi::um::wincon::GenerateConsoleCtrlEvent(winapi::um::wincon::CTRL_C_EVENT, 0);
+    }
+}
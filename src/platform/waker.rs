@@ -0,0 +1,108 @@
+use std::io;
+
+/// A handle that becomes readable (or signalled) once `wake` has been called, and stays so.
+pub struct Waker {
+    #[cfg(unix)]
+    read: std::os::fd::OwnedFd,
+    #[cfg(all(unix, not(target_os = "linux")))]
+    write: std::os::fd::OwnedFd,
+    #[cfg(windows)]
+    event: winapi::um::winnt::HANDLE,
+}
+
+#[cfg(windows)]
+unsafe impl Send for Waker {}
+#[cfg(windows)]
+unsafe impl Sync for Waker {}
+
+#[cfg(target_os = "linux")]
+impl Waker {
+    pub fn new() -> io::Result<Waker> {
+        use std::os::fd::FromRawFd;
+
+        let fd = unsafe { libc::eventfd(0, libc::EFD_CLOEXEC | libc::EFD_NONBLOCK) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Waker { read: unsafe { std::os::fd::OwnedFd::from_raw_fd(fd) } })
+    }
+
+    pub fn wake(&self) {
+        use std::os::fd::AsRawFd;
+
+        let one: u64 = 1;
+        unsafe {
+            libc::write(self.read.as_raw_fd(), &one as *const u64 as *const libc::c_void, 8);
+        }
+    }
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+impl Waker {
+    pub fn new() -> io::Result<Waker> {
+        use std::os::fd::FromRawFd;
+
+        let mut fds = [0; 2];
+        if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        for fd in fds {
+            unsafe {
+                libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC);
+                libc::fcntl(fd, libc::F_SETFL, libc::O_NONBLOCK);
+            }
+        }
+        unsafe {
+            Ok(Waker {
+                read: std::os::fd::OwnedFd::from_raw_fd(fds[0]),
+                write: std::os::fd::OwnedFd::from_raw_fd(fds[1]),
+            })
+        }
+    }
+
+    pub fn wake(&self) {
+        use std::os::fd::AsRawFd;
+
+        let byte = 1u8;
+        unsafe {
+            libc::write(self.write.as_raw_fd(), &byte as *const u8 as *const libc::c_void, 1);
+        }
+    }
+}
+
+#[cfg(unix)]
+impl std::os::fd::AsRawFd for Waker {
+    fn as_raw_fd(&self) -> std::os::fd::RawFd {
+        self.read.as_raw_fd()
+    }
+}
+
+#[cfg(windows)]
+impl Waker {
+    pub fn new() -> io::Result<Waker> {
+        let event = unsafe { winapi::um::synchapi::CreateEventW(std::ptr::null_mut(), 1, 0, std::ptr::null()) };
+        if event.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Waker { event })
+    }
+
+    pub fn wake(&self) {
+        unsafe {
+            winapi::um::synchapi::SetEvent(self.event);
+        }
+    }
+
+    pub fn handle(&self) -> winapi::um::winnt::HANDLE {
+        self.event
+    }
+}
+
+#[cfg(windows)]
+impl Drop for Waker {
+    fn drop(&mut self) {
+        unsafe {
+            winapi::um::handleapi::CloseHandle(self.event);
+        }
+    }
+}
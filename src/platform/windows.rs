@@ -1,10 +1,17 @@
-use crate::{Key, KeyEvent};
+use super::Source;
+use crate::{CancelHandle, Key, KeyEvent, KeyEventKind, Modifiers, Timestamp};
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
 use std::io;
-use winapi::shared::minwindef::{DWORD, UINT};
+use std::os::windows::io::AsRawHandle;
+use std::time::{Duration, Instant};
+use winapi::shared::minwindef::{DWORD, FALSE, UINT};
 use winapi::um::consoleapi::{ReadConsoleInputW, SetConsoleMode, GetConsoleMode};
 use winapi::um::handleapi::INVALID_HANDLE_VALUE;
 use winapi::um::processenv::GetStdHandle;
-use winapi::um::winbase::STD_INPUT_HANDLE;
+use winapi::um::synchapi::WaitForMultipleObjects;
+use winapi::shared::winerror::WAIT_TIMEOUT;
+use winapi::um::winbase::{INFINITE, STD_INPUT_HANDLE};
 use winapi::um::wincon::{INPUT_RECORD, KEY_EVENT,
                          LEFT_CTRL_PRESSED, RIGHT_CTRL_PRESSED,
                          LEFT_ALT_PRESSED, RIGHT_ALT_PRESSED,
@@ -33,59 +40,109 @@ impl Drop for ConsoleModeGuard {
     }
 }
 
-pub fn wait_key_with_mods() -> io::Result<KeyEvent> {
-    unsafe {
-        let h_stdin = GetStdHandle(STD_INPUT_HANDLE);
-        if h_stdin == INVALID_HANDLE_VALUE {
-            return super::crossterm_fallback::wait_key_with_mods();
-        }
+pub struct ConsoleSource {
+    guard: ConsoleModeGuard,
+    _conin: File,
+    cancel: Option<CancelHandle>,
+    down: HashSet<Key>,
+}
 
-        let _guard = match ConsoleModeGuard::new(h_stdin) {
-            Ok(g) => g,
-            Err(_) => return super::crossterm_fallback::wait_key_with_mods(),
-        };
-
-        let mut record: INPUT_RECORD = std::mem::zeroed();
-        let mut events_read: DWORD = 0;
-        
-        let mut shift_pressed = false;
-        let mut ctrl_pressed = false;
-        let mut alt_pressed = false;
-
-        loop {
-            if ReadConsoleInputW(h_stdin, &mut record, 1, &mut events_read) == 0 {
-                continue;
-            }
-            if events_read == 0 || record.EventType != KEY_EVENT {
-                continue;
-            }
+impl ConsoleSource {
+    pub fn open(cancel: Option<CancelHandle>) -> io::Result<Self> {
+        let conin = OpenOptions::new().read(true).write(true).open("CONIN$")?;
+        let guard = ConsoleModeGuard::new(conin.as_raw_handle() as HANDLE)?;
+        Ok(ConsoleSource { guard, _conin: conin, cancel, down: HashSet::new() })
+    }
+}
 
-            let ke = *record.Event.KeyEvent();
-            let vk = ke.wVirtualKeyCode;
-            let scan = ke.wVirtualScanCode;
-            let state = ke.dwControlKeyState;
-            let is_key_down = ke.bKeyDown != 0;
+impl Source for ConsoleSource {
+    fn devices(&self) -> Vec<String> {
+        vec!["console".to_string()]
+    }
 
-            shift_pressed = (state & SHIFT_PRESSED) != 0;
-            ctrl_pressed = (state & (LEFT_CTRL_PRESSED | RIGHT_CTRL_PRESSED)) != 0;
-            alt_pressed = (state & (LEFT_ALT_PRESSED | RIGHT_ALT_PRESSED)) != 0;
+    fn reports_releases(&self) -> bool {
+        true
+    }
 
-            if vk as i32 == VK_SHIFT || vk as i32 == VK_CONTROL || vk as i32 == VK_MENU {
-                continue;
-            }
+    fn pressed_keys(&self) -> io::Result<Vec<Key>> {
+        Ok(pressed_keys())
+    }
 
-            if !is_key_down {
-                continue;
-            }
+    fn next_event(&mut self, timeout: Option<Duration>) -> io::Result<Option<KeyEvent>> {
+        let h_stdin = self.guard.0;
+        let deadline = timeout.map(|t| Instant::now() + t);
+        unsafe {
+            let mut record: INPUT_RECORD = std::mem::zeroed();
+            let mut events_read: DWORD = 0;
+
+            loop {
+                let wait_ms = match deadline {
+                    Some(deadline) => deadline
+                        .saturating_duration_since(Instant::now())
+                        .as_millis()
+                        .min(INFINITE as u128 - 1) as DWORD,
+                    None => INFINITE,
+                };
+                let mut handles = vec![h_stdin];
+                if let Some(cancel) = &self.cancel {
+                    cancel.check()?;
+                    handles.push(cancel.waker().handle());
+                }
+                let waited = WaitForMultipleObjects(handles.len() as DWORD, handles.as_ptr(), FALSE, wait_ms);
+                if let Some(cancel) = &self.cancel {
+                    cancel.check()?;
+                }
+                if waited == WAIT_TIMEOUT {
+                    return Ok(None);
+                }
+                if ReadConsoleInputW(h_stdin, &mut record, 1, &mut events_read) == 0 {
+                    continue;
+                }
+                if events_read == 0 || record.EventType != KEY_EVENT {
+                    continue;
+                }
+
+                let ke = *record.Event.KeyEvent();
+                let vk = ke.wVirtualKeyCode;
+                let scan = ke.wVirtualScanCode;
+                let state = ke.dwControlKeyState;
+                let is_key_down = ke.bKeyDown != 0;
+
+                let shift_pressed = (state & SHIFT_PRESSED) != 0;
+                let ctrl_pressed = (state & (LEFT_CTRL_PRESSED | RIGHT_CTRL_PRESSED)) != 0;
+                let alt_pressed = (state & (LEFT_ALT_PRESSED | RIGHT_ALT_PRESSED)) != 0;
+
+                let key = map_scan(scan as UINT, vk as UINT, state);
+                let kind = if !is_key_down {
+                    self.down.remove(&key);
+                    KeyEventKind::Release
+                } else if self.down.insert(key) {
+                    KeyEventKind::Press
+                } else {
+                    KeyEventKind::Repeat
+                };
 
-            let key = map_scan(scan as UINT, vk as UINT, state);
+                // The console reports the sides of Ctrl and Alt only; the rest come from the
+                // keys seen going down.
+                let (held, mut right) = Modifiers::held(self.down.iter().copied());
+                for (flag, m) in [(RIGHT_CTRL_PRESSED, Modifiers::CTRL), (RIGHT_ALT_PRESSED, Modifiers::ALT)] {
+                    if state & flag != 0 {
+                        right |= m;
+                    }
+                }
 
-            return Ok(KeyEvent { 
-                key, 
-                shift: shift_pressed, 
-                ctrl: ctrl_pressed, 
-                alt: alt_pressed 
-            });
+                return Ok(Some(KeyEvent {
+                    key,
+                    kind,
+                    shift: shift_pressed,
+                    ctrl: ctrl_pressed,
+                    alt: alt_pressed,
+                    meta: held.contains(Modifiers::SUPER),
+                    right,
+                    timestamp: Timestamp::now(),
+                    device: 0,
+                }));
+            }
         }
     }
 }
@@ -123,6 +180,7 @@ fn map_scan(scan: UINT, vk: UINT, state: DWORD) -> Key {
             if (state & ENHANCED_KEY) != 0 { Key::AltRight } else { Key::AltLeft }
         },
         0x39 => Key::Space,
+        0x5B => Key::MetaLeft, 0x5C => Key::MetaRight,
         0x47 => Key::Home, 0x48 => Key::ArrowUp, 0x49 => Key::PageUp,
         0x4B => Key::ArrowLeft, 0x4D => Key::ArrowRight,
         0x4F => Key::End, 0x50 => Key::ArrowDown, 0x51 => Key::PageDown,
@@ -151,4 +209,62 @@ fn map_scan(scan: UINT, vk: UINT, state: DWORD) -> Key {
             }
         },
     }
-}
\ No newline at end of file
+}
+pub fn pressed_keys() -> Vec<Key> {
+    Key::ALL
+        .iter()
+        .copied()
+        .filter(|&key| match key_vk(key) {
+            Some(vk) => unsafe { GetAsyncKeyState(vk) as u16 & 0x8000 != 0 },
+            None => false,
+        })
+        .collect()
+}
+
+fn key_vk(key: Key) -> Option<i32> {
+    let vk = match key {
+        Key::Escape => VK_ESCAPE,
+        Key::F1 => VK_F1, Key::F2 => VK_F2, Key::F3 => VK_F3, Key::F4 => VK_F4,
+        Key::F5 => VK_F5, Key::F6 => VK_F6, Key::F7 => VK_F7, Key::F8 => VK_F8,
+        Key::F9 => VK_F9, Key::F10 => VK_F10, Key::F11 => VK_F11, Key::F12 => VK_F12,
+        Key::Grave => VK_OEM_3,
+        Key::Digit1 => 0x31, Key::Digit2 => 0x32, Key::Digit3 => 0x33, Key::Digit4 => 0x34,
+        Key::Digit5 => 0x35, Key::Digit6 => 0x36, Key::Digit7 => 0x37, Key::Digit8 => 0x38,
+        Key::Digit9 => 0x39, Key::Digit0 => 0x30,
+        Key::Minus => VK_OEM_MINUS, Key::Equal => VK_OEM_PLUS,
+        Key::Backspace => VK_BACK,
+        Key::Tab => VK_TAB,
+        Key::KeyQ => 0x51, Key::KeyW => 0x57, Key::KeyE => 0x45, Key::KeyR => 0x52,
+        Key::KeyT => 0x54, Key::KeyY => 0x59, Key::KeyU => 0x55, Key::KeyI => 0x49,
+        Key::KeyO => 0x4F, Key::KeyP => 0x50,
+        Key::LeftBracket => VK_OEM_4, Key::RightBracket => VK_OEM_6, Key::Backslash => VK_OEM_5,
+        Key::CapsLock => VK_CAPITAL,
+        Key::KeyA => 0x41, Key::KeyS => 0x53, Key::KeyD => 0x44, Key::KeyF => 0x46,
+        Key::KeyG => 0x47, Key::KeyH => 0x48, Key::KeyJ => 0x4A, Key::KeyK => 0x4B,
+        Key::KeyL => 0x4C,
+        Key::Semicolon => VK_OEM_1, Key::Quote => VK_OEM_7, Key::Enter => VK_RETURN,
+        Key::ShiftLeft => VK_LSHIFT,
+        Key::KeyZ => 0x5A, Key::KeyX => 0x58, Key::KeyC => 0x43, Key::KeyV => 0x56,
+        Key::KeyB => 0x42, Key::KeyN => 0x4E, Key::KeyM => 0x4D,
+        Key::Comma => VK_OEM_COMMA, Key::Period => VK_OEM_PERIOD, Key::Slash => VK_OEM_2,
+        Key::ShiftRight => VK_RSHIFT,
+        Key::ControlLeft => VK_LCONTROL, Key::AltLeft => VK_LMENU, Key::Space => VK_SPACE,
+        Key::AltRight => VK_RMENU, Key::ControlRight => VK_RCONTROL,
+        Key::MetaLeft => VK_LWIN, Key::MetaRight => VK_RWIN,
+        Key::ArrowLeft => VK_LEFT, Key::ArrowRight => VK_RIGHT, Key::ArrowUp => VK_UP,
+        Key::ArrowDown => VK_DOWN,
+        Key::Insert => VK_INSERT, Key::Delete => VK_DELETE, Key::Home => VK_HOME,
+        Key::End => VK_END, Key::PageUp => VK_PRIOR, Key::PageDown => VK_NEXT,
+        Key::NumLock => VK_NUMLOCK,
+        Key::Numpad0 => VK_NUMPAD0, Key::Numpad1 => VK_NUMPAD1, Key::Numpad2 => VK_NUMPAD2,
+        Key::Numpad3 => VK_NUMPAD3, Key::Numpad4 => VK_NUMPAD4, Key::Numpad5 => VK_NUMPAD5,
+        Key::Numpad6 => VK_NUMPAD6, Key::Numpad7 => VK_NUMPAD7, Key::Numpad8 => VK_NUMPAD8,
+        Key::Numpad9 => VK_NUMPAD9,
+        Key::NumpadDivide => VK_DIVIDE, Key::NumpadMultiply => VK_MULTIPLY,
+        Key::NumpadSubtract => VK_SUBTRACT, Key::NumpadAdd => VK_ADD,
+        Key::NumpadDecimal => VK_DECIMAL,
+        Key::NumpadEnter => return None,
+        Key::PrintScreen => VK_SNAPSHOT, Key::ScrollLock => VK_SCROLL, Key::Pause => VK_PAUSE,
+    };
+    Some(vk)
+}
@@ -0,0 +1,636 @@
+//! Recording sessions to a file and playing them back.
+//!
+//! A recording starts with a header naming the format version and the session's devices,
+//! followed by one entry per event with its key, kind, modifiers, timestamps and device.
+//!
+//! The JSON Lines form is meant to be read and edited by people:
+//!
+//! ```text
+//! {"keypress_recording":1,"devices":["AT Translated Set 2 keyboard"]}
+//! {"t_us":81234567,"wall_us":1700000000123456,"key":"KeyA","kind":"press","shift":false,"ctrl":false,"alt":false,"meta":false,"right":0,"device":0}
+//! ```
+//!
+//! `right` is the bitmask of modifiers held with a right-hand key, as in the binary form.
+//! `meta` and `right` may be left out.
+//!
+//! The binary form starts with the magic `KPRB` and a version byte, then the device names as
+//! length-prefixed UTF-8, then fixed 24-byte little-endian records: monotonic and wall time in
+//! microseconds (`u64::MAX` for no wall time), the key's index in `Key::ALL`, the kind, a
+//! modifier bitmask (shift 1, ctrl 2, alt 4, super 8; the same shifted left by four for those
+//! held with a right-hand key), a reserved byte, the device and two reserved bytes.
+
+use crate::testing::ScriptedBackend;
+use crate::time::since_epoch;
+use crate::{Key, KeyEvent, KeyEventKind, Modifiers, Timestamp};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::time::{Duration, UNIX_EPOCH};
+
+/// The newest format version this crate reads and the one it writes.
+pub const VERSION: u32 = 1;
+
+const MAGIC: &[u8; 4] = b"KPRB";
+const JSON_TAG: &str = "\"keypress_recording\"";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum RecordFormat {
+    #[default]
+    JsonLines,
+    Binary,
+}
+
+/// How playback spaces events out.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Timing {
+    /// The gaps between events as recorded.
+    #[default]
+    Original,
+    /// The recorded gaps multiplied by the factor: 0.5 plays twice as fast.
+    Scaled(f64),
+    /// Every event at once.
+    Immediate,
+}
+
+/// Writes events to a recording as they arrive.
+///
+/// Usually driven by `Session::record_to`; use it directly to record events obtained elsewhere.
+pub struct Recorder<W: Write> {
+    writer: W,
+    format: RecordFormat,
+}
+
+impl<W: Write> Recorder<W> {
+    /// Writes the header for a session reading `devices`.
+    pub fn new(mut writer: W, format: RecordFormat, devices: &[String]) -> io::Result<Self> {
+        match format {
+            RecordFormat::JsonLines => {
+                let names: Vec<String> = devices.iter().map(|d| json_string(d)).collect();
+                writeln!(writer, "{{{}:{},\"devices\":[{}]}}", JSON_TAG, VERSION, names.join(","))?;
+            }
+            RecordFormat::Binary => {
+                let count = u16::try_from(devices.len()).map_err(|_| unwritable(format!("{} devices", devices.len())))?;
+                if let Some(long) = devices.iter().find(|d| d.len() > u16::MAX as usize) {
+                    return Err(unwritable(format!("a device name of {} bytes", long.len())));
+                }
+                writer.write_all(MAGIC)?;
+                writer.write_all(&[VERSION as u8])?;
+                writer.write_all(&count.to_le_bytes())?;
+                for device in devices {
+                    writer.write_all(&(device.len() as u16).to_le_bytes())?;
+                    writer.write_all(device.as_bytes())?;
+                }
+            }
+        }
+        Ok(Recorder { writer, format })
+    }
+
+    pub fn record(&mut self, event: &KeyEvent) -> io::Result<()> {
+        let monotonic = event.timestamp.monotonic.as_micros() as u64;
+        let wall = event.timestamp.wall.map(|wall| since_epoch(wall).as_micros() as u64);
+        match self.format {
+            RecordFormat::JsonLines => {
+                let wall = wall.map(|w| format!("\"wall_us\":{},", w)).unwrap_or_default();
+                writeln!(
+                    self.writer,
+                    "{{\"t_us\":{},{}\"key\":\"{}\",\"kind\":\"{}\",\"shift\":{},\"ctrl\":{},\"alt\":{},\"meta\":{},\"right\":{},\"device\":{}}}",
+                    monotonic,
+                    wall,
+                    event.key,
+                    kind_name(event.kind),
+                    event.shift,
+                    event.ctrl,
+                    event.alt,
+                    event.meta,
+                    event.right.bits(),
+                    event.device
+                )
+            }
+            RecordFormat::Binary => {
+                let mut record = [0u8; 24];
+                record[0..8].copy_from_slice(&monotonic.to_le_bytes());
+                record[8..16].copy_from_slice(&wall.unwrap_or(u64::MAX).to_le_bytes());
+                let index = Key::ALL.iter().position(|&k| k == event.key).ok_or_else(|| unwritable(format!("key {}", event.key)))?;
+                record[16] = index as u8;
+                record[17] = event.kind as u8;
+                record[18] = event.modifiers().bits() | event.right.bits() << 4;
+                record[20..22].copy_from_slice(&event.device.to_le_bytes());
+                self.writer.write_all(&record)
+            }
+        }
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// A recording loaded into memory.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Recording {
+    pub devices: Vec<String>,
+    pub events: Vec<KeyEvent>,
+}
+
+impl Recording {
+    /// Reads a recording in either format, telling them apart by the first bytes.
+    pub fn read(reader: impl Read) -> io::Result<Recording> {
+        let mut reader = BufReader::new(reader);
+        if reader.fill_buf()?.starts_with(MAGIC) {
+            read_binary(reader)
+        } else {
+            read_json_lines(reader)
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Recording> {
+        Self::read(File::open(path)?)
+    }
+
+    pub fn write(&self, writer: impl Write, format: RecordFormat) -> io::Result<()> {
+        let mut recorder = Recorder::new(writer, format, &self.devices)?;
+        for event in &self.events {
+            recorder.record(event)?;
+        }
+        recorder.flush()
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>, format: RecordFormat) -> io::Result<()> {
+        self.write(BufWriter::new(File::create(path)?), format)
+    }
+
+    /// A scripted backend replaying the events; see `keypress::testing`.
+    ///
+    /// Replayed events keep their key, kind, modifiers and device and are stamped when delivered.
+    pub fn playback(&self, timing: Timing) -> ScriptedBackend {
+        let script = ScriptedBackend::new();
+        script.set_devices(self.devices.clone());
+        let mut previous = None;
+        for event in &self.events {
+            let gap = previous.map(|p| event.timestamp.monotonic.saturating_sub(p)).unwrap_or_default();
+            previous = Some(event.timestamp.monotonic);
+            let delay = match timing {
+                Timing::Original => gap,
+                Timing::Scaled(factor) if factor.is_finite() && factor > 0.0 => gap.mul_f64(factor),
+                Timing::Scaled(_) | Timing::Immediate => Duration::ZERO,
+            };
+            script.push_recorded(delay, *event);
+        }
+        script
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Recording {
+    /// Decodes `evemu-record` output through the evdev backend's key mapping, modifier
+    /// tracking and repeat handling. Timestamps are the capture's relative times.
+    pub fn from_evemu(reader: impl Read) -> io::Result<Recording> {
+        crate::platform::evdev_dump::read_evemu(BufReader::new(reader))
+    }
+
+    /// Decodes a raw `struct input_event` stream, e.g. from `cat /dev/input/eventX > dump`,
+    /// like `from_evemu`. The records must use this machine's layout and byte order.
+    pub fn from_input_events(reader: impl Read) -> io::Result<Recording> {
+        crate::platform::evdev_dump::read_input_events(reader)
+    }
+}
+
+/// Whether `bytes` look like the start of a recording in either format.
+pub(crate) fn is_recording(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC) || bytes.strip_prefix(b"{").is_some_and(|rest| rest.starts_with(JSON_TAG.as_bytes()))
+}
+
+fn kind_name(kind: KeyEventKind) -> &'static str {
+    match kind {
+        KeyEventKind::Press => "press",
+        KeyEventKind::Repeat => "repeat",
+        KeyEventKind::Release => "release",
+    }
+}
+
+fn invalid(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+/// A value the binary format has no room for.
+fn unwritable(what: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, format!("the binary recording format cannot hold {}", what))
+}
+
+fn check_version(version: u64) -> io::Result<()> {
+    if version == 0 || version > VERSION as u64 {
+        return Err(invalid(format!("unsupported recording version {}", version)));
+    }
+    Ok(())
+}
+
+fn timestamp(monotonic_us: u64, wall_us: Option<u64>) -> Timestamp {
+    Timestamp {
+        monotonic: Duration::from_micros(monotonic_us),
+        wall: wall_us.and_then(|w| UNIX_EPOCH.checked_add(Duration::from_micros(w))),
+        latency: None,
+    }
+}
+
+fn read_binary(mut reader: impl Read) -> io::Result<Recording> {
+    let mut header = [0u8; 7];
+    reader.read_exact(&mut header)?;
+    check_version(header[4] as u64)?;
+    let count = u16::from_le_bytes([header[5], header[6]]);
+    let mut devices = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let mut len = [0u8; 2];
+        reader.read_exact(&mut len)?;
+        let mut name = vec![0u8; u16::from_le_bytes(len) as usize];
+        reader.read_exact(&mut name)?;
+        devices.push(String::from_utf8(name).map_err(|_| invalid("device name is not UTF-8"))?);
+    }
+
+    let mut events = Vec::new();
+    let mut record = [0u8; 24];
+    loop {
+        match reader.read_exact(&mut record) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(err) => return Err(err),
+        }
+        let word = |at: usize| u64::from_le_bytes(record[at..at + 8].try_into().unwrap());
+        let wall = Some(word(8)).filter(|&w| w != u64::MAX);
+        let key = *Key::ALL.get(record[16] as usize).ok_or_else(|| invalid(format!("bad key index {}", record[16])))?;
+        let kind = match record[17] {
+            0 => KeyEventKind::Press,
+            1 => KeyEventKind::Repeat,
+            2 => KeyEventKind::Release,
+            other => return Err(invalid(format!("bad event kind {}", other))),
+        };
+        events.push(KeyEvent {
+            key,
+            kind,
+            shift: record[18] & 1 != 0,
+            ctrl: record[18] & 2 != 0,
+            alt: record[18] & 4 != 0,
+            meta: record[18] & 8 != 0,
+            right: Modifiers::from_bits(record[18] >> 4),
+            timestamp: timestamp(word(0), wall),
+            device: u16::from_le_bytes([record[20], record[21]]),
+        });
+    }
+    Ok(Recording { devices, events })
+}
+
+fn read_json_lines(reader: impl BufRead) -> io::Result<Recording> {
+    let mut lines = reader.lines().enumerate().filter(|(_, line)| !matches!(line, Ok(l) if l.trim().is_empty()));
+    let at = |n: usize, msg: String| invalid(format!("line {}: {}", n + 1, msg));
+
+    let (n, header) = lines.next().ok_or_else(|| invalid("empty recording"))?;
+    let header = json::parse_object(&header?).map_err(|msg| at(n, msg))?;
+    let version = header.number("keypress_recording").map_err(|_| at(n, "not a keypress recording".into()))?;
+    check_version(version)?;
+    let devices = match header.get("devices") {
+        Some(json::Value::Array(items)) => items
+            .iter()
+            .map(|item| match item {
+                json::Value::Str(name) => Ok(name.clone()),
+                _ => Err(at(n, "device names must be strings".into())),
+            })
+            .collect::<io::Result<Vec<_>>>()?,
+        None => Vec::new(),
+        Some(_) => return Err(at(n, "\"devices\" must be an array".into())),
+    };
+
+    let mut events = Vec::new();
+    for (n, line) in lines {
+        let object = json::parse_object(&line?).map_err(|msg| at(n, msg))?;
+        let event = (|| -> Result<KeyEvent, String> {
+            let key = object.string("key")?.parse::<Key>().map_err(|err| err.to_string())?;
+            let kind = match object.string("kind")? {
+                "press" => KeyEventKind::Press,
+                "repeat" => KeyEventKind::Repeat,
+                "release" => KeyEventKind::Release,
+                other => return Err(format!("bad event kind {:?}", other)),
+            };
+            let wall = match object.get("wall_us") {
+                None | Some(json::Value::Null) => None,
+                Some(_) => Some(object.number("wall_us")?),
+            };
+            Ok(KeyEvent {
+                key,
+                kind,
+                shift: object.flag("shift")?,
+                ctrl: object.flag("ctrl")?,
+                alt: object.flag("alt")?,
+                meta: object.flag("meta")?,
+                right: match object.get("right") {
+                    None => Modifiers::NONE,
+                    Some(_) => Modifiers::from_bits(object.number("right")? as u8),
+                },
+                timestamp: timestamp(object.number("t_us")?, wall),
+                device: u16::try_from(object.number("device").unwrap_or(0)).map_err(|_| "device out of range".to_string())?,
+            })
+        })()
+        .map_err(|msg| at(n, msg))?;
+        events.push(event);
+    }
+    Ok(Recording { devices, events })
+}
+
+fn json_string(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Just enough JSON for one flat object per line.
+mod json {
+    use std::iter::Peekable;
+    use std::str::Chars;
+
+    pub enum Value {
+        Null,
+        Bool(bool),
+        Number(u64),
+        Str(String),
+        Array(Vec<Value>),
+    }
+
+    pub struct Object(Vec<(String, Value)>);
+
+    impl Object {
+        pub fn get(&self, name: &str) -> Option<&Value> {
+            self.0.iter().find(|(key, _)| key == name).map(|(_, value)| value)
+        }
+
+        pub fn string(&self, name: &str) -> Result<&str, String> {
+            match self.get(name) {
+                Some(Value::Str(s)) => Ok(s),
+                _ => Err(format!("expected string field {:?}", name)),
+            }
+        }
+
+        pub fn number(&self, name: &str) -> Result<u64, String> {
+            match self.get(name) {
+                Some(Value::Number(n)) => Ok(*n),
+                _ => Err(format!("expected integer field {:?}", name)),
+            }
+        }
+
+        pub fn flag(&self, name: &str) -> Result<bool, String> {
+            match self.get(name) {
+                Some(Value::Bool(b)) => Ok(*b),
+                None => Ok(false),
+                _ => Err(format!("expected boolean field {:?}", name)),
+            }
+        }
+    }
+
+    pub fn parse_object(text: &str) -> Result<Object, String> {
+        let mut chars = text.trim().chars().peekable();
+        expect(&mut chars, '{')?;
+        let mut fields = Vec::new();
+        skip_ws(&mut chars);
+        if chars.peek() == Some(&'}') {
+            chars.next();
+        } else {
+            loop {
+                skip_ws(&mut chars);
+                let name = string(&mut chars)?;
+                skip_ws(&mut chars);
+                expect(&mut chars, ':')?;
+                fields.push((name, value(&mut chars)?));
+                skip_ws(&mut chars);
+                match chars.next() {
+                    Some(',') => continue,
+                    Some('}') => break,
+                    _ => return Err("expected ',' or '}'".into()),
+                }
+            }
+        }
+        skip_ws(&mut chars);
+        match chars.next() {
+            None => Ok(Object(fields)),
+            Some(c) => Err(format!("unexpected {:?} after object", c)),
+        }
+    }
+
+    fn value(chars: &mut Peekable<Chars>) -> Result<Value, String> {
+        skip_ws(chars);
+        match chars.peek() {
+            Some('"') => string(chars).map(Value::Str),
+            Some('[') => {
+                chars.next();
+                let mut items = Vec::new();
+                skip_ws(chars);
+                if chars.peek() == Some(&']') {
+                    chars.next();
+                    return Ok(Value::Array(items));
+                }
+                loop {
+                    items.push(value(chars)?);
+                    skip_ws(chars);
+                    match chars.next() {
+                        Some(',') => continue,
+                        Some(']') => return Ok(Value::Array(items)),
+                        _ => return Err("expected ',' or ']'".into()),
+                    }
+                }
+            }
+            Some(c) if c.is_ascii_digit() => {
+                let mut digits = String::new();
+                while let Some(&c) = chars.peek().filter(|c| c.is_ascii_digit()) {
+                    digits.push(c);
+                    chars.next();
+                }
+                digits.parse().map(Value::Number).map_err(|_| format!("bad number {}", digits))
+            }
+            Some(_) => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek().filter(|c| c.is_ascii_alphabetic()) {
+                    word.push(c);
+                    chars.next();
+                }
+                match word.as_str() {
+                    "true" => Ok(Value::Bool(true)),
+                    "false" => Ok(Value::Bool(false)),
+                    "null" => Ok(Value::Null),
+                    _ => Err(format!("unexpected value {:?}", word)),
+                }
+            }
+            None => Err("unexpected end of line".into()),
+        }
+    }
+
+    fn string(chars: &mut Peekable<Chars>) -> Result<String, String> {
+        expect(chars, '"')?;
+        let mut out = String::new();
+        loop {
+            match chars.next() {
+                Some('"') => return Ok(out),
+                Some('\\') => match chars.next() {
+                    Some('n') => out.push('\n'),
+                    Some('t') => out.push('\t'),
+                    Some('r') => out.push('\r'),
+                    Some('u') => {
+                        let hex: String = chars.by_ref().take(4).collect();
+                        let code = u32::from_str_radix(&hex, 16).map_err(|_| format!("bad escape \\u{}", hex))?;
+                        out.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+                    }
+                    Some(c) => out.push(c),
+                    None => return Err("unterminated string".into()),
+                },
+                Some(c) => out.push(c),
+                None => return Err("unterminated string".into()),
+            }
+        }
+    }
+
+    fn expect(chars: &mut Peekable<Chars>, want: char) -> Result<(), String> {
+        match chars.next() {
+            Some(c) if c == want => Ok(()),
+            Some(c) => Err(format!("expected {:?}, found {:?}", want, c)),
+            None => Err(format!("expected {:?}", want)),
+        }
+    }
+
+    fn skip_ws(chars: &mut Peekable<Chars>) {
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(key: Key, kind: KeyEventKind, t_us: u64, wall_us: Option<u64>) -> KeyEvent {
+        KeyEvent {
+            key,
+            kind,
+            shift: false,
+            ctrl: false,
+            alt: false,
+            meta: false,
+            right: Modifiers::NONE,
+            timestamp: timestamp(t_us, wall_us),
+            device: 0,
+        }
+    }
+
+    fn sample() -> Recording {
+        let shifted = KeyEvent {
+            shift: true,
+            ctrl: true,
+            meta: true,
+            right: Modifiers::SHIFT,
+            device: 1,
+            ..event(Key::KeyA, KeyEventKind::Repeat, 1_500, None)
+        };
+        Recording {
+            devices: vec!["AT keyboard".into(), "Quote \" and \\ and\nnewline, é".into()],
+            events: vec![
+                event(Key::ShiftRight, KeyEventKind::Press, 1_000, Some(1_700_000_000_123_456)),
+                shifted,
+                event(Key::KeyA, KeyEventKind::Release, 2_000, Some(1_700_000_000_124_456)),
+                event(Key::Pause, KeyEventKind::Press, u32::MAX as u64 * 10, None),
+            ],
+        }
+    }
+
+    fn round_trip(format: RecordFormat) {
+        let recording = sample();
+        let mut bytes = Vec::new();
+        recording.write(&mut bytes, format).unwrap();
+        assert!(is_recording(&bytes));
+        assert_eq!(Recording::read(&bytes[..]).unwrap(), recording);
+    }
+
+    #[test]
+    fn json_lines_round_trip() {
+        round_trip(RecordFormat::JsonLines);
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        round_trip(RecordFormat::Binary);
+    }
+
+    #[test]
+    fn binary_rejects_what_it_cannot_hold() {
+        let long = "x".repeat(u16::MAX as usize + 1);
+        let err = Recorder::new(Vec::new(), RecordFormat::Binary, &[long]).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        let many = vec![String::new(); u16::MAX as usize + 1];
+        let err = Recorder::new(Vec::new(), RecordFormat::Binary, &many).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        // Cutting this one at the limit would split its last character.
+        let split = "é".repeat(u16::MAX as usize / 2 + 1);
+        let err = Recorder::new(Vec::new(), RecordFormat::Binary, &[split]).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        let name = "é".repeat(u16::MAX as usize / 2) + "a";
+        let recording = Recording { devices: vec![name], events: Vec::new() };
+        let mut bytes = Vec::new();
+        recording.write(&mut bytes, RecordFormat::Binary).unwrap();
+        assert_eq!(Recording::read(&bytes[..]).unwrap(), recording);
+    }
+
+    #[test]
+    fn json_parser_reads_escapes_and_skips_unknown_fields() {
+        let text = concat!(
+            "{\"keypress_recording\":1,\"devices\":[\"a\\\"b\\\\c\\n\\t\\u00e9\\/\"],\"host\":\"box\"}\n",
+            "\n",
+            "{ \"t_us\" : 5 , \"key\":\"esc\",\"kind\":\"press\",\"extra\":[1,[true,null]],\"wall_us\":null }\n",
+        );
+        let recording = Recording::read(text.as_bytes()).unwrap();
+        assert_eq!(recording.devices, ["a\"b\\c\n\té/"]);
+        assert_eq!(recording.events, [event(Key::Escape, KeyEventKind::Press, 5, None)]);
+    }
+
+    #[test]
+    fn json_parser_reports_malformed_lines() {
+        let header = "{\"keypress_recording\":1}\n";
+        for (line, message) in [
+            ("{\"t_us\":1,\"key\":\"KeyA\",\"kind\":\"press\"", "line 2: expected ',' or '}'"),
+            ("{\"t_us\":1,\"key\":\"KeyA\",\"kind\":\"hold\"}", "line 2: bad event kind \"hold\""),
+            ("{\"t_us\":1,\"key\":\"Nope\",\"kind\":\"press\"}", "line 2: unknown key name \"Nope\""),
+            ("{\"t_us\":\"1\",\"key\":\"KeyA\",\"kind\":\"press\"}", "line 2: expected integer field \"t_us\""),
+            ("{\"t_us\":1,\"key\":\"KeyA\",\"kind\":\"press\",\"shift\":1}", "line 2: expected boolean field \"shift\""),
+            ("{\"t_us\":1,\"key\":\"KeyA\",\"kind\":\"press\"} x", "line 2: unexpected 'x' after object"),
+            ("{\"t_us\":1,\"key\":\"KeyA\",\"kind\":\"press\",\"device\":70000}", "line 2: device out of range"),
+            ("{\"key\":\"Key", "line 2: unterminated string"),
+        ] {
+            let err = Recording::read(format!("{}{}\n", header, line).as_bytes()).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{}", line);
+            assert_eq!(err.to_string(), message, "{}", line);
+        }
+        let err = Recording::read("{\"devices\":[]}\n".as_bytes()).unwrap_err();
+        assert_eq!(err.to_string(), "line 1: not a keypress recording");
+        assert_eq!(Recording::read(&b""[..]).unwrap_err().to_string(), "empty recording");
+    }
+
+    #[test]
+    fn unsupported_versions_are_rejected() {
+        for version in [0, VERSION + 1] {
+            let err = Recording::read(format!("{{\"keypress_recording\":{}}}\n", version).as_bytes()).unwrap_err();
+            assert_eq!(err.to_string(), format!("unsupported recording version {}", version));
+            let binary = [&MAGIC[..], &[version as u8, 0, 0]].concat();
+            let err = Recording::read(&binary[..]).unwrap_err();
+            assert_eq!(err.to_string(), format!("unsupported recording version {}", version));
+        }
+    }
+}
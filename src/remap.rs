@@ -0,0 +1,445 @@
+use crate::{Key, KeyEvent, KeyEventKind, Modifiers};
+use std::collections::HashMap;
+use std::io;
+#[cfg(feature = "toml")]
+use std::path::Path;
+
+/// Rewrites key events between the backend and the caller.
+///
+/// Three kinds of rule are applied to every press, in this order:
+///
+/// 1. While a layer's hold key is down, that layer's mappings replace everything else. The hold
+///    key itself is swallowed; the most recently held layer wins.
+/// 2. Physical rewrites (`map`, `swap`) change the key, e.g. CapsLock into ControlLeft. A key
+///    rewritten into a modifier sets that modifier's flag on the events that follow.
+/// 3. Modifier-dependent remaps (`map_chord`) then turn a key pressed with exactly the given
+///    modifiers into another key and modifier set, e.g. Alt+H into ArrowLeft.
+///
+/// Repeats and releases follow whatever the press turned into, even if the active layer has
+/// changed since. Layers need key releases; on backends without them (the terminal) hold keys
+/// pass through and layers never activate.
+#[derive(Debug, Clone)]
+pub struct Remapper {
+    keys: HashMap<Key, Key>,
+    chords: HashMap<(Key, Modifiers), (Key, Modifiers)>,
+    layers: Vec<Layer>,
+    /// Physical keys held down, and what their press was delivered as (`None` if swallowed).
+    active: HashMap<Key, Option<(Key, Option<Modifiers>)>>,
+    /// Indices of layers whose hold key is down, oldest first.
+    held_layers: Vec<usize>,
+    releases: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Layer {
+    name: String,
+    hold: Key,
+    keys: HashMap<Key, Key>,
+}
+
+impl Default for Remapper {
+    fn default() -> Self {
+        Remapper {
+            keys: HashMap::new(),
+            chords: HashMap::new(),
+            layers: Vec::new(),
+            active: HashMap::new(),
+            held_layers: Vec::new(),
+            releases: true,
+        }
+    }
+}
+
+impl Remapper {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Delivers `from` as `to`.
+    pub fn map(mut self, from: Key, to: Key) -> Self {
+        self.keys.insert(from, to);
+        self
+    }
+
+    /// Exchanges two keys, e.g. `AltLeft` and `MetaLeft`.
+    pub fn swap(self, a: Key, b: Key) -> Self {
+        self.map(a, b).map(b, a)
+    }
+
+    /// Delivers `from` pressed with exactly `modifiers` as `to` with `to_modifiers`.
+    pub fn map_chord(mut self, from: Key, modifiers: Modifiers, to: Key, to_modifiers: Modifiers) -> Self {
+        self.chords.insert((from, modifiers), (to, to_modifiers));
+        self
+    }
+
+    /// Adds a layer active while `hold` is down, mapping keys as given.
+    pub fn layer(mut self, name: impl Into<String>, hold: Key, keys: impl IntoIterator<Item = (Key, Key)>) -> Self {
+        self.layers.push(Layer { name: name.into(), hold, keys: keys.into_iter().collect() });
+        self
+    }
+
+    /// Reads rules from TOML:
+    ///
+    /// ```toml
+    /// [keys]
+    /// CapsLock = "ControlLeft"
+    ///
+    /// [chords]
+    /// "Alt+H" = "ArrowLeft"
+    ///
+    /// [layers.fn]
+    /// hold = "AltRight"
+    /// keys = { H = "ArrowLeft", J = "ArrowDown", K = "ArrowUp", L = "ArrowRight" }
+    /// ```
+    #[cfg(feature = "toml")]
+    pub fn from_toml(text: &str) -> io::Result<Remapper> {
+        let table: toml::Table = text.parse().map_err(|err| invalid(format!("{}", err)))?;
+        Self::from_table(&table)
+    }
+
+    #[cfg(feature = "toml")]
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Remapper> {
+        Self::from_toml(&std::fs::read_to_string(path)?)
+    }
+
+    #[cfg(feature = "toml")]
+    pub(crate) fn from_table(table: &toml::Table) -> io::Result<Remapper> {
+        let mut remapper = Remapper::new();
+        for (section, value) in table {
+            match (section.as_str(), value) {
+                ("keys", toml::Value::Table(keys)) => {
+                    for (from, to) in keys {
+                        remapper = remapper.map(key(from)?, key(string(to, from)?)?);
+                    }
+                }
+                ("chords", toml::Value::Table(chords)) => {
+                    for (from, to) in chords {
+                        let (from, mods) = chord(from)?;
+                        let (to, to_mods) = chord(string(to, &format!("chords.{}", from))?)?;
+                        remapper = remapper.map_chord(from, mods, to, to_mods);
+                    }
+                }
+                ("layers", toml::Value::Table(layers)) => {
+                    for (name, layer) in layers {
+                        let layer = layer.as_table().ok_or_else(|| invalid(format!("layer {:?} must be a table", name)))?;
+                        let hold = layer.get("hold").ok_or_else(|| invalid(format!("layer {:?} has no hold key", name)))?;
+                        let hold = key(string(hold, "hold")?)?;
+                        let mut keys = Vec::new();
+                        if let Some(map) = layer.get("keys") {
+                            let map = map.as_table().ok_or_else(|| invalid(format!("layers.{}.keys must be a table", name)))?;
+                            for (from, to) in map {
+                                keys.push((key(from)?, key(string(to, from)?)?));
+                            }
+                        }
+                        remapper = remapper.layer(name.clone(), hold, keys);
+                    }
+                }
+                _ => return Err(invalid(format!("unexpected remap section {:?}", section))),
+            }
+        }
+        Ok(remapper)
+    }
+
+    /// The layer whose mappings apply right now, if any.
+    pub fn active_layer(&self) -> Option<&str> {
+        self.held_layers.last().map(|&i| self.layers[i].name.as_str())
+    }
+
+    /// Forgets held keys and layers, e.g. after the input source was reopened.
+    pub fn reset(&mut self) {
+        self.active.clear();
+        self.held_layers.clear();
+    }
+
+    /// Tells the remapper whether releases will arrive; see the type documentation.
+    pub(crate) fn set_releases(&mut self, releases: bool) {
+        self.releases = releases;
+        self.reset();
+    }
+
+    /// Rewrites one event; `None` means it was swallowed (a layer hold key).
+    pub fn process(&mut self, event: KeyEvent) -> Option<KeyEvent> {
+        if !self.releases {
+            let key = self.keys.get(&event.key).copied().unwrap_or(event.key);
+            return Some(self.chord(KeyEvent { key, ..event }).0);
+        }
+
+        let phys = event.key;
+        match event.kind {
+            KeyEventKind::Press => {
+                if let Some(layer) = self.layers.iter().position(|l| l.hold == phys) {
+                    self.active.insert(phys, None);
+                    self.held_layers.retain(|&i| i != layer);
+                    self.held_layers.push(layer);
+                    return None;
+                }
+                let key = match self.held_layers.last().and_then(|&i| self.layers[i].keys.get(&phys)) {
+                    Some(&key) => key,
+                    None => self.keys.get(&phys).copied().unwrap_or(phys),
+                };
+                self.active.insert(phys, Some((key, None)));
+                let (out, chord) = self.chord(self.flags(KeyEvent { key, ..event }));
+                if chord {
+                    self.active.insert(phys, Some((out.key, Some(out.modifiers()))));
+                }
+                Some(out)
+            }
+            KeyEventKind::Repeat | KeyEventKind::Release => {
+                let delivered = if event.kind == KeyEventKind::Release {
+                    self.active.remove(&phys)
+                } else {
+                    self.active.get(&phys).copied()
+                };
+                match delivered {
+                    Some(None) => {
+                        if event.kind == KeyEventKind::Release {
+                            self.held_layers.retain(|&i| self.layers[i].hold != phys);
+                        }
+                        None
+                    }
+                    Some(Some((key, Some(mods)))) => Some(with_modifiers(KeyEvent { key, ..event }, mods)),
+                    Some(Some((key, None))) => Some(self.flags(KeyEvent { key, ..event })),
+                    None => {
+                        let key = self.keys.get(&phys).copied().unwrap_or(phys);
+                        Some(self.flags(KeyEvent { key, ..event }))
+                    }
+                }
+            }
+        }
+    }
+
+    /// Whether some physical input comes out as `key` with `mods`, ignoring layers' hold keys.
+    ///
+    /// The key must be delivered by a physical key or a layer, every modifier must be
+    /// delivered by some key, and a chord remap must not take the combination first, unless
+    /// another chord produces it.
+    pub(crate) fn can_produce(&self, key: Key, mods: Modifiers) -> bool {
+        let delivers = |wanted: &dyn Fn(Key) -> bool| {
+            let holds: Vec<Key> = self.layers.iter().map(|l| l.hold).collect();
+            Key::ALL.iter().any(|&phys| !holds.contains(&phys) && wanted(self.keys.get(&phys).copied().unwrap_or(phys)))
+                || self.layers.iter().any(|l| l.keys.values().any(|&k| wanted(k)))
+        };
+        let chord_out = self.chords.values().any(|&(k, m)| k == key && m == mods);
+        if chord_out {
+            return true;
+        }
+        if !delivers(&|k| k == key) || self.chords.contains_key(&(key, mods)) {
+            return false;
+        }
+        [Modifiers::SHIFT, Modifiers::CTRL, Modifiers::ALT, Modifiers::SUPER]
+            .into_iter()
+            .filter(|&m| mods.contains(m))
+            .all(|m| delivers(&|k| Modifiers::of_key(k).is_some_and(|(of, _)| of == m)))
+    }
+
+    /// Applies a modifier-dependent remap; the flag tells whether one matched.
+    fn chord(&self, event: KeyEvent) -> (KeyEvent, bool) {
+        match self.chords.get(&(event.key, event.modifiers())) {
+            Some(&(key, mods)) => (with_modifiers(KeyEvent { key, ..event }, mods), true),
+            None => (event, false),
+        }
+    }
+
+    /// Recomputes the modifier flags from what held keys were delivered as.
+    fn flags(&self, event: KeyEvent) -> KeyEvent {
+        let (mut mods, mut right) = (Modifiers::NONE, Modifiers::NONE);
+        for (m, sources) in [
+            (Modifiers::SHIFT, [Key::ShiftLeft, Key::ShiftRight]),
+            (Modifiers::CTRL, [Key::ControlLeft, Key::ControlRight]),
+            (Modifiers::ALT, [Key::AltLeft, Key::AltRight]),
+            (Modifiers::SUPER, [Key::MetaLeft, Key::MetaRight]),
+        ] {
+            let (held, held_right) = self.flag(event.modifiers().contains(m), event.right.contains(m), sources);
+            if held {
+                mods |= m;
+            }
+            if held_right {
+                right |= m;
+            }
+        }
+        KeyEvent { right, ..with_modifiers(event, mods) }
+    }
+
+    /// Whether a modifier is held, and held on the right, given what the backend reported.
+    fn flag(&self, reported: bool, reported_right: bool, sources: [Key; 2]) -> (bool, bool) {
+        let delivered_as = |phys: &Key, keys: &[Key]| matches!(self.active.get(phys), Some(Some((key, _))) if keys.contains(key));
+        let delivered = |phys: &Key| delivered_as(phys, &sources);
+        let produced = self.active.keys().any(delivered);
+        let produced_right = self.active.keys().any(|phys| delivered_as(phys, &sources[1..]));
+        let physical: Vec<&Key> = sources.iter().filter(|k| self.active.contains_key(k)).collect();
+        let kept = reported && (physical.is_empty() || physical.into_iter().any(delivered));
+        let kept_right = reported_right && !self.active.contains_key(&sources[1]);
+        (produced || kept, produced_right || kept_right)
+    }
+}
+
+/// Sets the modifier flags to `mods`; the sides follow when a flag is cleared.
+fn with_modifiers(event: KeyEvent, mods: Modifiers) -> KeyEvent {
+    KeyEvent {
+        shift: mods.contains(Modifiers::SHIFT),
+        ctrl: mods.contains(Modifiers::CTRL),
+        alt: mods.contains(Modifiers::ALT),
+        meta: mods.contains(Modifiers::SUPER),
+        right: Modifiers::from_bits(event.right.bits() & mods.bits()),
+        ..event
+    }
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(feature = "toml")]
+fn string<'a>(value: &'a toml::Value, what: &str) -> io::Result<&'a str> {
+    value.as_str().ok_or_else(|| invalid(format!("{:?} must map to a key name", what)))
+}
+
+#[cfg(feature = "toml")]
+fn key(name: &str) -> io::Result<Key> {
+    name.parse().map_err(|err: crate::ParseKeyError| invalid(err.to_string()))
+}
+
+#[cfg(feature = "toml")]
+fn chord(text: &str) -> io::Result<(Key, Modifiers)> {
+    crate::names::parse_chord(text).map_err(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Timestamp;
+
+    /// An event as a backend reports it, with the modifier flags of the physical keys held.
+    fn event(key: Key, kind: KeyEventKind, mods: Modifiers) -> KeyEvent {
+        with_modifiers(
+            KeyEvent {
+                key,
+                kind,
+                shift: false,
+                ctrl: false,
+                alt: false,
+                meta: false,
+                right: Modifiers::NONE,
+                timestamp: Timestamp::default(),
+                device: 0,
+            },
+            mods,
+        )
+    }
+
+    fn press(key: Key, mods: Modifiers) -> KeyEvent {
+        event(key, KeyEventKind::Press, mods)
+    }
+
+    fn release(key: Key, mods: Modifiers) -> KeyEvent {
+        event(key, KeyEventKind::Release, mods)
+    }
+
+    fn out(remapper: &mut Remapper, event: KeyEvent) -> Option<(Key, KeyEventKind, Modifiers)> {
+        remapper.process(event).map(|e| (e.key, e.kind, e.modifiers()))
+    }
+
+    #[test]
+    fn caps_lock_becomes_control() {
+        let mut r = Remapper::new().map(Key::CapsLock, Key::ControlLeft);
+        assert_eq!(out(&mut r, press(Key::CapsLock, Modifiers::NONE)), Some((Key::ControlLeft, KeyEventKind::Press, Modifiers::CTRL)));
+        assert_eq!(out(&mut r, press(Key::KeyC, Modifiers::NONE)), Some((Key::KeyC, KeyEventKind::Press, Modifiers::CTRL)));
+        assert_eq!(out(&mut r, release(Key::KeyC, Modifiers::NONE)), Some((Key::KeyC, KeyEventKind::Release, Modifiers::CTRL)));
+        assert_eq!(out(&mut r, release(Key::CapsLock, Modifiers::NONE)), Some((Key::ControlLeft, KeyEventKind::Release, Modifiers::NONE)));
+        assert_eq!(out(&mut r, press(Key::KeyC, Modifiers::NONE)), Some((Key::KeyC, KeyEventKind::Press, Modifiers::NONE)));
+    }
+
+    #[test]
+    fn swapped_modifiers_trade_flags() {
+        let mut r = Remapper::new().swap(Key::AltLeft, Key::MetaLeft);
+        assert_eq!(out(&mut r, press(Key::AltLeft, Modifiers::ALT)), Some((Key::MetaLeft, KeyEventKind::Press, Modifiers::SUPER)));
+        assert_eq!(out(&mut r, press(Key::KeyX, Modifiers::ALT)), Some((Key::KeyX, KeyEventKind::Press, Modifiers::SUPER)));
+        assert_eq!(out(&mut r, release(Key::AltLeft, Modifiers::NONE)), Some((Key::MetaLeft, KeyEventKind::Release, Modifiers::NONE)));
+        assert_eq!(out(&mut r, press(Key::MetaLeft, Modifiers::SUPER)), Some((Key::AltLeft, KeyEventKind::Press, Modifiers::ALT)));
+    }
+
+    #[test]
+    fn chords_need_exactly_their_modifiers() {
+        let mut r = Remapper::new().map_chord(Key::KeyH, Modifiers::ALT, Key::ArrowLeft, Modifiers::NONE);
+        assert_eq!(out(&mut r, press(Key::AltLeft, Modifiers::ALT)), Some((Key::AltLeft, KeyEventKind::Press, Modifiers::ALT)));
+        assert_eq!(out(&mut r, press(Key::KeyH, Modifiers::ALT)), Some((Key::ArrowLeft, KeyEventKind::Press, Modifiers::NONE)));
+        // The repeat and release follow the press, whatever is held by then.
+        let repeat = event(Key::KeyH, KeyEventKind::Repeat, Modifiers::ALT | Modifiers::SHIFT);
+        assert_eq!(out(&mut r, repeat), Some((Key::ArrowLeft, KeyEventKind::Repeat, Modifiers::NONE)));
+        assert_eq!(out(&mut r, release(Key::KeyH, Modifiers::NONE)), Some((Key::ArrowLeft, KeyEventKind::Release, Modifiers::NONE)));
+
+        let shifted = Modifiers::ALT | Modifiers::SHIFT;
+        assert_eq!(out(&mut r, press(Key::KeyH, shifted)), Some((Key::KeyH, KeyEventKind::Press, shifted)));
+        assert_eq!(out(&mut r, release(Key::AltLeft, Modifiers::NONE)), Some((Key::AltLeft, KeyEventKind::Release, Modifiers::NONE)));
+        assert_eq!(out(&mut r, press(Key::KeyJ, Modifiers::NONE)), Some((Key::KeyJ, KeyEventKind::Press, Modifiers::NONE)));
+    }
+
+    #[test]
+    fn layers_apply_while_held() {
+        let mut r = Remapper::new().map(Key::KeyH, Key::KeyX).layer("nav", Key::AltRight, [(Key::KeyH, Key::ArrowLeft)]);
+        assert_eq!(out(&mut r, press(Key::AltRight, Modifiers::ALT)), None);
+        assert_eq!(r.active_layer(), Some("nav"));
+        // The swallowed hold key does not leak its modifier.
+        assert_eq!(out(&mut r, press(Key::KeyH, Modifiers::ALT)), Some((Key::ArrowLeft, KeyEventKind::Press, Modifiers::NONE)));
+        assert_eq!(out(&mut r, press(Key::KeyJ, Modifiers::ALT)), Some((Key::KeyJ, KeyEventKind::Press, Modifiers::NONE)));
+
+        // Releasing the hold key first still releases what the mapped key was delivered as.
+        assert_eq!(out(&mut r, release(Key::AltRight, Modifiers::NONE)), None);
+        assert_eq!(r.active_layer(), None);
+        assert_eq!(out(&mut r, release(Key::KeyH, Modifiers::NONE)), Some((Key::ArrowLeft, KeyEventKind::Release, Modifiers::NONE)));
+        assert_eq!(out(&mut r, press(Key::KeyH, Modifiers::NONE)), Some((Key::KeyX, KeyEventKind::Press, Modifiers::NONE)));
+    }
+
+    #[test]
+    fn layers_need_releases() {
+        let mut r = Remapper::new().layer("nav", Key::AltRight, [(Key::KeyH, Key::ArrowLeft)]);
+        r.set_releases(false);
+        assert_eq!(out(&mut r, press(Key::AltRight, Modifiers::ALT)), Some((Key::AltRight, KeyEventKind::Press, Modifiers::ALT)));
+        assert_eq!(out(&mut r, press(Key::KeyH, Modifiers::NONE)), Some((Key::KeyH, KeyEventKind::Press, Modifiers::NONE)));
+        assert_eq!(r.active_layer(), None);
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn reads_toml() {
+        let mut r = Remapper::from_toml(
+            r#"
+            [keys]
+            CapsLock = "ControlLeft"
+
+            [chords]
+            "Alt+H" = "ArrowLeft"
+
+            [layers.fn]
+            hold = "AltRight"
+            keys = { J = "ArrowDown" }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(out(&mut r, press(Key::CapsLock, Modifiers::NONE)).map(|(k, ..)| k), Some(Key::ControlLeft));
+        assert_eq!(out(&mut r, release(Key::CapsLock, Modifiers::NONE)).map(|(k, ..)| k), Some(Key::ControlLeft));
+        assert_eq!(out(&mut r, press(Key::KeyH, Modifiers::ALT)).map(|(k, ..)| k), Some(Key::ArrowLeft));
+        assert_eq!(out(&mut r, press(Key::AltRight, Modifiers::ALT)), None);
+        assert_eq!(r.active_layer(), Some("fn"));
+        assert_eq!(out(&mut r, press(Key::KeyJ, Modifiers::NONE)).map(|(k, ..)| k), Some(Key::ArrowDown));
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn rejects_bad_toml() {
+        let error = |text: &str| {
+            let err = Remapper::from_toml(text).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{}", text);
+            err.to_string()
+        };
+        error("[keys");
+        assert_eq!(error("[macros]"), r#"unexpected remap section "macros""#);
+        assert_eq!(error("keys = 1"), r#"unexpected remap section "keys""#);
+        assert_eq!(error("[keys]\nCapsLock = 1"), r#""CapsLock" must map to a key name"#);
+        assert_eq!(error("[keys]\nCapsLock = \"Hyper\""), "Hyper".parse::<Key>().unwrap_err().to_string());
+        assert_eq!(error("[chords]\n\"Alt+H\" = 1"), r#""chords.KeyH" must map to a key name"#);
+        assert_eq!(error("[layers]\nfn = 1"), r#"layer "fn" must be a table"#);
+        assert_eq!(error("[layers.fn]\nkeys = {}"), r#"layer "fn" has no hold key"#);
+        assert_eq!(error("[layers.fn]\nhold = \"AltRight\"\nkeys = 1"), "layers.fn.keys must be a table");
+    }
+}
@@ -0,0 +1,258 @@
+use crate::platform::{self, Backend};
+use crate::testing::ScriptedBackend;
+use crate::{CancelHandle, Error, Key, KeyEvent, KeyEventKind, Modifiers, Remapper, Session, Timestamp};
+use std::collections::HashSet;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum PromptSink {
+    Stderr,
+    Stdout,
+    /// The controlling terminal, or stderr when there is none.
+    #[default]
+    Tty,
+    None,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ModifierPolicy {
+    /// Presses of Shift, Ctrl and Alt on their own are skipped.
+    #[default]
+    Ignore,
+    /// Presses of Shift, Ctrl and Alt are returned like any other key.
+    Report,
+}
+
+/// What happens when Ctrl+C is pressed during a wait.
+///
+/// The terminal is switched to raw mode while waiting, so Ctrl+C never raises SIGINT by itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum InterruptPolicy {
+    /// Ctrl+C is returned as `KeyC` with `ctrl` set.
+    Key,
+    /// The wait fails with `Error::Cancelled`.
+    #[default]
+    Error,
+    /// Terminal state is restored, then SIGINT is raised against the process.
+    Reraise,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct KeyRequest {
+    prompt: String,
+    sink: PromptSink,
+    clear_prompt: bool,
+    echo: bool,
+    allowed: Option<HashSet<Key>>,
+    timeout: Option<Duration>,
+    default: Option<Key>,
+    backend: Backend,
+    modifiers: ModifierPolicy,
+    interrupt: InterruptPolicy,
+    cancel: Option<CancelHandle>,
+    script: Option<ScriptedBackend>,
+    remapper: Option<Remapper>,
+}
+
+impl KeyRequest {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.prompt = prompt.into();
+        self
+    }
+
+    pub fn sink(mut self, sink: PromptSink) -> Self {
+        self.sink = sink;
+        self
+    }
+
+    /// Erases the prompt line once a key has been read instead of leaving it on screen.
+    pub fn clear_prompt(mut self, clear: bool) -> Self {
+        self.clear_prompt = clear;
+        self
+    }
+
+    /// Writes the name of the chosen key after the prompt.
+    pub fn echo(mut self, echo: bool) -> Self {
+        self.echo = echo;
+        self
+    }
+
+    /// Restricts the accepted keys; anything else is read and discarded.
+    pub fn allow(mut self, keys: impl IntoIterator<Item = Key>) -> Self {
+        self.allowed.get_or_insert_with(HashSet::new).extend(keys);
+        self
+    }
+
+    /// Gives up after `timeout`, returning the default key or a `TimedOut` error.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn default_key(mut self, key: Key) -> Self {
+        self.default = Some(key);
+        self
+    }
+
+    pub fn backend(mut self, backend: Backend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    pub fn modifiers(mut self, policy: ModifierPolicy) -> Self {
+        self.modifiers = policy;
+        self
+    }
+
+    pub fn interrupt(mut self, policy: InterruptPolicy) -> Self {
+        self.interrupt = policy;
+        self
+    }
+
+    /// Lets `handle` abort the wait from another thread.
+    pub fn cancel_on(mut self, handle: &CancelHandle) -> Self {
+        self.cancel = Some(handle.clone());
+        self
+    }
+
+    /// Reads keys from `script` instead of the backend; see `keypress::testing`.
+    pub fn script(mut self, script: &ScriptedBackend) -> Self {
+        self.script = Some(script.clone());
+        self
+    }
+
+    /// Rewrites keys before the request looks at them; see `Remapper`.
+    pub fn remap(mut self, remapper: Remapper) -> Self {
+        self.remapper = Some(remapper);
+        self
+    }
+
+    /// Opens a session with this request's backend, cancel handle and remapper.
+    pub fn session(&self) -> io::Result<Session> {
+        let mut session = match &self.script {
+            Some(script) => Session::scripted_with(script, self.cancel.as_ref()),
+            None => Session::open_with(self.backend, self.cancel.as_ref())?,
+        };
+        session.set_remapper(self.remapper.clone());
+        Ok(session)
+    }
+
+    pub fn wait(&self) -> io::Result<KeyEvent> {
+        self.write(&self.prompt)?;
+        let result = self.read();
+
+        let mut tail = String::new();
+        if self.clear_prompt {
+            tail.push_str("\r\x1b[2K");
+        }
+        if let (true, Ok(event)) = (self.echo, &result) {
+            tail.push_str(&format!("{}\n", event.key));
+        }
+        self.write(&tail)?;
+        result
+    }
+
+    fn read(&self) -> io::Result<KeyEvent> {
+        let mut session = self.session()?;
+        let deadline = self.timeout.map(|t| Instant::now() + t);
+        loop {
+            let remaining = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+            let event = match session.next_event(remaining)? {
+                Some(event) => event,
+                None => {
+                    return match self.default {
+                        Some(key) => Ok(KeyEvent {
+                            key,
+                            kind: KeyEventKind::Press,
+                            shift: false,
+                            ctrl: false,
+                            alt: false,
+                            meta: false,
+                            right: Modifiers::NONE,
+                            timestamp: Timestamp::now(),
+                            device: 0,
+                        }),
+                        None => Err(io::ErrorKind::TimedOut.into()),
+                    }
+                }
+            };
+            if event.kind != KeyEventKind::Press {
+                continue;
+            }
+            if is_interrupt(&event) {
+                match self.interrupt {
+                    InterruptPolicy::Key => {}
+                    InterruptPolicy::Error => return Err(Error::Cancelled.into()),
+                    InterruptPolicy::Reraise => {
+                        drop(session);
+                        platform::tty::raise_interrupt();
+                        return Err(io::ErrorKind::Interrupted.into());
+                    }
+                }
+            }
+            if self.modifiers == ModifierPolicy::Ignore && Modifiers::is_key(event.key) {
+                continue;
+            }
+            if let Some(allowed) = &self.allowed {
+                if !allowed.contains(&event.key) {
+                    continue;
+                }
+            }
+            return Ok(event);
+        }
+    }
+
+    fn write(&self, text: &str) -> io::Result<()> {
+        if text.is_empty() {
+            return Ok(());
+        }
+        match self.sink {
+            PromptSink::Stderr => {
+                let mut err = io::stderr();
+                err.write_all(text.as_bytes())?;
+                err.flush()
+            }
+            PromptSink::Stdout => {
+                let mut out = io::stdout();
+                out.write_all(text.as_bytes())?;
+                out.flush()
+            }
+            PromptSink::Tty => platform::tty::write_prompt(text),
+            PromptSink::None => Ok(()),
+        }
+    }
+}
+
+fn is_interrupt(event: &KeyEvent) -> bool {
+    event.key == Key::KeyC && event.ctrl && !event.shift && !event.alt && !event.meta
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wait(script: &str, policy: InterruptPolicy) -> io::Result<KeyEvent> {
+        let script = ScriptedBackend::parse(script).unwrap();
+        KeyRequest::new().sink(PromptSink::None).script(&script).interrupt(policy).wait()
+    }
+
+    #[test]
+    fn ctrl_c_cancels_by_default() {
+        let err = wait("ctrl+c a", InterruptPolicy::default()).unwrap_err();
+        assert_eq!(Error::of(&err), Some(Error::Cancelled));
+        let event = wait("ctrl+c a", InterruptPolicy::Key).unwrap();
+        assert_eq!((event.key, event.ctrl), (Key::KeyC, true));
+    }
+
+    #[test]
+    fn ctrl_c_with_other_modifiers_is_a_key() {
+        for script in ["ctrl+shift+c", "ctrl+alt+c", "ctrl+super+c"] {
+            assert_eq!(wait(script, InterruptPolicy::Error).unwrap().key, Key::KeyC, "{}", script);
+        }
+    }
+}
@@ -0,0 +1,458 @@
+use crate::encode::us_char;
+use crate::names::char_key;
+use crate::{KeyEvent, KeyEventKind, Modifiers, ParseShortcutError, Shortcut};
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// One step of a key sequence binding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Stroke {
+    Key(Shortcut),
+    /// Whatever key `SequenceMatcher::leader` is set to; `<leader>` in binding text.
+    Leader,
+}
+
+impl Stroke {
+    /// Parses a whitespace-separated sequence such as `C-x C-s`, `g g` or `<leader> f f`.
+    pub fn parse_sequence(text: &str) -> Result<Vec<Stroke>, ParseShortcutError> {
+        let strokes = text.split_whitespace().map(str::parse).collect::<Result<Vec<_>, _>>()?;
+        if strokes.is_empty() {
+            return Err(ParseShortcutError::new(format!("empty key sequence {:?}", text)));
+        }
+        Ok(strokes)
+    }
+}
+
+/// Reads one stroke: `<leader>`, an Emacs-style `C-x`/`M-x`/`s-x` (Control, Meta as Alt,
+/// Super; `S-` is Shift), a single character where an upper-case letter means Shift (`G`), a
+/// `<...>`-wrapped form of these, or anything `Shortcut` accepts.
+impl FromStr for Stroke {
+    type Err = ParseShortcutError;
+
+    fn from_str(text: &str) -> Result<Stroke, ParseShortcutError> {
+        let inner = match text.strip_prefix('<').and_then(|t| t.strip_suffix('>')) {
+            Some(inner) if !inner.is_empty() => inner,
+            _ => text,
+        };
+        if inner.eq_ignore_ascii_case("leader") {
+            return Ok(Stroke::Leader);
+        }
+
+        let mut mods = Modifiers::NONE;
+        let mut rest = inner;
+        while let Some((m, tail)) = emacs_prefix(rest) {
+            mods |= m;
+            rest = tail;
+        }
+        let mut chars = rest.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            if let Some((key, shifted)) = char_key(c) {
+                if shifted {
+                    mods |= Modifiers::SHIFT;
+                }
+                return Ok(Stroke::Key(Shortcut::new(key, mods)));
+            }
+        }
+        let shortcut: Shortcut = rest.parse()?;
+        Ok(Stroke::Key(Shortcut { modifiers: shortcut.modifiers | mods, ..shortcut }))
+    }
+}
+
+fn emacs_prefix(text: &str) -> Option<(Modifiers, &str)> {
+    let m = match text.get(..2)? {
+        "C-" => Modifiers::CTRL,
+        "M-" | "A-" => Modifiers::ALT,
+        "S-" => Modifiers::SHIFT,
+        "s-" | "D-" => Modifiers::SUPER,
+        _ => return None,
+    };
+    Some((m, &text[2..])).filter(|(_, rest)| !rest.is_empty())
+}
+
+/// Writes the form `FromStr` reads back: the character for plain and shifted printable keys
+/// (`g`, `G`, `?`), `<leader>`, and the `Shortcut` form otherwise.
+impl fmt::Display for Stroke {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stroke::Key(shortcut) if shortcut.left.is_empty() && shortcut.right.is_empty() => {
+                let shift = shortcut.modifiers == Modifiers::SHIFT;
+                match us_char(shortcut.key, shift) {
+                    Some(c) if (shift || shortcut.modifiers.is_empty()) && c != ' ' && char_key(c) == Some((shortcut.key, shift)) => {
+                        write!(f, "{}", c)
+                    }
+                    _ => shortcut.fmt(f),
+                }
+            }
+            Stroke::Key(shortcut) => shortcut.fmt(f),
+            Stroke::Leader => f.write_str("<leader>"),
+        }
+    }
+}
+
+/// What a `SequenceMatcher` made of the keys so far.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SequenceStep<A> {
+    /// The keys so far begin at least one binding and more are needed, or the event was a
+    /// release outside a sequence.
+    Pending,
+    /// A binding completed.
+    Matched(A),
+    /// The keys begin no binding; they were held back and should now be handled as ordinary
+    /// input, in order.
+    NoMatch(Vec<KeyEvent>),
+}
+
+/// Matches multi-stroke bindings such as `C-x C-s`, `g g` or `<leader> f f`, Emacs and Vim
+/// style.
+///
+/// Bindings are kept in a prefix trie. When one binding is a prefix of another (`g` and
+/// `g g`), the shorter one fires once the next key rules the longer out or the inter-key
+/// timeout passes, like Vim's `timeoutlen`. Modifier keys and releases never advance a
+/// sequence; they are held back with it so `NoMatch` replays the input exactly. Releases
+/// outside a sequence are dropped, so the keys that ended one do not come back as `NoMatch`.
+///
+/// Feed it events with `feed` and call `advance` when `deadline` passes, or use
+/// `Session::next_sequence`. A key can settle two things at once, the shorter binding it cut
+/// off and itself; the second result is returned by the next call to `feed` or `advance`.
+///
+/// ```no_run
+/// use keypress::{Backend, SequenceMatcher, SequenceStep, Session};
+///
+/// let mut session = Session::open(Backend::Auto)?;
+/// let mut keys = SequenceMatcher::new()
+///     .leader("Space".parse()?)
+///     .bind("C-x C-s", "save")?
+///     .bind("g g", "top")?
+///     .bind("<leader> f f", "find file")?;
+/// match session.next_sequence(&mut keys)? {
+///     SequenceStep::Matched(action) => println!("{}", action),
+///     SequenceStep::NoMatch(events) => println!("unbound: {:?}", events),
+///     SequenceStep::Pending => unreachable!(),
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct SequenceMatcher<A> {
+    nodes: Vec<Node<A>>,
+    leader: Option<Shortcut>,
+    timeout: Duration,
+    /// Current trie node; 0 is the root.
+    node: usize,
+    held: Vec<KeyEvent>,
+    /// Time of the last key that advanced the sequence.
+    last: Option<Duration>,
+    ready: VecDeque<SequenceStep<A>>,
+}
+
+#[derive(Debug, Clone)]
+struct Node<A> {
+    children: Vec<(Stroke, usize)>,
+    action: Option<A>,
+}
+
+impl<A> Node<A> {
+    fn new() -> Self {
+        Node { children: Vec::new(), action: None }
+    }
+}
+
+impl<A> Default for SequenceMatcher<A> {
+    fn default() -> Self {
+        SequenceMatcher {
+            nodes: vec![Node::new()],
+            leader: None,
+            timeout: Duration::from_secs(1),
+            node: 0,
+            held: Vec::new(),
+            last: None,
+            ready: VecDeque::new(),
+        }
+    }
+}
+
+impl<A: Clone> SequenceMatcher<A> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Binds a sequence in the `Stroke::parse_sequence` syntax; a later binding of the same
+    /// sequence replaces the earlier one.
+    pub fn bind(self, keys: &str, action: A) -> Result<Self, ParseShortcutError> {
+        Ok(self.bind_strokes(Stroke::parse_sequence(keys)?, action))
+    }
+
+    pub fn bind_strokes(mut self, strokes: impl IntoIterator<Item = Stroke>, action: A) -> Self {
+        let mut node = 0;
+        for stroke in strokes {
+            node = match self.nodes[node].children.iter().find(|(s, _)| *s == stroke) {
+                Some(&(_, child)) => child,
+                None => {
+                    self.nodes.push(Node::new());
+                    let child = self.nodes.len() - 1;
+                    self.nodes[node].children.push((stroke, child));
+                    child
+                }
+            };
+        }
+        self.nodes[node].action = Some(action);
+        self
+    }
+
+    /// The key `<leader>` stands for. Bindings using it never match while it is unset.
+    pub fn leader(mut self, key: Shortcut) -> Self {
+        self.leader = Some(key);
+        self
+    }
+
+    /// How long to wait for the next key of a sequence; 1s by default.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Whether a sequence is under way.
+    pub fn is_pending(&self) -> bool {
+        self.node != 0
+    }
+
+    /// The monotonic time at which a pending sequence times out, or zero when a result is
+    /// already waiting.
+    pub fn deadline(&self) -> Option<Duration> {
+        if !self.ready.is_empty() {
+            return Some(Duration::ZERO);
+        }
+        self.last.filter(|_| self.is_pending()).and_then(|last| last.checked_add(self.timeout))
+    }
+
+    /// Abandons a pending sequence without reporting it.
+    pub fn reset(&mut self) {
+        self.node = 0;
+        self.held.clear();
+        self.last = None;
+        self.ready.clear();
+    }
+
+    pub fn feed(&mut self, event: &KeyEvent) -> SequenceStep<A> {
+        self.push(event);
+        self.pop()
+    }
+
+    /// Settles a pending sequence whose timeout has passed by the monotonic time `now`.
+    pub fn advance(&mut self, now: Duration) -> SequenceStep<A> {
+        self.expire(now);
+        self.pop()
+    }
+
+    fn pop(&mut self) -> SequenceStep<A> {
+        self.take_ready().unwrap_or(SequenceStep::Pending)
+    }
+
+    pub(crate) fn take_ready(&mut self) -> Option<SequenceStep<A>> {
+        self.ready.pop_front()
+    }
+
+    pub(crate) fn expire(&mut self, now: Duration) {
+        let timed_out = self.last.and_then(|last| last.checked_add(self.timeout)).is_some_and(|d| d <= now);
+        if self.is_pending() && timed_out {
+            self.settle();
+        }
+    }
+
+    pub(crate) fn push(&mut self, event: &KeyEvent) {
+        self.expire(event.timestamp.monotonic);
+        let passive = event.kind == KeyEventKind::Release || Modifiers::is_key(event.key);
+        if passive {
+            if self.is_pending() {
+                self.held.push(*event);
+            } else if event.kind != KeyEventKind::Release {
+                self.ready.push_back(SequenceStep::NoMatch(vec![*event]));
+            }
+            return;
+        }
+
+        if let Some(child) = self.child(self.node, event) {
+            self.held.push(*event);
+            self.enter(child, event.timestamp.monotonic);
+            return;
+        }
+        if self.is_pending() && self.nodes[self.node].action.is_none() && self.child(0, event).is_none() {
+            // An unbound sequence and a key starting nothing: give them back together.
+            self.held.push(*event);
+            self.settle();
+            return;
+        }
+        if self.is_pending() {
+            // The key ends the sequence: settle what came before, then start over with it.
+            self.settle();
+            if let Some(child) = self.child(0, event) {
+                self.held.push(*event);
+                self.enter(child, event.timestamp.monotonic);
+                return;
+            }
+        }
+        self.ready.push_back(SequenceStep::NoMatch(vec![*event]));
+    }
+
+    /// Moves to `node`, reporting its action at once if nothing longer could follow.
+    fn enter(&mut self, node: usize, time: Duration) {
+        self.node = node;
+        self.last = Some(time);
+        if self.nodes[node].children.is_empty() {
+            self.settle();
+        }
+    }
+
+    /// Ends the current sequence: its action if it has one, otherwise the held keys.
+    fn settle(&mut self) {
+        let held = std::mem::take(&mut self.held);
+        let step = match &self.nodes[self.node].action {
+            Some(action) => SequenceStep::Matched(action.clone()),
+            None => SequenceStep::NoMatch(held),
+        };
+        self.ready.push_back(step);
+        self.node = 0;
+        self.last = None;
+    }
+
+    fn child(&self, node: usize, event: &KeyEvent) -> Option<usize> {
+        self.nodes[node].children.iter().find_map(|&(stroke, child)| {
+            let shortcut = match stroke {
+                Stroke::Key(shortcut) => shortcut,
+                Stroke::Leader => self.leader?,
+            };
+            Some(child).filter(|_| shortcut.matches(event))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Key, Timestamp};
+
+    const MS: Duration = Duration::from_millis(1);
+
+    fn event(key: Key, kind: KeyEventKind, mods: Modifiers, ms: u32) -> KeyEvent {
+        KeyEvent {
+            key,
+            kind,
+            shift: mods.contains(Modifiers::SHIFT),
+            ctrl: mods.contains(Modifiers::CTRL),
+            alt: mods.contains(Modifiers::ALT),
+            meta: mods.contains(Modifiers::SUPER),
+            right: Modifiers::NONE,
+            timestamp: Timestamp { monotonic: MS * ms, wall: None, latency: None },
+            device: 0,
+        }
+    }
+
+    fn press(key: Key, ms: u32) -> KeyEvent {
+        event(key, KeyEventKind::Press, Modifiers::NONE, ms)
+    }
+
+    fn release(key: Key, ms: u32) -> KeyEvent {
+        event(key, KeyEventKind::Release, Modifiers::NONE, ms)
+    }
+
+    fn matcher() -> SequenceMatcher<&'static str> {
+        SequenceMatcher::new()
+            .leader(Shortcut::new(Key::Space, Modifiers::NONE))
+            .bind("C-x C-s", "save")
+            .and_then(|m| m.bind("C-x C-c", "quit"))
+            .and_then(|m| m.bind("g", "go"))
+            .and_then(|m| m.bind("g g", "top"))
+            .and_then(|m| m.bind("<leader> f f", "find file"))
+            .unwrap()
+    }
+
+    #[test]
+    fn parses_strokes() {
+        let ctrl_x = Stroke::Key(Shortcut::new(Key::KeyX, Modifiers::CTRL));
+        assert_eq!("C-x".parse(), Ok(ctrl_x));
+        assert_eq!("<C-x>".parse(), Ok(ctrl_x));
+        assert_eq!("Ctrl+X".parse(), Ok(ctrl_x));
+        assert_eq!("G".parse(), Ok(Stroke::Key(Shortcut::new(Key::KeyG, Modifiers::SHIFT))));
+        assert_eq!("M-S-x".parse(), Ok(Stroke::Key(Shortcut::new(Key::KeyX, Modifiers::ALT | Modifiers::SHIFT))));
+        assert_eq!("<Leader>".parse(), Ok(Stroke::Leader));
+        for text in ["g", "G", "?", "<leader>", "Ctrl+X", "Space", "Alt+Enter"] {
+            assert_eq!(text.parse::<Stroke>().unwrap().to_string(), text);
+        }
+        assert!(Stroke::parse_sequence("  ").is_err());
+        assert!(Stroke::parse_sequence("C-x C-Bogus").is_err());
+    }
+
+    #[test]
+    fn follows_the_trie() {
+        let mut m = matcher();
+        let ctrl = |key, ms| event(key, KeyEventKind::Press, Modifiers::CTRL, ms);
+        assert_eq!(m.feed(&ctrl(Key::KeyX, 0)), SequenceStep::Pending);
+        assert!(m.is_pending());
+        assert_eq!(m.deadline(), Some(MS * 1000));
+        assert_eq!(m.feed(&ctrl(Key::KeyS, 100)), SequenceStep::Matched("save"));
+        assert!(!m.is_pending());
+
+        assert_eq!(m.feed(&press(Key::Space, 200)), SequenceStep::Pending);
+        assert_eq!(m.feed(&press(Key::KeyF, 300)), SequenceStep::Pending);
+        assert_eq!(m.feed(&press(Key::KeyF, 400)), SequenceStep::Matched("find file"));
+    }
+
+    #[test]
+    fn shorter_binding_fires_when_the_next_key_rules_out_the_longer() {
+        let mut m = matcher();
+        assert_eq!(m.feed(&press(Key::KeyG, 0)), SequenceStep::Pending);
+        assert_eq!(m.feed(&press(Key::KeyG, 100)), SequenceStep::Matched("top"));
+
+        assert_eq!(m.feed(&press(Key::KeyG, 1000)), SequenceStep::Pending);
+        // X cuts `g` off and starts nothing; it comes back on the next call.
+        assert_eq!(m.feed(&press(Key::KeyX, 1100)), SequenceStep::Matched("go"));
+        assert_eq!(m.advance(MS * 1100), SequenceStep::NoMatch(vec![press(Key::KeyX, 1100)]));
+    }
+
+    #[test]
+    fn times_out() {
+        let mut m = matcher().timeout(MS * 500);
+        assert_eq!(m.feed(&press(Key::KeyG, 0)), SequenceStep::Pending);
+        assert_eq!(m.advance(MS * 499), SequenceStep::Pending);
+        assert_eq!(m.advance(MS * 500), SequenceStep::Matched("go"));
+
+        // An unbound prefix times out into the keys it held back, modifiers and releases too.
+        let held = [
+            press(Key::ControlLeft, 1000),
+            event(Key::KeyX, KeyEventKind::Press, Modifiers::CTRL, 1010),
+            event(Key::KeyX, KeyEventKind::Release, Modifiers::CTRL, 1050),
+            release(Key::ControlLeft, 1060),
+        ];
+        assert_eq!(m.feed(&held[0]), SequenceStep::NoMatch(vec![held[0]]));
+        for e in &held[1..] {
+            assert_eq!(m.feed(e), SequenceStep::Pending);
+        }
+        assert_eq!(m.deadline(), Some(MS * 1510));
+        assert_eq!(m.feed(&press(Key::KeyA, 2000)), SequenceStep::NoMatch(held[1..].to_vec()));
+        assert_eq!(m.advance(MS * 2000), SequenceStep::NoMatch(vec![press(Key::KeyA, 2000)]));
+    }
+
+    #[test]
+    fn unbound_keys_come_back() {
+        let mut m = matcher();
+        assert_eq!(m.feed(&press(Key::KeyQ, 0)), SequenceStep::NoMatch(vec![press(Key::KeyQ, 0)]));
+        let ctrl_x = event(Key::KeyX, KeyEventKind::Press, Modifiers::CTRL, 10);
+        assert_eq!(m.feed(&ctrl_x), SequenceStep::Pending);
+        assert_eq!(m.feed(&press(Key::KeyQ, 20)), SequenceStep::NoMatch(vec![ctrl_x, press(Key::KeyQ, 20)]));
+        // The leader means nothing until it is set.
+        let mut unset = SequenceMatcher::new().bind("<leader> f", "find").unwrap();
+        assert_eq!(unset.feed(&press(Key::Space, 0)), SequenceStep::NoMatch(vec![press(Key::Space, 0)]));
+    }
+
+    #[test]
+    fn drops_releases_outside_a_sequence() {
+        let mut m = matcher();
+        assert_eq!(m.feed(&release(Key::KeyQ, 0)), SequenceStep::Pending);
+        assert_eq!(m.feed(&press(Key::KeyG, 10)), SequenceStep::Pending);
+        assert_eq!(m.feed(&press(Key::KeyG, 20)), SequenceStep::Matched("top"));
+        assert_eq!(m.feed(&release(Key::KeyG, 30)), SequenceStep::Pending);
+        assert!(!m.is_pending());
+        assert_eq!(m.deadline(), None);
+    }
+}
@@ -0,0 +1,286 @@
+use crate::platform::{self, Backend, Source};
+use crate::record::{RecordFormat, Recorder};
+use crate::testing::ScriptedBackend;
+use crate::time::monotonic_now;
+use crate::{
+    CancelHandle, ComboDetector, ComboEvent, Error, GestureDetector, GestureEvent, Key, KeyEvent, Remapper, SequenceMatcher,
+    SequenceStep,
+};
+use std::fmt;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+/// An open backend that keeps devices, modifier state and terminal mode across reads.
+///
+/// On Linux the session exposes a descriptor that becomes readable when keys are waiting,
+/// so it can be registered with an external epoll/mio loop and serviced with `drain_ready`.
+pub struct Session {
+    source: Box<dyn Source>,
+    recorder: Option<Recorder<Box<dyn Write>>>,
+    remapper: Option<Remapper>,
+}
+
+impl Session {
+    pub fn open(backend: Backend) -> io::Result<Session> {
+        Self::open_with(backend, None)
+    }
+
+    pub(crate) fn open_with(backend: Backend, cancel: Option<&CancelHandle>) -> io::Result<Session> {
+        Ok(Self::from_source(platform::open(backend, cancel)?))
+    }
+
+    /// Reads the given evdev devices (`/dev/input/event*`) instead of every keyboard.
+    #[cfg(target_os = "linux")]
+    pub fn open_evdev<P: AsRef<std::path::Path>>(paths: &[P]) -> io::Result<Session> {
+        let paths: Vec<&std::path::Path> = paths.iter().map(|p| p.as_ref()).collect();
+        Ok(Self::from_source(platform::open_evdev(&paths, None)?))
+    }
+
+    /// Takes the devices exclusively, so their keys reach only this session until it is dropped.
+    ///
+    /// Only the evdev backend supports this; elsewhere it fails with `Error::Unsupported`.
+    pub fn grab(&mut self) -> io::Result<()> {
+        self.source.grab()
+    }
+
+    /// A session that replays `script` instead of reading a keyboard; see `keypress::testing`.
+    pub fn scripted(script: &ScriptedBackend) -> Session {
+        Self::scripted_with(script, None)
+    }
+
+    pub(crate) fn scripted_with(script: &ScriptedBackend, cancel: Option<&CancelHandle>) -> Session {
+        Self::from_source(Box::new(script.source(cancel.cloned())))
+    }
+
+    fn from_source(source: Box<dyn Source>) -> Session {
+        Session { source, recorder: None, remapper: None }
+    }
+
+    /// Records every event this session returns from now on; see `keypress::record`.
+    ///
+    /// Replaces any recording already in progress, flushing it first.
+    pub fn record_to(&mut self, writer: impl Write + 'static, format: RecordFormat) -> io::Result<()> {
+        self.stop_recording()?;
+        self.recorder = Some(Recorder::new(Box::new(writer) as Box<dyn Write>, format, &self.devices())?);
+        Ok(())
+    }
+
+    /// Passes every event through `remapper` before returning it; `None` removes it.
+    ///
+    /// Recordings keep the events as the backend delivered them, before remapping.
+    pub fn set_remapper(&mut self, remapper: Option<Remapper>) {
+        self.remapper = remapper.map(|mut remapper| {
+            remapper.set_releases(self.reports_releases());
+            remapper
+        });
+    }
+
+    pub fn remapper(&self) -> Option<&Remapper> {
+        self.remapper.as_ref()
+    }
+
+    /// Ends the current recording, flushing what was written.
+    pub fn stop_recording(&mut self) -> io::Result<()> {
+        match self.recorder.take() {
+            Some(mut recorder) => recorder.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// Blocks until the next key event, including releases and repeats where the backend reports them.
+    pub fn next_key(&mut self) -> io::Result<KeyEvent> {
+        loop {
+            if let Some(event) = self.next_event(None)? {
+                return Ok(event);
+            }
+        }
+    }
+
+    /// Names of the devices this session reads, indexed by `KeyEvent::device`.
+    pub fn devices(&self) -> Vec<String> {
+        self.source.devices()
+    }
+
+    /// Whether this backend reports key releases. The terminal backend only sees presses.
+    pub fn reports_releases(&self) -> bool {
+        self.source.reports_releases()
+    }
+
+    /// Keys held right now according to the OS; `Error::Unsupported` on the terminal backend.
+    pub fn pressed_keys(&self) -> io::Result<Vec<Key>> {
+        self.source.pressed_keys()
+    }
+
+    /// Waits at most `timeout`; `Ok(None)` means no key arrived in time.
+    pub fn next_key_timeout(&mut self, timeout: Duration) -> io::Result<Option<KeyEvent>> {
+        self.next_event(Some(timeout))
+    }
+
+    /// Processes whatever input is already buffered and returns the resulting events without blocking.
+    pub fn drain_ready(&mut self) -> io::Result<Vec<KeyEvent>> {
+        let events = self.source.drain_ready()?;
+        if let Some(recorder) = &mut self.recorder {
+            for event in &events {
+                recorder.record(event)?;
+            }
+        }
+        Ok(match &mut self.remapper {
+            Some(remapper) => events.into_iter().filter_map(|event| remapper.process(event)).collect(),
+            None => events,
+        })
+    }
+
+    pub(crate) fn next_event(&mut self, timeout: Option<Duration>) -> io::Result<Option<KeyEvent>> {
+        let deadline = timeout.map(|t| Instant::now() + t);
+        loop {
+            let remaining = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+            let event = match self.source.next_event(remaining)? {
+                Some(event) => event,
+                None => return Ok(None),
+            };
+            if let Some(recorder) = &mut self.recorder {
+                recorder.record(&event)?;
+            }
+            match &mut self.remapper {
+                Some(remapper) => {
+                    if let Some(event) = remapper.process(event) {
+                        return Ok(Some(event));
+                    }
+                }
+                None => return Ok(Some(event)),
+            }
+        }
+    }
+
+    /// Reads events until `detector` classifies a gesture; see `GestureDetector`.
+    ///
+    /// Events that complete no gesture are consumed. Needs key releases, so it fails with
+    /// `Error::Unsupported` on the terminal backend.
+    pub fn next_gesture(&mut self, detector: &mut GestureDetector) -> io::Result<GestureEvent> {
+        if !self.reports_releases() {
+            return Err(Error::Unsupported.into());
+        }
+        self.drive(detector)
+    }
+
+    /// Reads events until `detector` has a combo or a passed-on key to report; see
+    /// `ComboDetector`.
+    ///
+    /// Needs key releases, so it fails with `Error::Unsupported` on the terminal backend.
+    pub fn next_combo<A: Clone>(&mut self, detector: &mut ComboDetector<A>) -> io::Result<ComboEvent<A>> {
+        if !self.reports_releases() {
+            return Err(Error::Unsupported.into());
+        }
+        self.drive(detector)
+    }
+
+    /// Reads events until `matcher` completes a binding or gives up on the keys so far; never
+    /// returns `SequenceStep::Pending`. See `SequenceMatcher`.
+    pub fn next_sequence<A: Clone>(&mut self, matcher: &mut SequenceMatcher<A>) -> io::Result<SequenceStep<A>> {
+        self.drive(matcher)
+    }
+
+    /// Feeds events to `detector` until it has something to report, expiring its timeout
+    /// whenever the read waits past `deadline`.
+    fn drive<D: Detector>(&mut self, detector: &mut D) -> io::Result<D::Output> {
+        loop {
+            if let Some(output) = detector.pop() {
+                return Ok(output);
+            }
+            let timeout = detector.deadline().map(|deadline| deadline.saturating_sub(monotonic_now()));
+            match self.next_event(timeout)? {
+                Some(event) => detector.push(&event),
+                None => detector.expire(monotonic_now()),
+            }
+        }
+    }
+
+    /// The descriptor to poll for readiness, if the backend has one.
+    #[cfg(unix)]
+    pub fn raw_fd(&self) -> Option<std::os::fd::RawFd> {
+        self.source.raw_fd()
+    }
+}
+
+/// What `Session::drive` needs from the gesture, combo and sequence detectors.
+trait Detector {
+    type Output;
+
+    fn pop(&mut self) -> Option<Self::Output>;
+    fn deadline(&self) -> Option<Duration>;
+    fn push(&mut self, event: &KeyEvent);
+    fn expire(&mut self, now: Duration);
+}
+
+impl Detector for GestureDetector {
+    type Output = GestureEvent;
+
+    fn pop(&mut self) -> Option<GestureEvent> {
+        GestureDetector::pop(self)
+    }
+
+    fn deadline(&self) -> Option<Duration> {
+        GestureDetector::deadline(self)
+    }
+
+    fn push(&mut self, event: &KeyEvent) {
+        GestureDetector::push(self, event)
+    }
+
+    fn expire(&mut self, now: Duration) {
+        GestureDetector::expire(self, now)
+    }
+}
+
+impl<A: Clone> Detector for ComboDetector<A> {
+    type Output = ComboEvent<A>;
+
+    fn pop(&mut self) -> Option<ComboEvent<A>> {
+        ComboDetector::pop(self)
+    }
+
+    fn deadline(&self) -> Option<Duration> {
+        ComboDetector::deadline(self)
+    }
+
+    fn push(&mut self, event: &KeyEvent) {
+        ComboDetector::push(self, event)
+    }
+
+    fn expire(&mut self, now: Duration) {
+        ComboDetector::expire(self, now)
+    }
+}
+
+/// Pending steps are never queued, so a popped step always completes or abandons keys.
+impl<A: Clone> Detector for SequenceMatcher<A> {
+    type Output = SequenceStep<A>;
+
+    fn pop(&mut self) -> Option<SequenceStep<A>> {
+        self.take_ready()
+    }
+
+    fn deadline(&self) -> Option<Duration> {
+        SequenceMatcher::deadline(self)
+    }
+
+    fn push(&mut self, event: &KeyEvent) {
+        SequenceMatcher::push(self, event)
+    }
+
+    fn expire(&mut self, now: Duration) {
+        SequenceMatcher::expire(self, now)
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        let _ = self.stop_recording();
+    }
+}
+
+impl fmt::Debug for Session {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Session").finish_non_exhaustive()
+    }
+}
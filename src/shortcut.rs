@@ -0,0 +1,512 @@
+use crate::names::{eq_ignore_case, key_named, modifier_named};
+use crate::{Key, KeyEvent, KeyEventKind, Modifiers};
+use std::fmt;
+use std::str::FromStr;
+
+/// A key plus the modifiers held with it, such as Ctrl+Shift+K.
+///
+/// Parses the usual spellings: `Ctrl+Shift+K`, `ctrl-alt-del`, `Super+Enter`, `⌘⇧K`,
+/// `Ctrl+?` (Shift implied by the character) and side-specific modifiers like `RCtrl+Enter`;
+/// a modifier pinned to both sides is an error, since no key press could match it. `Display` writes the canonical form `FromStr` reads back: modifiers in the order Ctrl, Alt,
+/// Shift, Super, joined with `+`. The `shortcut!` and `keys!` macros check shortcut text at
+/// compile time.
+///
+/// ```
+/// use keypress::{Key, Modifiers, Shortcut};
+///
+/// let save: Shortcut = "ctrl-s".parse()?;
+/// assert_eq!(save, Shortcut::new(Key::KeyS, Modifiers::CTRL));
+/// assert_eq!("⌘⇧K".parse::<Shortcut>()?.to_string(), "Shift+Super+K");
+/// # Ok::<(), keypress::ParseShortcutError>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Shortcut {
+    pub key: Key,
+    /// Every modifier that must be held, including the side-specific ones.
+    pub modifiers: Modifiers,
+    /// Modifiers that must be held with the left-hand key.
+    pub left: Modifiers,
+    /// Modifiers that must be held with the right-hand key.
+    pub right: Modifiers,
+}
+
+/// A `Shortcut` constant from shortcut text, checked at compile time: a misspelled key or
+/// modifier name fails the build.
+///
+/// Takes anything `Shortcut::from_str` accepts, as a string literal or a `&str` constant; only
+/// a literal is quoted in the build error. Patterns cannot call macros, so to match an
+/// event against shortcuts, bind them to constants and match `KeyEvent::shortcut` against
+/// those.
+///
+/// ```
+/// use keypress::{keys, shortcut, KeyEvent, Shortcut};
+///
+/// const SAVE: Shortcut = keys!(Ctrl + S);
+/// const QUIT: Shortcut = shortcut!("Ctrl+Q");
+///
+/// fn handle(event: &KeyEvent) {
+///     match event.shortcut() {
+///         SAVE => println!("save"),
+///         QUIT => println!("quit"),
+///         _ => {}
+///     }
+/// }
+/// assert_eq!(shortcut!("Ctrl+Alt+Del"), "Ctrl+Alt+Delete".parse().unwrap());
+///
+/// const REDO_TEXT: &str = "Ctrl+Shift+Z";
+/// assert_eq!(shortcut!(REDO_TEXT).to_string(), REDO_TEXT);
+/// ```
+///
+/// ```compile_fail
+/// let typo = keypress::shortcut!("Ctrl+Shfit+K");
+/// ```
+#[macro_export]
+macro_rules! shortcut {
+    // Text `concat!` can quote in the error: a literal, or the `concat!` that `keys!` builds.
+    (@quoted $text:expr) => {{
+        const SHORTCUT: $crate::Shortcut = match $crate::Shortcut::parse_const($text) {
+            ::core::option::Option::Some(shortcut) => shortcut,
+            ::core::option::Option::None => ::core::panic!(::core::concat!("invalid shortcut: ", $text)),
+        };
+        SHORTCUT
+    }};
+    ($text:literal) => {
+        $crate::shortcut!(@quoted $text)
+    };
+    ($text:expr) => {{
+        const SHORTCUT: $crate::Shortcut = match $crate::Shortcut::parse_const($text) {
+            ::core::option::Option::Some(shortcut) => shortcut,
+            ::core::option::Option::None => ::core::panic!("invalid shortcut"),
+        };
+        SHORTCUT
+    }};
+}
+
+/// `shortcut!` written as tokens: `keys!(Ctrl + Shift + K)`, `keys!(Alt + F4)`,
+/// `keys!(Ctrl + /)`.
+///
+/// Each part is one token, so names with a `-` in them need `shortcut!`.
+///
+/// ```
+/// use keypress::{keys, Key, Modifiers, Shortcut};
+///
+/// assert_eq!(keys!(Ctrl + Shift + K), Shortcut::new(Key::KeyK, Modifiers::CTRL | Modifiers::SHIFT));
+/// ```
+///
+/// ```compile_fail
+/// let typo = keypress::keys!(Ctrl + Entr);
+/// ```
+#[macro_export]
+macro_rules! keys {
+    ($first:tt $(+ $rest:tt)*) => {
+        $crate::shortcut!(@quoted ::core::concat!(::core::stringify!($first) $(, "+", ::core::stringify!($rest))*))
+    };
+}
+
+/// How strictly `Shortcut::matches_with` compares modifiers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct MatchOptions {
+    pub extra: ExtraModifiers,
+    pub sides: Sides,
+}
+
+/// What to do with modifiers held beyond those the shortcut names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ExtraModifiers {
+    /// Any extra modifier fails the match: Ctrl+K does not match Ctrl+Shift+K.
+    #[default]
+    Reject,
+    /// An extra Shift is allowed, for layouts that need Shift to reach the key.
+    IgnoreShift,
+    /// Extra modifiers are allowed.
+    Ignore,
+}
+
+/// Whether side-specific modifiers (`RCtrl`, `LeftAlt`) are checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Sides {
+    /// Pinned modifiers must be held on their side. A modifier held on both sides counts as
+    /// held on the right; backends that cannot tell sides apart (the terminal) report every
+    /// modifier as held on the left.
+    #[default]
+    Pinned,
+    /// Either side will do.
+    Any,
+}
+
+/// Returned when a string does not describe a shortcut.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseShortcutError(String);
+
+impl fmt::Display for ParseShortcutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ParseShortcutError {}
+
+impl ParseShortcutError {
+    pub(crate) fn new(msg: String) -> ParseShortcutError {
+        ParseShortcutError(msg)
+    }
+}
+
+impl Shortcut {
+    pub const fn new(key: Key, modifiers: Modifiers) -> Shortcut {
+        Shortcut { key, modifiers, left: Modifiers::NONE, right: Modifiers::NONE }
+    }
+
+    /// Requires `modifiers` to be held with their left-hand keys, replacing any right-hand pin.
+    pub const fn left(self, modifiers: Modifiers) -> Shortcut {
+        Shortcut {
+            modifiers: self.modifiers.union(modifiers),
+            left: self.left.union(modifiers),
+            right: self.right.difference(modifiers),
+            ..self
+        }
+    }
+
+    /// Requires `modifiers` to be held with their right-hand keys, replacing any left-hand pin.
+    pub const fn right(self, modifiers: Modifiers) -> Shortcut {
+        Shortcut {
+            modifiers: self.modifiers.union(modifiers),
+            left: self.left.difference(modifiers),
+            right: self.right.union(modifiers),
+            ..self
+        }
+    }
+
+    /// Whether `event` presses (or repeats) this shortcut, with the default `MatchOptions`:
+    /// no extra modifiers and pinned sides checked.
+    pub fn matches(&self, event: &KeyEvent) -> bool {
+        self.matches_with(event, MatchOptions::default())
+    }
+
+    pub fn matches_with(&self, event: &KeyEvent, options: MatchOptions) -> bool {
+        if event.key != self.key || event.kind == KeyEventKind::Release {
+            return false;
+        }
+        let held = own_modifier_removed(event.key, event.modifiers());
+        if !held.contains(self.modifiers) {
+            return false;
+        }
+        let extra = held.difference(self.modifiers);
+        let extra_ok = match options.extra {
+            ExtraModifiers::Reject => extra.is_empty(),
+            ExtraModifiers::IgnoreShift => extra.difference(Modifiers::SHIFT).is_empty(),
+            ExtraModifiers::Ignore => true,
+        };
+        let sides_ok = match options.sides {
+            Sides::Pinned => event.right.contains(self.right) && Modifiers::from_bits(event.right.bits() & self.left.bits()).is_empty(),
+            Sides::Any => true,
+        };
+        extra_ok && sides_ok
+    }
+}
+
+/// The shortcut an event presses: its key and modifier flags, without sides.
+///
+/// Normalized so it can be compared, or matched against constants, directly. A modifier key's
+/// own flag is left out, so pressing ShiftLeft gives plain `ShiftLeft`.
+impl From<&KeyEvent> for Shortcut {
+    fn from(event: &KeyEvent) -> Shortcut {
+        Shortcut::new(event.key, own_modifier_removed(event.key, event.modifiers()))
+    }
+}
+
+impl KeyEvent {
+    /// The shortcut this event presses; see `Shortcut`'s `From<&KeyEvent>`.
+    pub fn shortcut(&self) -> Shortcut {
+        Shortcut::from(self)
+    }
+}
+
+fn own_modifier_removed(key: Key, mods: Modifiers) -> Modifiers {
+    match Modifiers::of_key(key) {
+        Some((own, _)) => mods.difference(own),
+        None => mods,
+    }
+}
+
+const ORDER: [(Modifiers, &str); 4] =
+    [(Modifiers::CTRL, "Ctrl"), (Modifiers::ALT, "Alt"), (Modifiers::SHIFT, "Shift"), (Modifiers::SUPER, "Super")];
+
+impl fmt::Display for Shortcut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (m, name) in ORDER {
+            if self.right.contains(m) {
+                write!(f, "Right{}+", name)?;
+            } else if self.left.contains(m) {
+                write!(f, "Left{}+", name)?;
+            } else if self.modifiers.contains(m) {
+                write!(f, "{}+", name)?;
+            }
+        }
+        f.write_str(&key_name(self.key))
+    }
+}
+
+/// The shortest name `Key::from_str` reads back: `K`, `1`, `F5`, `Enter`.
+pub(crate) fn key_name(key: Key) -> String {
+    let name = key.to_string();
+    match name.strip_prefix("Key").or_else(|| name.strip_prefix("Digit")) {
+        Some(short) => short.to_string(),
+        None => name,
+    }
+}
+
+impl FromStr for Shortcut {
+    type Err = ParseShortcutError;
+
+    fn from_str(text: &str) -> Result<Shortcut, ParseShortcutError> {
+        let error = |msg: String| ParseShortcutError(format!("{} in shortcut {:?}", msg, text));
+        match parse(text.as_bytes()) {
+            Ok(shortcut) => Ok(shortcut),
+            Err(Fault::NoKey) => Err(error("no key".into())),
+            Err(Fault::Modifier(start, end)) => Err(error(format!("unknown modifier {:?}", &text[start..end]))),
+            Err(Fault::Key(start, end)) => Err(error(format!("unknown key {:?}", &text[start..end]))),
+            Err(Fault::BothSides(start, end)) => Err(error(format!("{:?} pins a modifier already pinned to the other side", &text[start..end]))),
+        }
+    }
+}
+
+impl Shortcut {
+    /// `FromStr` as a `const fn`, for shortcuts in constants; `None` where `FromStr` fails.
+    /// The `shortcut!` and `keys!` macros build on it.
+    pub const fn parse_const(text: &str) -> Option<Shortcut> {
+        match parse(text.as_bytes()) {
+            Ok(shortcut) => Some(shortcut),
+            Err(_) => None,
+        }
+    }
+}
+
+/// Why `parse` failed, with the byte range of the offending name.
+enum Fault {
+    NoKey,
+    Modifier(usize, usize),
+    Key(usize, usize),
+    /// A modifier pinned to one side after the other, which no key press can match.
+    BothSides(usize, usize),
+}
+
+/// The parser behind `FromStr` and `parse_const`, written with byte loops so it runs in
+/// const contexts.
+const fn parse(text: &[u8]) -> Result<Shortcut, Fault> {
+    let (mut start, mut end) = trim(text, 0, text.len());
+    let mut shortcut = Shortcut::new(Key::Space, Modifiers::NONE);
+
+    // Leading macOS symbols: ⌘⇧K, ⌃⌥Delete.
+    'symbols: loop {
+        let mut i = 0;
+        while i < MAC_SYMBOLS.len() {
+            let (symbol, m) = MAC_SYMBOLS[i];
+            if has_prefix(text, start, end, symbol.as_bytes()) {
+                shortcut.modifiers = shortcut.modifiers.union(m);
+                start += symbol.len();
+                while start < end && (is_separator(text[start]) || text[start] == b' ') {
+                    start += 1;
+                }
+                continue 'symbols;
+            }
+            i += 1;
+        }
+        break;
+    }
+    if start == end {
+        return Err(Fault::NoKey);
+    }
+
+    // The key comes last; a separator character right after a separator is the key itself.
+    let (mods_end, key_start) = if end - start >= 2 && is_separator(text[end - 1]) && is_separator(text[end - 2]) {
+        (end - 2, end - 1)
+    } else {
+        let mut i = end - 1;
+        while i > start && !is_separator(text[i]) {
+            i -= 1;
+        }
+        if is_separator(text[i]) && i + 1 < end {
+            (i, i + 1)
+        } else {
+            (start, start)
+        }
+    };
+
+    let mut token = start;
+    while token < mods_end {
+        let mut token_end = token;
+        while token_end < mods_end && !is_separator(text[token_end]) {
+            token_end += 1;
+        }
+        let (a, b) = trim(text, token, token_end);
+        if a < b {
+            shortcut = match modifier(text, a, b) {
+                Some((m, Side::Either)) => Shortcut { modifiers: shortcut.modifiers.union(m), ..shortcut },
+                Some((m, Side::Left)) if shortcut.right.contains(m) => return Err(Fault::BothSides(a, b)),
+                Some((m, Side::Right)) if shortcut.left.contains(m) => return Err(Fault::BothSides(a, b)),
+                Some((m, Side::Left)) => shortcut.left(m),
+                Some((m, Side::Right)) => shortcut.right(m),
+                None => return Err(Fault::Modifier(a, b)),
+            };
+        }
+        token = token_end + 1;
+    }
+
+    (start, end) = trim(text, key_start, end);
+    if let Some((key, shifted)) = key_named(text, start, end) {
+        shortcut.key = key;
+        if shifted {
+            shortcut.modifiers = shortcut.modifiers.union(Modifiers::SHIFT);
+        }
+        return Ok(shortcut);
+    }
+    Err(Fault::Key(key_start, end))
+}
+
+#[derive(Clone, Copy)]
+enum Side {
+    Either,
+    Left,
+    Right,
+}
+
+/// A modifier name in `text[start..end]`, optionally pinned to a side: `ctrl`, `RCtrl`,
+/// `LeftAlt`, `ShiftRight`.
+const fn modifier(text: &[u8], start: usize, end: usize) -> Option<(Modifiers, Side)> {
+    if let Some(m) = modifier_named(text, start, end) {
+        return Some((m, Side::Either));
+    }
+    let prefixes: [(&str, Side); 4] = [("left", Side::Left), ("right", Side::Right), ("l", Side::Left), ("r", Side::Right)];
+    let mut i = 0;
+    while i < prefixes.len() {
+        let (prefix, side) = prefixes[i];
+        if has_prefix(text, start, end, prefix.as_bytes()) {
+            if let Some(m) = modifier_named(text, start + prefix.len(), end) {
+                return Some((m, side));
+            }
+        }
+        i += 1;
+    }
+    let suffixes: [(&str, Side); 2] = [("left", Side::Left), ("right", Side::Right)];
+    let mut i = 0;
+    while i < suffixes.len() {
+        let (suffix, side) = suffixes[i];
+        if end - start > suffix.len() && eq_ignore_case(text, end - suffix.len(), end, suffix.as_bytes()) {
+            if let Some(m) = modifier_named(text, start, end - suffix.len()) {
+                return Some((m, side));
+            }
+        }
+        i += 1;
+    }
+    None
+}
+
+const fn is_separator(byte: u8) -> bool {
+    byte == b'+' || byte == b'-'
+}
+
+const fn trim(text: &[u8], mut start: usize, mut end: usize) -> (usize, usize) {
+    while start < end && text[start].is_ascii_whitespace() {
+        start += 1;
+    }
+    while end > start && text[end - 1].is_ascii_whitespace() {
+        end -= 1;
+    }
+    (start, end)
+}
+
+const fn has_prefix(text: &[u8], start: usize, end: usize, prefix: &[u8]) -> bool {
+    end - start >= prefix.len() && eq_ignore_case(text, start, start + prefix.len(), prefix)
+}
+
+const MAC_SYMBOLS: [(&str, Modifiers); 4] =
+    [("⌘", Modifiers::SUPER), ("⇧", Modifiers::SHIFT), ("⌃", Modifiers::CTRL), ("⌥", Modifiers::ALT)];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Timestamp;
+
+    fn parse(text: &str) -> Shortcut {
+        text.parse().unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    fn press(key: Key, mods: Modifiers, right: Modifiers) -> KeyEvent {
+        KeyEvent {
+            key,
+            kind: KeyEventKind::Press,
+            shift: mods.contains(Modifiers::SHIFT),
+            ctrl: mods.contains(Modifiers::CTRL),
+            alt: mods.contains(Modifiers::ALT),
+            meta: mods.contains(Modifiers::SUPER),
+            right,
+            timestamp: Timestamp::default(),
+            device: 0,
+        }
+    }
+
+    #[test]
+    fn parses_common_spellings() {
+        assert_eq!(parse("ctrl-alt-del"), Shortcut::new(Key::Delete, Modifiers::CTRL | Modifiers::ALT));
+        assert_eq!(parse("Ctrl+Alt+Delete"), parse("ctrl-alt-del"));
+        assert_eq!(parse("⌘⇧K"), Shortcut::new(Key::KeyK, Modifiers::SUPER | Modifiers::SHIFT));
+        assert_eq!(parse("⌃⌥ Delete"), Shortcut::new(Key::Delete, Modifiers::CTRL | Modifiers::ALT));
+        assert_eq!(parse("Ctrl+?"), Shortcut::new(Key::Slash, Modifiers::CTRL | Modifiers::SHIFT));
+        assert_eq!(parse("ctrl++"), Shortcut::new(Key::Equal, Modifiers::CTRL | Modifiers::SHIFT));
+        assert_eq!(parse("Ctrl+-"), Shortcut::new(Key::Minus, Modifiers::CTRL));
+        assert_eq!(parse(" Super + Enter "), Shortcut::new(Key::Enter, Modifiers::SUPER));
+    }
+
+    #[test]
+    fn parses_sides() {
+        let rctrl_k = Shortcut::new(Key::KeyK, Modifiers::NONE).right(Modifiers::CTRL);
+        assert_eq!(parse("RCtrl+K"), rctrl_k);
+        assert_eq!(parse("RightCtrl+K"), rctrl_k);
+        assert_eq!(parse("ctrlright+k"), rctrl_k);
+        assert_eq!(rctrl_k.modifiers, Modifiers::CTRL);
+        assert_eq!(parse("LAlt+Shift+Tab"), Shortcut::new(Key::Tab, Modifiers::SHIFT).left(Modifiers::ALT));
+    }
+
+    #[test]
+    fn sides_round_trip() {
+        for text in ["LeftCtrl+K", "RightCtrl+K", "LeftCtrl+RightAlt+Shift+F5", "Ctrl+RightShift+LeftSuper+Enter"] {
+            let shortcut = parse(text);
+            assert_eq!(shortcut.to_string(), text);
+            assert_eq!(parse(&shortcut.to_string()), shortcut);
+        }
+        assert_eq!(parse("lctrl-k").to_string(), "LeftCtrl+K");
+    }
+
+    #[test]
+    fn rejects_pins_to_both_sides() {
+        let err = "LCtrl+RCtrl+K".parse::<Shortcut>().unwrap_err();
+        assert_eq!(err.to_string(), r#""RCtrl" pins a modifier already pinned to the other side in shortcut "LCtrl+RCtrl+K""#);
+        assert!("RightAlt+AltLeft+X".parse::<Shortcut>().is_err());
+        assert!(Shortcut::parse_const("LCtrl+RCtrl+K").is_none());
+        // The same side twice, or a plain modifier next to a pinned one, is fine.
+        assert_eq!(parse("LCtrl+LCtrl+K"), parse("LCtrl+K"));
+        assert_eq!(parse("Ctrl+RCtrl+K"), parse("RCtrl+K"));
+    }
+
+    #[test]
+    fn later_pins_replace_the_other_side() {
+        let shortcut = Shortcut::new(Key::KeyK, Modifiers::NONE).left(Modifiers::CTRL).right(Modifiers::CTRL);
+        assert_eq!(shortcut, parse("RCtrl+K"));
+        let right = press(Key::KeyK, Modifiers::CTRL, Modifiers::CTRL);
+        assert!(shortcut.matches(&right));
+        assert!(!shortcut.matches(&press(Key::KeyK, Modifiers::CTRL, Modifiers::NONE)));
+        assert!(parse("LCtrl+K").matches(&press(Key::KeyK, Modifiers::CTRL, Modifiers::NONE)));
+        assert!(!parse("LCtrl+K").matches(&right));
+        assert!(parse("LCtrl+K").matches_with(&right, MatchOptions { sides: Sides::Any, ..MatchOptions::default() }));
+    }
+
+    #[test]
+    fn rejects_unknown_names() {
+        let error = |text: &str| text.parse::<Shortcut>().unwrap_err().to_string();
+        assert_eq!(error("Ctrl+Shfit+K"), r#"unknown modifier "Shfit" in shortcut "Ctrl+Shfit+K""#);
+        assert_eq!(error("Ctrl+Entr"), r#"unknown key "Entr" in shortcut "Ctrl+Entr""#);
+        assert_eq!(error("⌘"), r#"no key in shortcut "⌘""#);
+        assert_eq!(error(""), r#"no key in shortcut """#);
+    }
+}
@@ -0,0 +1,63 @@
+use crate::platform::{self, AsyncSource, Backend};
+use crate::{KeyEvent, KeyEventKind};
+use futures_core::Stream;
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// An asynchronous stream of key events, for use inside a tokio runtime.
+///
+/// Raw mode is held for the lifetime of the stream and Ctrl+C is delivered as a key.
+/// Dropping the stream, or a pending `next_key` future, ends the wait and restores the terminal.
+pub struct KeyStream {
+    source: Box<dyn AsyncSource>,
+}
+
+impl KeyStream {
+    pub fn open(backend: Backend) -> io::Result<Self> {
+        Ok(KeyStream { source: platform::open_async(backend)? })
+    }
+
+    pub async fn next_key(&mut self) -> io::Result<KeyEvent> {
+        NextKey { stream: self }.await
+    }
+}
+
+impl Stream for KeyStream {
+    type Item = io::Result<KeyEvent>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.source.poll_next_event(cx).map(Some)
+    }
+}
+
+impl fmt::Debug for KeyStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyStream").finish_non_exhaustive()
+    }
+}
+
+struct NextKey<'a> {
+    stream: &'a mut KeyStream,
+}
+
+impl Future for NextKey<'_> {
+    type Output = io::Result<KeyEvent>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.stream.source.poll_next_event(cx)
+    }
+}
+
+/// Waits for a single key press without blocking the runtime.
+pub async fn next_key() -> io::Result<KeyEvent> {
+    let mut stream = KeyStream::open(Backend::Auto)?;
+    loop {
+        let event = stream.next_key().await?;
+        if event.kind == KeyEventKind::Press {
+            return Ok(event);
+        }
+    }
+}
@@ -0,0 +1,599 @@
+//! Scripted key input for tests and CI.
+//!
+//! A `ScriptedBackend` replays a fixed list of key events instead of reading a keyboard. Hand
+//! it to `Session::scripted` or `KeyRequest::script`, or set `KEYPRESS_SCRIPT` to drive every
+//! session the process opens:
+//!
+//! ```text
+//! KEYPRESS_SCRIPT='y wait:200ms ctrl+c' ./my-installer
+//! KEYPRESS_SCRIPT=@answers.keys ./my-installer
+//! ```
+//!
+//! # Script format
+//!
+//! Tokens are separated by whitespace; lines starting with `#` are comments.
+//!
+//! - `a`, `Enter`, `F5`, `ArrowUp`: a press of that key (any name `Key::from_str` accepts).
+//!   A single shifted character such as `A` or `?` also sets `shift`.
+//! - `ctrl+c`, `ctrl+shift+Tab`, `alt+x`, `super+Enter`: a press with those modifiers set.
+//! - `ShiftLeft:down`, `ShiftLeft:up`, `a:repeat`: an explicit press, release or repeat.
+//!   Modifier keys held with `:down` apply to every event until their `:up`.
+//! - `"some text"`: a press for every character, typed on a US layout; `\"` and `\\` escape.
+//! - `wait:150ms`, `wait:2s`: a delay before the next event.
+
+use crate::names::{char_key, modifier_named};
+use crate::platform::Source;
+use crate::record::{self, Recording, Timing};
+use crate::{CancelHandle, Key, KeyEvent, KeyEventKind, Modifiers, Timestamp};
+use std::collections::VecDeque;
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+#[cfg(feature = "async")]
+use std::task::{Context, Poll};
+
+/// The variable `Session::open`, `KeyRequest` and `Listener` consult before opening a device.
+///
+/// It holds a script, or `@` followed by the path of a script file. It is read once, when the
+/// first session opens; every later session continues the same script where the previous one
+/// stopped, so several prompts in a row each get the next key.
+pub const SCRIPT_ENV: &str = "KEYPRESS_SCRIPT";
+
+/// Key input replayed from a script instead of a keyboard.
+///
+/// Clones share the same script, so a test can keep one handle and check with
+/// `assert_consumed` after the code under test has read from another. Once the script runs
+/// out, reads fail with `io::ErrorKind::UnexpectedEof` rather than blocking forever.
+#[derive(Clone, Default)]
+pub struct ScriptedBackend {
+    script: Arc<Mutex<Script>>,
+}
+
+#[derive(Default)]
+struct Script {
+    steps: VecDeque<Step>,
+    /// When the front step becomes due; set once a reader starts waiting for it.
+    due: Option<Instant>,
+    /// Delay to attach to the next pushed step.
+    delay: Duration,
+    held: Vec<Key>,
+    releases: bool,
+    devices: Vec<String>,
+}
+
+struct Step {
+    delay: Duration,
+    event: KeyEvent,
+    /// Whether the event gets its timestamp when it is delivered.
+    stamp: bool,
+}
+
+impl ScriptedBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replays `events` back to back, keeping their timestamps.
+    pub fn from_events(events: impl IntoIterator<Item = KeyEvent>) -> Self {
+        let script = Self::new();
+        for event in events {
+            script.add(event, false);
+        }
+        script
+    }
+
+    /// Parses a script in the format described in the module documentation.
+    pub fn parse(script: &str) -> io::Result<Self> {
+        let backend = Self::new();
+        let mut mods = HeldModifiers::default();
+        for (n, line) in script.lines().enumerate() {
+            if line.trim_start().starts_with('#') {
+                continue;
+            }
+            let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidInput, format!("line {}: {}", n + 1, msg));
+            for token in tokenize(line).map_err(invalid)? {
+                match token {
+                    Token::Text(text) => {
+                        for c in text.chars() {
+                            let (key, shift) = char_key(c).ok_or_else(|| invalid(format!("cannot type {:?}", c)))?;
+                            backend.add(mods.event(key, KeyEventKind::Press, [shift, false, false, false]), true);
+                        }
+                    }
+                    Token::Word(word) => {
+                        if let Some(delay) = word.strip_prefix("wait:") {
+                            let delay = parse_duration(delay).ok_or_else(|| invalid(format!("bad delay {:?}", word)))?;
+                            backend.script.lock().unwrap().delay += delay;
+                            continue;
+                        }
+                        let event = mods.parse(&word).map_err(invalid)?;
+                        backend.add(event, true);
+                    }
+                }
+            }
+        }
+        Ok(backend)
+    }
+
+    /// Reads a script file, or a session recording or (on Linux) an `evemu-record` capture,
+    /// which play back with their original timing.
+    pub fn from_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        let bytes = std::fs::read(path)?;
+        if record::is_recording(&bytes) {
+            return Ok(Recording::read(&bytes[..])?.playback(Timing::Original));
+        }
+        #[cfg(target_os = "linux")]
+        if bytes.starts_with(b"# EVEMU") {
+            return Ok(Recording::from_evemu(&bytes[..])?.playback(Timing::Original));
+        }
+        let text = String::from_utf8(bytes).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        Self::parse(&text)
+    }
+
+    /// The script named by `KEYPRESS_SCRIPT`, if the variable is set and not empty.
+    ///
+    /// Each call reads the script afresh; the sessions the crate opens share a single copy.
+    pub fn from_env() -> io::Result<Option<Self>> {
+        match std::env::var(SCRIPT_ENV) {
+            Ok(value) if value.is_empty() => Ok(None),
+            Ok(value) => match value.strip_prefix('@') {
+                Some(path) => Self::from_file(path).map(Some),
+                None => Self::parse(&value).map(Some),
+            },
+            Err(std::env::VarError::NotPresent) => Ok(None),
+            Err(err) => Err(io::Error::new(io::ErrorKind::InvalidData, err)),
+        }
+    }
+
+    /// Appends an event, keeping its timestamp.
+    pub fn event(self, event: KeyEvent) -> Self {
+        self.add(event, false);
+        self
+    }
+
+    /// Appends a press of `key` without modifiers.
+    pub fn key(self, key: Key) -> Self {
+        self.add(HeldModifiers::default().event(key, KeyEventKind::Press, [false; 4]), true);
+        self
+    }
+
+    /// Delays the next appended event by `delay`, measured from when a reader starts waiting for it.
+    pub fn delay(self, delay: Duration) -> Self {
+        self.script.lock().unwrap().delay += delay;
+        self
+    }
+
+    /// How many events have not been read yet.
+    pub fn remaining(&self) -> usize {
+        self.script.lock().unwrap().steps.len()
+    }
+
+    pub fn is_consumed(&self) -> bool {
+        self.remaining() == 0
+    }
+
+    /// Panics, listing the unread events, unless the whole script has been read.
+    #[track_caller]
+    pub fn assert_consumed(&self) {
+        let script = self.script.lock().unwrap();
+        if !script.steps.is_empty() {
+            let left: Vec<String> = script.steps.iter().map(|step| describe(&step.event)).collect();
+            panic!("{} scripted key event(s) not consumed: {}", left.len(), left.join(" "));
+        }
+    }
+
+    fn add(&self, event: KeyEvent, stamp: bool) {
+        let mut script = self.script.lock().unwrap();
+        let delay = std::mem::take(&mut script.delay);
+        script.push(delay, event, stamp);
+    }
+
+    /// Appends `event` after `delay`; a recorded event is restamped when it is delivered.
+    pub(crate) fn push_recorded(&self, delay: Duration, event: KeyEvent) {
+        let mut script = self.script.lock().unwrap();
+        let delay = std::mem::take(&mut script.delay) + delay;
+        script.push(delay, event, true);
+    }
+
+    pub(crate) fn set_devices(&self, devices: Vec<String>) {
+        self.script.lock().unwrap().devices = devices;
+    }
+
+    pub(crate) fn source(&self, cancel: Option<CancelHandle>) -> ScriptSource {
+        ScriptSource {
+            script: self.clone(),
+            cancel,
+            #[cfg(feature = "async")]
+            timer: None,
+        }
+    }
+}
+
+/// The `KEYPRESS_SCRIPT` script shared by the whole process; see `SCRIPT_ENV`.
+pub(crate) fn env_script() -> io::Result<Option<ScriptedBackend>> {
+    static SCRIPT: Mutex<Option<Option<ScriptedBackend>>> = Mutex::new(None);
+    let mut script = SCRIPT.lock().unwrap_or_else(|e| e.into_inner());
+    if script.is_none() {
+        *script = Some(ScriptedBackend::from_env()?);
+    }
+    Ok(script.clone().flatten())
+}
+
+impl fmt::Debug for ScriptedBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ScriptedBackend").field("remaining", &self.remaining()).finish()
+    }
+}
+
+impl Script {
+    fn push(&mut self, delay: Duration, event: KeyEvent, stamp: bool) {
+        self.releases |= event.kind == KeyEventKind::Release;
+        self.steps.push_back(Step { delay, event, stamp });
+    }
+
+    /// When the front step is due, or `None` when the script is exhausted.
+    fn due(&mut self) -> Option<Instant> {
+        let delay = self.steps.front()?.delay;
+        Some(*self.due.get_or_insert_with(|| Instant::now() + delay))
+    }
+
+    fn pop(&mut self) -> Option<KeyEvent> {
+        let step = self.steps.pop_front()?;
+        self.due = None;
+        let mut event = step.event;
+        if step.stamp {
+            event.timestamp = Timestamp::now();
+        }
+        match event.kind {
+            KeyEventKind::Press if !self.held.contains(&event.key) => self.held.push(event.key),
+            KeyEventKind::Release => self.held.retain(|&key| key != event.key),
+            _ => {}
+        }
+        Some(event)
+    }
+}
+
+pub(crate) struct ScriptSource {
+    script: ScriptedBackend,
+    cancel: Option<CancelHandle>,
+    #[cfg(feature = "async")]
+    timer: Option<Instant>,
+}
+
+impl ScriptSource {
+    /// Sleeps until `until`, returning early with an error if the wait is cancelled.
+    fn sleep_until(&self, until: Instant) -> io::Result<()> {
+        match &self.cancel {
+            #[cfg(unix)]
+            Some(cancel) => {
+                use std::os::fd::AsRawFd;
+
+                crate::platform::poll::wait_readable(&[cancel.waker().as_raw_fd()], Some(until))?;
+                cancel.check()
+            }
+            #[cfg(not(unix))]
+            Some(cancel) => {
+                const SLICE: Duration = Duration::from_millis(50);
+                loop {
+                    cancel.check()?;
+                    let remaining = until.saturating_duration_since(Instant::now());
+                    if remaining.is_zero() {
+                        return Ok(());
+                    }
+                    std::thread::sleep(remaining.min(SLICE));
+                }
+            }
+            None => {
+                std::thread::sleep(until.saturating_duration_since(Instant::now()));
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Source for ScriptSource {
+    fn devices(&self) -> Vec<String> {
+        let script = self.script.script.lock().unwrap();
+        if script.devices.is_empty() {
+            vec!["script".to_string()]
+        } else {
+            script.devices.clone()
+        }
+    }
+
+    fn next_event(&mut self, timeout: Option<Duration>) -> io::Result<Option<KeyEvent>> {
+        let deadline = timeout.map(|t| Instant::now() + t);
+        loop {
+            if let Some(cancel) = &self.cancel {
+                cancel.check()?;
+            }
+            let due = {
+                let mut script = self.script.script.lock().unwrap();
+                match script.due() {
+                    Some(due) if due <= Instant::now() => return Ok(script.pop()),
+                    Some(due) => due,
+                    None => return Err(exhausted()),
+                }
+            };
+            match deadline {
+                Some(deadline) if deadline < due => {
+                    self.sleep_until(deadline)?;
+                    return Ok(None);
+                }
+                _ => self.sleep_until(due)?,
+            }
+        }
+    }
+
+    /// Returns the events that are due, without failing once the script is exhausted.
+    fn drain_ready(&mut self) -> io::Result<Vec<KeyEvent>> {
+        if let Some(cancel) = &self.cancel {
+            cancel.check()?;
+        }
+        let mut script = self.script.script.lock().unwrap();
+        let mut events = Vec::new();
+        while script.due().is_some_and(|due| due <= Instant::now()) {
+            events.extend(script.pop());
+        }
+        Ok(events)
+    }
+
+    /// True when the script contains releases; otherwise every key is a bare press.
+    fn reports_releases(&self) -> bool {
+        self.script.script.lock().unwrap().releases
+    }
+
+    /// Keys pressed and not yet released by the events read so far.
+    fn pressed_keys(&self) -> io::Result<Vec<Key>> {
+        Ok(self.script.script.lock().unwrap().held.clone())
+    }
+}
+
+#[cfg(feature = "async")]
+impl crate::platform::AsyncSource for ScriptSource {
+    fn poll_next_event(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<KeyEvent>> {
+        let mut script = self.script.script.lock().unwrap();
+        match script.due() {
+            Some(due) if due <= Instant::now() => Poll::Ready(Ok(script.pop().expect("due step"))),
+            Some(due) => {
+                if self.timer != Some(due) {
+                    self.timer = Some(due);
+                    let waker = cx.waker().clone();
+                    std::thread::spawn(move || {
+                        std::thread::sleep(due.saturating_duration_since(Instant::now()));
+                        waker.wake();
+                    });
+                }
+                Poll::Pending
+            }
+            None => Poll::Ready(Err(exhausted())),
+        }
+    }
+}
+
+fn exhausted() -> io::Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, "key script exhausted")
+}
+
+fn describe(event: &KeyEvent) -> String {
+    let mut text = String::new();
+    for (on, name) in [(event.ctrl, "ctrl+"), (event.alt, "alt+"), (event.shift, "shift+"), (event.meta, "super+")] {
+        if on {
+            text.push_str(name);
+        }
+    }
+    text.push_str(&event.key.to_string());
+    match event.kind {
+        KeyEventKind::Press => {}
+        KeyEventKind::Repeat => text.push_str(":repeat"),
+        KeyEventKind::Release => text.push_str(":up"),
+    }
+    text
+}
+
+/// Modifier keys held down by `:down` tokens earlier in the script.
+#[derive(Default)]
+struct HeldModifiers {
+    held: Vec<Key>,
+}
+
+impl HeldModifiers {
+    fn parse(&mut self, word: &str) -> Result<KeyEvent, String> {
+        let (chord, kind) = match word.rsplit_once(':') {
+            Some((chord, "down" | "press")) if !chord.is_empty() => (chord, KeyEventKind::Press),
+            Some((chord, "up" | "release")) if !chord.is_empty() => (chord, KeyEventKind::Release),
+            Some((chord, "repeat")) if !chord.is_empty() => (chord, KeyEventKind::Repeat),
+            _ => (word, KeyEventKind::Press),
+        };
+
+        let (mods, name) = if chord.len() > 1 && chord.ends_with("++") {
+            (&chord[..chord.len() - 2], "+")
+        } else {
+            match chord.rsplit_once('+') {
+                Some((mods, name)) if !name.is_empty() => (mods, name),
+                _ => ("", chord),
+            }
+        };
+        let (mut shift, mut ctrl, mut alt, mut meta) = (false, false, false, false);
+        for m in mods.split('+').filter(|m| !m.is_empty()) {
+            let modifier = modifier_named(m.as_bytes(), 0, m.len()).ok_or_else(|| format!("unknown modifier {:?} in {:?}", m, word))?;
+            shift |= modifier.contains(Modifiers::SHIFT);
+            ctrl |= modifier.contains(Modifiers::CTRL);
+            alt |= modifier.contains(Modifiers::ALT);
+            meta |= modifier.contains(Modifiers::SUPER);
+        }
+
+        let mut chars = name.chars();
+        let key = match (chars.next(), chars.next()) {
+            (Some(c), None) => match char_key(c) {
+                Some((key, shifted)) => {
+                    shift |= shifted;
+                    key
+                }
+                None => return Err(format!("unknown key {:?}", name)),
+            },
+            _ => name.parse::<Key>().map_err(|err| err.to_string())?,
+        };
+
+        if Modifiers::is_key(key) {
+            match kind {
+                KeyEventKind::Press if !self.held.contains(&key) => self.held.push(key),
+                KeyEventKind::Release => self.held.retain(|&k| k != key),
+                _ => {}
+            }
+        }
+        Ok(self.event(key, kind, [shift, ctrl, alt, meta]))
+    }
+
+    /// An event with the given shift, ctrl, alt and super flags plus those of held modifier keys.
+    fn event(&self, key: Key, kind: KeyEventKind, [shift, ctrl, alt, meta]: [bool; 4]) -> KeyEvent {
+        let (held, right) = Modifiers::held(self.held.iter().copied());
+        KeyEvent {
+            key,
+            kind,
+            shift: shift || held.contains(Modifiers::SHIFT),
+            ctrl: ctrl || held.contains(Modifiers::CTRL),
+            alt: alt || held.contains(Modifiers::ALT),
+            meta: meta || held.contains(Modifiers::SUPER),
+            right,
+            timestamp: Timestamp::default(),
+            device: 0,
+        }
+    }
+}
+
+
+enum Token {
+    Word(String),
+    Text(String),
+}
+
+fn tokenize(line: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' {
+            chars.next();
+            let mut text = String::new();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => match chars.next() {
+                        Some(escaped) => text.push(escaped),
+                        None => return Err("unterminated escape".to_string()),
+                    },
+                    Some(c) => text.push(c),
+                    None => return Err("unterminated quoted text".to_string()),
+                }
+            }
+            tokens.push(Token::Text(text));
+        } else {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+            tokens.push(Token::Word(word));
+        }
+    }
+    Ok(tokens)
+}
+
+fn parse_duration(text: &str) -> Option<Duration> {
+    if let Some(ms) = text.strip_suffix("ms") {
+        ms.parse().ok().map(Duration::from_millis)
+    } else if let Some(secs) = text.strip_suffix('s') {
+        secs.parse::<f64>().ok().and_then(|s| Duration::try_from_secs_f64(s).ok())
+    } else {
+        text.parse().ok().map(Duration::from_millis)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn steps(script: &str) -> Vec<(Duration, KeyEvent)> {
+        let backend = ScriptedBackend::parse(script).unwrap();
+        let script = backend.script.lock().unwrap();
+        script.steps.iter().map(|step| (step.delay, step.event)).collect()
+    }
+
+    fn events(script: &str) -> Vec<String> {
+        steps(script).iter().map(|(_, event)| describe(event)).collect()
+    }
+
+    fn error(script: &str) -> String {
+        let err = ScriptedBackend::parse(script).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        err.to_string()
+    }
+
+    #[test]
+    fn parses_keys_chords_and_text() {
+        assert_eq!(events("a Enter F5 ArrowUp"), ["KeyA", "Enter", "F5", "ArrowUp"]);
+        assert_eq!(events("A ?"), ["shift+KeyA", "shift+Slash"]);
+        assert_eq!(events("ctrl+c ctrl+shift+Tab alt+x super+Enter"), ["ctrl+KeyC", "ctrl+shift+Tab", "alt+KeyX", "super+Enter"]);
+        assert_eq!(events("ctrl++"), ["ctrl+shift+Equal"]);
+        assert_eq!(events(r#""Hi \"x\"""#), ["shift+KeyH", "KeyI", "Space", "shift+Quote", "KeyX", "shift+Quote"]);
+        assert_eq!(events("# a comment\n  # another\nb"), ["KeyB"]);
+    }
+
+    #[test]
+    fn held_modifiers_apply_until_released() {
+        assert_eq!(
+            events("ShiftLeft:down a ShiftLeft:up a"),
+            ["shift+ShiftLeft", "shift+KeyA", "ShiftLeft:up", "KeyA"]
+        );
+        assert_eq!(events("MetaLeft:down x MetaLeft:release x"), ["super+MetaLeft", "super+KeyX", "MetaLeft:up", "KeyX"]);
+        assert_eq!(events("a:repeat b:press b:up"), ["KeyA:repeat", "KeyB", "KeyB:up"]);
+
+        let right = steps("ControlRight:down k");
+        assert!(right[1].1.ctrl && right[1].1.right.contains(Modifiers::CTRL));
+    }
+
+    #[test]
+    fn waits_delay_the_next_event() {
+        let delays: Vec<Duration> = steps("a wait:150ms b wait:2s wait:1.5s c wait:20 d").iter().map(|(delay, _)| *delay).collect();
+        assert_eq!(
+            delays,
+            [Duration::ZERO, Duration::from_millis(150), Duration::from_millis(3500), Duration::from_millis(20)]
+        );
+    }
+
+    #[test]
+    fn rejects_bad_scripts_with_the_line() {
+        assert_eq!(error("a\nNoSuchKey"), format!("line 2: {}", "NoSuchKey".parse::<Key>().unwrap_err()));
+        assert_eq!(error("hyper+a"), r#"line 1: unknown modifier "hyper" in "hyper+a""#);
+        assert_eq!(error("a\n\nwait:soon"), r#"line 3: bad delay "wait:soon""#);
+        assert_eq!(error(r#""open"#), "line 1: unterminated quoted text");
+        assert_eq!(error(r#""end\"#), "line 1: unterminated escape");
+        assert_eq!(error(r#""café""#), "line 1: cannot type 'é'");
+    }
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("250ms"), Some(Duration::from_millis(250)));
+        assert_eq!(parse_duration("2s"), Some(Duration::from_secs(2)));
+        assert_eq!(parse_duration("0.25s"), Some(Duration::from_millis(250)));
+        assert_eq!(parse_duration("40"), Some(Duration::from_millis(40)));
+        for bad in ["", "ms", "-1ms", "-1s", "NaNs", "infs", "1e30s", "1m", "fast"] {
+            assert_eq!(parse_duration(bad), None, "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn reports_unread_events() {
+        let backend = ScriptedBackend::parse("a b").unwrap();
+        assert_eq!(backend.remaining(), 2);
+        assert!(!backend.is_consumed());
+        let err = std::panic::catch_unwind(|| backend.assert_consumed()).unwrap_err();
+        assert_eq!(err.downcast_ref::<String>().unwrap(), "2 scripted key event(s) not consumed: KeyA KeyB");
+    }
+}
@@ -0,0 +1,68 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// When a key event happened.
+///
+/// `monotonic` is measured on CLOCK_MONOTONIC (on Unix; a process-local monotonic clock
+/// elsewhere), so events from different devices can be ordered and subtracted. For evdev it
+/// is the kernel's event time; other backends stamp the event when they read it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Timestamp {
+    pub monotonic: Duration,
+    pub wall: Option<SystemTime>,
+    /// Delay between the event time and delivery to the caller, when the backend knows the
+    /// event time independently of reading it.
+    pub latency: Option<Duration>,
+}
+
+impl Timestamp {
+    pub fn now() -> Timestamp {
+        Timestamp { monotonic: monotonic_now(), wall: Some(SystemTime::now()), latency: None }
+    }
+
+    /// A timestamp for an event that happened at `monotonic`, deriving wall-clock time from
+    /// the current offset between the two clocks.
+    pub fn from_monotonic(monotonic: Duration) -> Timestamp {
+        let age = monotonic_now().saturating_sub(monotonic);
+        Timestamp { monotonic, wall: SystemTime::now().checked_sub(age), latency: None }
+    }
+
+    /// A timestamp for an event that happened at wall-clock time `wall`.
+    pub fn from_wall(wall: SystemTime) -> Timestamp {
+        let age = SystemTime::now().duration_since(wall).unwrap_or_default();
+        Timestamp { monotonic: monotonic_now().saturating_sub(age), wall: Some(wall), latency: None }
+    }
+
+    /// Records delivery to the caller now, filling in `latency`.
+    pub(crate) fn delivered(mut self) -> Timestamp {
+        self.latency = Some(monotonic_now().saturating_sub(self.monotonic));
+        self
+    }
+}
+
+impl Default for Timestamp {
+    fn default() -> Timestamp {
+        Timestamp { monotonic: Duration::ZERO, wall: None, latency: None }
+    }
+}
+
+#[cfg(unix)]
+pub(crate) fn monotonic_now() -> Duration {
+    let mut ts: libc::timespec = unsafe { std::mem::zeroed() };
+    unsafe {
+        libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts);
+    }
+    Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+}
+
+#[cfg(not(unix))]
+pub(crate) fn monotonic_now() -> Duration {
+    use std::sync::OnceLock;
+    use std::time::Instant;
+
+    static BASE: OnceLock<Instant> = OnceLock::new();
+    BASE.get_or_init(Instant::now).elapsed()
+}
+
+pub(crate) fn since_epoch(time: SystemTime) -> Duration {
+    time.duration_since(UNIX_EPOCH).unwrap_or_default()
+}